@@ -0,0 +1,2 @@
+pub mod ranged;
+pub mod squad;
@@ -0,0 +1,203 @@
+use crate::constants::*;
+use crate::defence::threat::*;
+use crate::util::*;
+use log::*;
+use screeps::constants::find::*;
+use screeps::constants::*;
+use screeps::{prelude::*, Creep, Part, Position, ReturnCode};
+
+// ranged_mass_attackの1partあたりのdamage(距離1:10, 2:4, 3:1).
+pub fn calc_mass_attack_damage(range: u32) -> u32 {
+    match range {
+        0 | 1 => RANGED_ATTACK_POWER,
+        2 => RANGED_ATTACK_POWER * 4 / 10,
+        3 => RANGED_ATTACK_POWER / 10,
+        _ => 0,
+    }
+}
+
+pub fn calc_single_attack_total(ranged_parts: u32, target_range: u32) -> u32 {
+    if target_range > 3 {
+        return 0;
+    }
+
+    return ranged_parts * RANGED_ATTACK_POWER;
+}
+
+pub fn calc_mass_attack_total(ranged_parts: u32, target_ranges: &Vec<u32>) -> u32 {
+    return target_ranges
+        .iter()
+        .map(|range| ranged_parts * calc_mass_attack_damage(*range))
+        .sum();
+}
+
+// 脅威の高い順. 同じなら減っている方.
+fn select_ranged_target(enemies: &Vec<Creep>, from: &Position) -> Option<Creep> {
+    return enemies
+        .iter()
+        .filter(|enemy| enemy.pos().get_range_to(from) <= 3)
+        .max_by_key(|enemy| (assess_hostile(enemy).score, enemy.hits_max() - enemy.hits()))
+        .cloned();
+}
+
+// massは味方のcreepにも当たる.
+fn check_ally_in_range(creep: &Creep) -> bool {
+    let room = creep.room().expect("room is not visible to you");
+    let allies = get_allies();
+
+    return room.find(HOSTILE_CREEPS).iter().any(|other| {
+        other.pos().get_range_to(&creep.pos()) <= 3 && allies.contains(&other.owner_name())
+    });
+}
+
+// 単発とmassのうちdamageの大きい方で攻撃する.
+pub fn ranged_attack_best(
+    creep: &Creep,
+    enemies: &Vec<Creep>,
+    target: Option<&Creep>,
+) -> ReturnCode {
+    let ranged_parts = creep.get_active_bodyparts(Part::RangedAttack);
+    if ranged_parts <= 0 {
+        return ReturnCode::NoBodypart;
+    }
+
+    let target_ranges: Vec<u32> = enemies
+        .iter()
+        .map(|enemy| enemy.pos().get_range_to(&creep.pos()))
+        .filter(|range| *range <= 3)
+        .collect();
+
+    let single_total = match target {
+        Some(target) => {
+            calc_single_attack_total(ranged_parts, target.pos().get_range_to(&creep.pos()))
+        }
+        None => 0,
+    };
+    let mass_total = calc_mass_attack_total(ranged_parts, &target_ranges);
+
+    debug!(
+        "ranged attack {}: single:{}, mass:{}",
+        creep.name(),
+        single_total,
+        mass_total
+    );
+
+    if mass_total > single_total && check_ally_in_range(creep) == false {
+        return creep.ranged_mass_attack();
+    }
+
+    match target {
+        Some(target) => {
+            return creep.ranged_attack(target);
+        }
+        None => {}
+    }
+
+    return ReturnCode::NotInRange;
+}
+
+fn check_melee_enemy(enemy: &Creep) -> bool {
+    return enemy.get_active_bodyparts(Part::Attack) > 0;
+}
+
+// 近接の敵とはRANGED_KITING_RANGEを保ち、射程内の敵を撃つ.
+// 行動した場合はtrueを返す.
+pub fn run_ranged_combat(creep: &Creep) -> bool {
+    let enemies = get_hostile_creeps(&creep.room().expect("room is not visible to you"));
+
+    if enemies.len() <= 0 {
+        return false;
+    }
+
+    let target = select_ranged_target(&enemies, &creep.pos());
+    let r = ranged_attack_best(creep, &enemies, target.as_ref());
+    if r == ReturnCode::Ok {
+        info!("ranged attack to enemy!!");
+    }
+
+    // 近接の敵が近ければ下がる.
+    let melee_enemies: Vec<Creep> = enemies
+        .iter()
+        .filter(|enemy| check_melee_enemy(enemy))
+        .filter(|enemy| enemy.pos().get_range_to(&creep.pos()) < RANGED_KITING_RANGE)
+        .cloned()
+        .collect();
+
+    if melee_enemies.len() > 0 {
+        let res = find_flee_path_from_enemies(creep, &melee_enemies, RANGED_KITING_RANGE);
+
+        if res.load_local_path().len() > 0 {
+            let res = creep.move_by_path_search_result(&res);
+            info!("kiting from enemy: {:?}", res);
+        }
+        return true;
+    }
+
+    if target.is_some() {
+        return true;
+    }
+
+    // 射程外なら射程ぎりぎりまで寄る.
+    let res = find_nearest_enemy(creep, RANGED_KITING_RANGE);
+
+    if res.load_local_path().len() > 0 {
+        let res = creep.move_by_path_search_result(&res);
+        if res == ReturnCode::Ok {
+            info!("move to enemy: {:?}", res);
+            return true;
+        }
+    }
+
+    return r == ReturnCode::Ok;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn mass_attack_falloff() {
+        assert_eq!(calc_mass_attack_damage(0), 10);
+        assert_eq!(calc_mass_attack_damage(1), 10);
+        assert_eq!(calc_mass_attack_damage(2), 4);
+        assert_eq!(calc_mass_attack_damage(3), 1);
+        assert_eq!(calc_mass_attack_damage(4), 0);
+    }
+
+    #[test]
+    fn single_attack_range() {
+        assert_eq!(calc_single_attack_total(5, 1), 50);
+        assert_eq!(calc_single_attack_total(5, 3), 50);
+        assert_eq!(calc_single_attack_total(5, 4), 0);
+    }
+
+    #[test]
+    fn mass_attack_total() {
+        assert_eq!(calc_mass_attack_total(5, &vec![1, 2, 3]), 75);
+        assert_eq!(calc_mass_attack_total(5, &vec![4, 5]), 0);
+        assert_eq!(calc_mass_attack_total(5, &Vec::new()), 0);
+    }
+
+    #[test]
+    fn single_and_mass_crossover() {
+        // 隣接1体なら同じなので単発.
+        assert_eq!(
+            calc_mass_attack_total(5, &vec![1]),
+            calc_single_attack_total(5, 1)
+        );
+
+        // 隣接2体ならmass.
+        assert!(calc_mass_attack_total(5, &vec![1, 1]) > calc_single_attack_total(5, 1));
+
+        // 距離2は3体でmassが上回る.
+        assert!(calc_mass_attack_total(5, &vec![2, 2]) < calc_single_attack_total(5, 2));
+        assert!(calc_mass_attack_total(5, &vec![2, 2, 2]) > calc_single_attack_total(5, 2));
+
+        // 距離3は11体いないと上回らない.
+        assert_eq!(
+            calc_mass_attack_total(5, &vec![3; 10]),
+            calc_single_attack_total(5, 3)
+        );
+        assert!(calc_mass_attack_total(5, &vec![3; 11]) > calc_single_attack_total(5, 3));
+    }
+}
@@ -0,0 +1,880 @@
+use crate::attack::ranged::*;
+use crate::constants::*;
+use crate::defence::threat::*;
+use crate::manage::boost::*;
+use crate::route::*;
+use crate::util::*;
+use log::*;
+use screeps::constants::find::*;
+use screeps::{
+    game, prelude::*, Creep, LookResult, Part, Position, ReturnCode, Room, RoomName, Structure,
+    StructureType, Terrain,
+};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+#[derive(PartialEq, Eq, Clone, Copy, Debug, Serialize, Deserialize)]
+pub enum SquadRole {
+    MELEE,
+    RANGED,
+    HEALER,
+    // power bank用. ATTACKだけ.
+    BREAKER,
+}
+
+#[derive(PartialEq, Eq, Clone, Copy, Debug, Serialize, Deserialize)]
+pub enum Formation {
+    DUO,
+    QUAD,
+    POWER,
+}
+
+#[derive(PartialEq, Eq, Clone, Copy, Debug, Serialize, Deserialize)]
+pub enum SquadState {
+    FORMING,
+    MOVING,
+    ENGAGED,
+    RETREAT,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Squad {
+    pub home: String,
+    pub target_room: String,
+    pub formation: Formation,
+    pub state: SquadState,
+    // creep名とrole.
+    pub members: Vec<(String, SquadRole)>,
+    // flag以外で作ったsquadの攻撃対象.
+    #[serde(default)]
+    pub target_id: Option<String>,
+}
+
+pub enum SquadTarget {
+    CREEP(Creep),
+    STRUCTURE(Structure),
+}
+
+type SquadMap = HashMap<String, Squad>;
+
+pub fn load_squads() -> SquadMap {
+    let json_str = screeps::memory::root()
+        .string("squads")
+        .unwrap_or(None)
+        .unwrap_or(String::from("{}"));
+
+    return serde_json::from_str(json_str.as_str()).unwrap_or(HashMap::new());
+}
+
+fn save_squads(squad_map: &SquadMap) {
+    let json_str = serde_json::to_string(squad_map).unwrap();
+    screeps::memory::root().set("squads", json_str);
+}
+
+pub fn get_squad_composition(formation: Formation) -> Vec<SquadRole> {
+    match formation {
+        Formation::DUO => vec![SquadRole::RANGED, SquadRole::HEALER],
+        Formation::QUAD => vec![
+            SquadRole::MELEE,
+            SquadRole::RANGED,
+            SquadRole::HEALER,
+            SquadRole::HEALER,
+        ],
+        Formation::POWER => vec![SquadRole::BREAKER, SquadRole::HEALER],
+    }
+}
+
+// 足りないroleを1つ返す.
+pub fn find_missing_role(squad: &Squad) -> Option<SquadRole> {
+    let mut needed = get_squad_composition(squad.formation);
+
+    for (_name, role) in squad.members.iter() {
+        if let Some(index) = needed.iter().position(|r| r == role) {
+            needed.remove(index);
+        }
+    }
+
+    return needed.first().cloned();
+}
+
+pub fn make_squad_body(role: SquadRole, energy_capacity: u32) -> Vec<Part> {
+    let body_unit = match role {
+        SquadRole::MELEE => vec![Part::Tough, Part::Attack, Part::Move, Part::Move],
+        SquadRole::RANGED => vec![Part::RangedAttack, Part::Move],
+        SquadRole::HEALER => vec![Part::Heal, Part::Move],
+        SquadRole::BREAKER => vec![Part::Attack, Part::Move],
+    };
+    let body_cost: u32 = body_unit.iter().map(|p| p.cost()).sum();
+
+    // 反射ダメージを回復役が受けきれる数まで.
+    let max_set = match role {
+        SquadRole::BREAKER => std::cmp::min(SQUAD_MAX_BODY_SET, POWER_BREAKER_MAX_SET),
+        _ => SQUAD_MAX_BODY_SET,
+    };
+
+    let mut body = Vec::new();
+    let mut cost = 0;
+    let mut set_num = 0;
+
+    while set_num < max_set
+        && cost + body_cost <= energy_capacity
+        && ((body.len() + body_unit.len()) <= screeps::constants::MAX_CREEP_SIZE as usize)
+    {
+        body.extend(body_unit.iter().cloned());
+        cost += body_cost;
+        set_num += 1;
+    }
+
+    // TOUGHは前に並べる.
+    body.sort_by_key(|part| *part != Part::Tough);
+
+    return body;
+}
+
+// 湧いたcreepをsquad_idを見てsquadに登録する.
+fn collect_squad_members(squad_map: &mut SquadMap) {
+    for creep in game::creeps::values() {
+        let squad_id = match creep.memory().string("squad_id").unwrap_or(None) {
+            Some(squad_id) => squad_id,
+            None => continue,
+        };
+
+        let role: SquadRole = match creep
+            .memory()
+            .string("squad_role")
+            .unwrap_or(None)
+            .and_then(|role| serde_json::from_str(role.as_str()).ok())
+        {
+            Some(role) => role,
+            None => continue,
+        };
+
+        let squad = match squad_map.get_mut(&squad_id) {
+            Some(squad) => squad,
+            None => {
+                // squadが無くなっていればrecycle.
+                creep.memory().del("squad_id");
+                creep.memory().set("recycling", true);
+                continue;
+            }
+        };
+
+        if squad
+            .members
+            .iter()
+            .any(|(name, _role)| *name == creep.name())
+            == false
+        {
+            info!("squad {} joined: {} {:?}", squad_id, creep.name(), role);
+            squad.members.push((creep.name(), role));
+        }
+    }
+}
+
+fn find_nearest_home(room_name: RoomName) -> Option<String> {
+    let mut nearest: Option<(u32, String)> = None;
+
+    for spawn in game::spawns::values() {
+        let home = spawn.room().expect("room is not visible to you").name();
+        let distance = game::map::get_room_linear_distance(home, room_name, false);
+
+        let is_nearer = match &nearest {
+            Some((nearest_distance, _home)) => distance < *nearest_distance,
+            None => true,
+        };
+
+        if is_nearer {
+            nearest = Some((distance, home.to_string()));
+        }
+    }
+
+    return nearest.map(|(_distance, home)| home);
+}
+
+// "squad_duo_xxx", "squad_quad_xxx"という名前のflagでsquadを作る.
+// flagが消えたら撤退させる.
+fn sync_squad_flags(squad_map: &mut SquadMap) {
+    let mut flag_names = Vec::new();
+
+    for flag in game::flags::values() {
+        let name = flag.name();
+
+        let formation = if name.starts_with("squad_duo") {
+            Formation::DUO
+        } else if name.starts_with("squad_quad") {
+            Formation::QUAD
+        } else {
+            continue;
+        };
+
+        flag_names.push(name.clone());
+
+        match squad_map.get_mut(&name) {
+            Some(squad) => {
+                squad.target_room = flag.pos().room_name().to_string();
+            }
+            None => {
+                let home = match find_nearest_home(flag.pos().room_name()) {
+                    Some(home) => home,
+                    None => continue,
+                };
+
+                info!("new squad: {}, {:?} from {}", name, formation, home);
+                squad_map.insert(
+                    name,
+                    Squad {
+                        home: home,
+                        target_room: flag.pos().room_name().to_string(),
+                        formation: formation,
+                        state: SquadState::FORMING,
+                        members: Vec::new(),
+                        target_id: None,
+                    },
+                );
+            }
+        }
+    }
+
+    for (squad_id, squad) in squad_map.iter_mut() {
+        // flagで作ったsquadだけ.
+        if squad_id.starts_with("squad_") == false {
+            continue;
+        }
+
+        if flag_names.contains(squad_id) == false && squad.state != SquadState::RETREAT {
+            info!("squad flag removed: {}", squad_id);
+            squad.state = SquadState::RETREAT;
+        }
+    }
+}
+
+fn get_members(squad: &Squad) -> Vec<(Creep, SquadRole)> {
+    let mut members = Vec::new();
+
+    for (name, role) in squad.members.iter() {
+        if let Some(creep) = game::creeps::get(name) {
+            members.push((creep, *role));
+        }
+    }
+
+    return members;
+}
+
+// 先頭は攻撃役. 回復役は後ろにつく.
+fn get_leader(members: &Vec<(Creep, SquadRole)>) -> Option<Creep> {
+    for (creep, role) in members.iter() {
+        if *role != SquadRole::HEALER {
+            return Some(creep.clone());
+        }
+    }
+
+    return members.first().map(|(creep, _role)| creep.clone());
+}
+
+fn check_room_edge(pos: &Position) -> bool {
+    return pos.x() == 0 || pos.y() == 0 || pos.x() == 49 || pos.y() == 49;
+}
+
+fn move_creep_by_path(creep: &Creep, res: &screeps::pathfinder::SearchResults) {
+    if res.load_local_path().len() > 0 {
+        let r = creep.move_by_path_search_result(res);
+        if r != ReturnCode::Ok {
+            debug!("couldn't move squad member: {:?}", r);
+        }
+    }
+}
+
+// QUADはleaderを角にした2x2. leaderが左上, 右上, 左下, 右下の順に試す.
+const QUAD_ANCHORS: [(i32, i32); 4] = [(0, 0), (-1, 0), (0, -1), (-1, -1)];
+
+// leader以外のmemberの位置を返す. 2x2が入らなければNone.
+pub fn find_quad_slots<F>(leader_x: u32, leader_y: u32, is_free: F) -> Option<Vec<(u32, u32)>>
+where
+    F: Fn(u32, u32) -> bool,
+{
+    for (ax, ay) in QUAD_ANCHORS.iter() {
+        let left = leader_x as i32 + ax;
+        let top = leader_y as i32 + ay;
+
+        // 出口のマスには置かない.
+        if left < 1 || top < 1 || left + 1 > 48 || top + 1 > 48 {
+            continue;
+        }
+
+        let mut slots = Vec::new();
+        for (dx, dy) in [(0, 0), (1, 0), (0, 1), (1, 1)].iter() {
+            let x = (left + dx) as u32;
+            let y = (top + dy) as u32;
+            if x == leader_x && y == leader_y {
+                continue;
+            }
+            slots.push((x, y));
+        }
+
+        if slots.iter().all(|(x, y)| is_free(*x, *y)) {
+            return Some(slots);
+        }
+    }
+
+    return None;
+}
+
+// 壁と通れない建物, squad以外のcreepがいるマスは使えない.
+fn check_quad_tile(room: &Room, x: u32, y: u32, members: &Vec<(Creep, SquadRole)>) -> bool {
+    for object in room.look_at_xy(x, y) {
+        match object {
+            LookResult::Terrain(terrain) => {
+                if terrain == Terrain::Wall {
+                    return false;
+                }
+            }
+
+            LookResult::Structure(structure) => match structure.structure_type() {
+                StructureType::Road | StructureType::Container => {}
+                StructureType::Rampart => {
+                    if check_my_structure(&structure) == false {
+                        return false;
+                    }
+                }
+                _ => return false,
+            },
+
+            LookResult::Creep(creep) => {
+                if members
+                    .iter()
+                    .any(|(member, _role)| member.name() == creep.name())
+                    == false
+                {
+                    return false;
+                }
+            }
+
+            _ => {}
+        }
+    }
+
+    return true;
+}
+
+// 揃っていればleaderと同じ方向に1歩. 揃っていなければ自分の位置へ向かう.
+// 2x2が入らない場所では1列でついていく.
+fn move_in_quad(
+    members: &Vec<(Creep, SquadRole)>,
+    leader: &Creep,
+    res: &screeps::pathfinder::SearchResults,
+) {
+    let leader_pos = leader.pos();
+    let room = leader.room().expect("room is not visible to you");
+
+    let slots = match find_quad_slots(leader_pos.x(), leader_pos.y(), |x, y| {
+        check_quad_tile(&room, x, y, members)
+    }) {
+        Some(slots) => slots,
+        None => {
+            move_in_line(members, leader, res);
+            return;
+        }
+    };
+
+    let followers: Vec<&Creep> = members
+        .iter()
+        .map(|(creep, _role)| creep)
+        .filter(|creep| creep.name() != leader.name())
+        .collect();
+
+    let get_slot = |index: usize| {
+        let (x, y) = slots[index % slots.len()];
+        Position::new(x, y, leader_pos.room_name())
+    };
+
+    let is_gathered = followers.iter().enumerate().all(|(index, creep)| {
+        creep.pos().room_name() != leader_pos.room_name() || creep.pos() == get_slot(index)
+    });
+
+    if is_gathered == false && check_room_edge(&leader_pos) == false {
+        for (index, creep) in followers.iter().enumerate() {
+            let slot = get_slot(index);
+            if creep.pos() == slot {
+                continue;
+            }
+
+            let res = find_route_path_between(&creep.pos(), &slot, 0);
+            move_creep_by_path(creep, &res);
+        }
+        return;
+    }
+
+    move_creep_by_path(leader, res);
+
+    let direction = res
+        .load_local_path()
+        .first()
+        .filter(|next| next.room_name() == leader_pos.room_name())
+        .and_then(|next| leader_pos.get_direction_to(next));
+
+    for creep in followers.iter() {
+        match direction {
+            Some(direction) if creep.pos().room_name() == leader_pos.room_name() => {
+                let r = creep.move_direction(direction);
+                if r != ReturnCode::Ok {
+                    debug!("couldn't move squad member: {:?}", r);
+                }
+            }
+            _ => {
+                let res = find_route_path_between(&creep.pos(), &leader_pos, 1);
+                move_creep_by_path(creep, &res);
+            }
+        }
+    }
+}
+
+// leaderは全員が揃っている時だけ進む. 部屋の境目では待たない.
+fn move_in_line(
+    members: &Vec<(Creep, SquadRole)>,
+    leader: &Creep,
+    res: &screeps::pathfinder::SearchResults,
+) {
+    let is_gathered = members.iter().all(|(creep, _role)| {
+        creep.pos().room_name() != leader.pos().room_name()
+            || creep.pos().get_range_to(&leader.pos()) <= 1
+    });
+
+    if is_gathered || check_room_edge(&leader.pos()) {
+        move_creep_by_path(leader, res);
+    }
+
+    for (creep, _role) in members.iter() {
+        if creep.name() == leader.name() {
+            continue;
+        }
+
+        if creep.pos().get_range_to(&leader.pos()) <= 1 && is_gathered {
+            // leaderについていく.
+            let _r = creep.move_to(&leader.pos());
+            continue;
+        }
+
+        let res = find_route_path_between(&creep.pos(), &leader.pos(), 1);
+        move_creep_by_path(creep, &res);
+    }
+}
+
+pub fn move_in_formation(
+    members: &Vec<(Creep, SquadRole)>,
+    leader: &Creep,
+    res: &screeps::pathfinder::SearchResults,
+    formation: Formation,
+) {
+    if formation == Formation::QUAD {
+        move_in_quad(members, leader, res);
+        return;
+    }
+
+    move_in_line(members, leader, res);
+}
+
+// 火力の高い敵creep, なければ指定された対象, tower/spawnなどの建物.
+pub fn select_squad_target(
+    room: &Room,
+    from: &Position,
+    target_id: &Option<String>,
+) -> Option<SquadTarget> {
+    let enemy = get_ranked_hostiles(room)
+        .into_iter()
+        .max_by_key(|hostile| {
+            (
+                hostile.score,
+                -(hostile.creep.pos().get_range_to(from) as i32),
+            )
+        })
+        .map(|hostile| hostile.creep);
+
+    if let Some(enemy) = enemy {
+        return Some(SquadTarget::CREEP(enemy));
+    }
+
+    if let Some(target_id) = target_id {
+        let target = room
+            .find(STRUCTURES)
+            .into_iter()
+            .find(|s| s.id().to_string() == *target_id);
+
+        if let Some(target) = target {
+            return Some(SquadTarget::STRUCTURE(target));
+        }
+    }
+
+    let structure = room
+        .find(STRUCTURES)
+        .into_iter()
+        .filter(|s| {
+            s.as_owned()
+                .map(|o| o.my() == false && o.owner_name().is_some())
+                .unwrap_or(false)
+        })
+        .min_by_key(|s| {
+            let type_order = match s {
+                Structure::Tower(_) => 0,
+                Structure::Spawn(_) => 1,
+                _ => 2,
+            };
+            (type_order, s.pos().get_range_to(from))
+        });
+
+    return structure.map(|s| SquadTarget::STRUCTURE(s));
+}
+
+fn get_target_pos(target: &SquadTarget) -> Position {
+    match target {
+        SquadTarget::CREEP(creep) => creep.pos(),
+        SquadTarget::STRUCTURE(structure) => structure.pos(),
+    }
+}
+
+fn attack_target(creep: &Creep, role: SquadRole, target: &SquadTarget) {
+    let r = match (role, target) {
+        (SquadRole::MELEE, SquadTarget::CREEP(enemy))
+        | (SquadRole::BREAKER, SquadTarget::CREEP(enemy)) => creep.attack(enemy),
+        (SquadRole::RANGED, SquadTarget::CREEP(enemy)) => {
+            let enemies = get_hostile_creeps(&creep.room().expect("room is not visible to you"));
+            ranged_attack_best(creep, &enemies, Some(enemy))
+        }
+        (SquadRole::MELEE, SquadTarget::STRUCTURE(structure))
+        | (SquadRole::BREAKER, SquadTarget::STRUCTURE(structure)) => {
+            match structure.as_attackable() {
+                Some(attackable) => creep.attack(attackable),
+                None => ReturnCode::InvalidTarget,
+            }
+        }
+        (SquadRole::RANGED, SquadTarget::STRUCTURE(structure)) => match structure.as_attackable() {
+            Some(attackable) => creep.ranged_attack(attackable),
+            None => ReturnCode::InvalidTarget,
+        },
+        _ => ReturnCode::Ok,
+    };
+
+    if r != ReturnCode::Ok && r != ReturnCode::NotInRange {
+        debug!("couldn't attack: {:?}", r);
+    }
+}
+
+// 一番減っているmemberを回復する.
+pub fn heal_members(members: &Vec<(Creep, SquadRole)>) {
+    for (healer, role) in members.iter() {
+        if *role != SquadRole::HEALER {
+            continue;
+        }
+
+        let patient = members
+            .iter()
+            .map(|(creep, _role)| creep)
+            .filter(|creep| creep.pos().room_name() == healer.pos().room_name())
+            .min_by_key(|creep| (creep.hits() * 100) / std::cmp::max(creep.hits_max(), 1));
+
+        let patient = match patient {
+            Some(patient) => patient,
+            None => continue,
+        };
+
+        let range = healer.pos().get_range_to(&patient.pos());
+        let r = if range <= 1 {
+            healer.heal(patient)
+        } else if range <= 3 {
+            healer.ranged_heal(patient)
+        } else {
+            ReturnCode::NotInRange
+        };
+
+        if r != ReturnCode::Ok {
+            debug!("couldn't heal: {:?}", r);
+        }
+    }
+}
+
+fn calc_squad_hp_rate(members: &Vec<(Creep, SquadRole)>) -> f64 {
+    let hits: u32 = members.iter().map(|(creep, _role)| creep.hits()).sum();
+    let hits_max: u32 = members.iter().map(|(creep, _role)| creep.hits_max()).sum();
+
+    if hits_max <= 0 {
+        return 0 as f64;
+    }
+
+    return hits as f64 / hits_max as f64;
+}
+
+fn calc_squad_heal(members: &Vec<(Creep, SquadRole)>) -> u32 {
+    return members
+        .iter()
+        .map(|(creep, _role)| creep.get_active_bodyparts(Part::Heal) * HEAL_POWER)
+        .sum();
+}
+
+// memberの周りにいる敵の火力.
+fn calc_incoming_damage(members: &Vec<(Creep, SquadRole)>) -> u32 {
+    let mut damage = 0;
+    let mut counted = Vec::new();
+
+    for (creep, _role) in members.iter() {
+        let room = creep.room().expect("room is not visible to you");
+
+        for hostile in get_dangerous_hostiles(&room) {
+            let enemy = hostile.creep;
+            if enemy.pos().get_range_to(&creep.pos()) > 3 || counted.contains(&enemy.name()) {
+                continue;
+            }
+
+            damage += hostile.damage;
+            counted.push(enemy.name());
+        }
+    }
+
+    return damage;
+}
+
+fn check_retreat(members: &Vec<(Creep, SquadRole)>) -> bool {
+    let hp_rate = calc_squad_hp_rate(members);
+    let incoming = calc_incoming_damage(members);
+    let heal = calc_squad_heal(members);
+
+    if hp_rate < SQUAD_RETREAT_HP_RATE {
+        info!("squad retreat: hp:{}", hp_rate);
+        return true;
+    }
+
+    if incoming as f64 > heal as f64 * SQUAD_RETREAT_DAMAGE_RATE {
+        info!("squad retreat: incoming:{}, heal:{}", incoming, heal);
+        return true;
+    }
+
+    return false;
+}
+
+// 対象がいなくなったらfalse.
+fn run_squad_engaged(members: &Vec<(Creep, SquadRole)>, leader: &Creep, squad: &Squad) -> bool {
+    let room = leader.room().expect("room is not visible to you");
+
+    match select_squad_target(&room, &leader.pos(), &squad.target_id) {
+        Some(target) => {
+            let target_pos = get_target_pos(&target);
+
+            for (creep, role) in members.iter() {
+                attack_target(creep, *role, &target);
+            }
+
+            let range = if members
+                .iter()
+                .any(|(_c, r)| *r == SquadRole::MELEE || *r == SquadRole::BREAKER)
+            {
+                1
+            } else {
+                3
+            };
+
+            let res = find_path(leader, &target_pos, range);
+            move_in_formation(members, leader, &res, squad.formation);
+            return true;
+        }
+        None => {
+            debug!("squad has no target in {:?}", room.name());
+            return false;
+        }
+    }
+}
+
+fn run_squad(squad_id: &str, squad: &mut Squad) -> bool {
+    let members = get_members(squad);
+
+    // 死んだmemberは外す.
+    squad
+        .members
+        .retain(|(name, _role)| members.iter().any(|(creep, _r)| creep.name() == *name));
+
+    let leader = match get_leader(&members) {
+        Some(leader) => leader,
+        None => {
+            if squad.state == SquadState::FORMING {
+                return true;
+            }
+            info!("squad is wiped out: {}", squad_id);
+            return false;
+        }
+    };
+
+    if members.iter().any(|(creep, _role)| creep.spawning()) {
+        return true;
+    }
+
+    heal_members(&members);
+
+    match squad.state {
+        SquadState::FORMING => {
+            // boost中のmemberはcreeps::boostで動かすので、終わるまで待つ.
+            let is_boosting = members
+                .iter()
+                .any(|(creep, _role)| get_boost_request(creep).len() > 0);
+
+            if find_missing_role(squad).is_none() && is_boosting == false {
+                info!("squad formed: {}", squad_id);
+                squad.state = SquadState::MOVING;
+            }
+        }
+
+        SquadState::MOVING => {
+            if leader
+                .room()
+                .expect("room is not visible to you")
+                .name()
+                .to_string()
+                == squad.target_room
+            {
+                squad.state = SquadState::ENGAGED;
+            } else {
+                let target_room: RoomName = match squad.target_room.parse() {
+                    Ok(target_room) => target_room,
+                    Err(_err) => return false,
+                };
+                let res = find_path_to_room(&leader, target_room);
+                move_in_formation(&members, &leader, &res, squad.formation);
+            }
+
+            if check_retreat(&members) {
+                squad.state = SquadState::RETREAT;
+            }
+        }
+
+        SquadState::ENGAGED => {
+            if check_retreat(&members) {
+                squad.state = SquadState::RETREAT;
+            } else if run_squad_engaged(&members, &leader, squad) == false {
+                // flagのsquadは対象が出るまでflagの位置で待つ.
+                match game::flags::get(squad_id) {
+                    Some(flag) => {
+                        let res = find_path(&leader, &flag.pos(), 1);
+                        move_in_formation(&members, &leader, &res, squad.formation);
+                    }
+                    None => {
+                        squad.state = SquadState::RETREAT;
+                    }
+                }
+            }
+        }
+
+        SquadState::RETREAT => {
+            let home: RoomName = match squad.home.parse() {
+                Ok(home) => home,
+                Err(_err) => return false,
+            };
+
+            let is_home = leader.room().expect("room is not visible to you").name() == home;
+            let is_flag_alive = game::flags::get(squad_id).is_some();
+
+            if is_home == false {
+                let res = find_path_to_room(&leader, home);
+                move_in_formation(&members, &leader, &res, squad.formation);
+            } else if is_flag_alive == false {
+                // 解散してrecycleする.
+                info!("squad disbanded: {}", squad_id);
+                for (creep, _role) in members.iter() {
+                    creep.memory().del("squad_id");
+                    creep.memory().set("recycling", true);
+                }
+                return false;
+            } else if calc_squad_hp_rate(&members) >= SQUAD_REENGAGE_HP_RATE {
+                squad.state = SquadState::MOVING;
+            }
+        }
+    }
+
+    return true;
+}
+
+// flag以外から作る. 既にあれば何もしない.
+pub fn add_squad(squad_id: &str, squad: Squad) {
+    let mut squad_map = load_squads();
+
+    if squad_map.contains_key(squad_id) {
+        return;
+    }
+
+    info!(
+        "new squad: {}, {:?} from {}",
+        squad_id, squad.formation, squad.home
+    );
+    squad_map.insert(squad_id.to_string(), squad);
+    save_squads(&squad_map);
+}
+
+// 撤退させる. homeに着いたら解散する.
+pub fn retreat_squad(squad_id: &str) {
+    let mut squad_map = load_squads();
+
+    match squad_map.get_mut(squad_id) {
+        Some(squad) => {
+            if squad.state == SquadState::RETREAT {
+                return;
+            }
+            info!("squad retreat: {}", squad_id);
+            squad.state = SquadState::RETREAT;
+        }
+        None => return,
+    }
+
+    save_squads(&squad_map);
+}
+
+pub fn run_squads() {
+    let mut squad_map = load_squads();
+
+    sync_squad_flags(&mut squad_map);
+    collect_squad_members(&mut squad_map);
+
+    let mut finished = Vec::new();
+    for (squad_id, squad) in squad_map.iter_mut() {
+        debug!("running squad {}: {:?}", squad_id, squad.state);
+
+        if run_squad(squad_id.as_str(), squad) == false {
+            finished.push(squad_id.clone());
+        }
+    }
+
+    for squad_id in finished {
+        squad_map.remove(&squad_id);
+    }
+
+    save_squads(&squad_map);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn quad_slots_open_field() {
+        let slots = find_quad_slots(10, 10, |_x, _y| true).unwrap();
+        assert_eq!(slots, vec![(11, 10), (10, 11), (11, 11)]);
+    }
+
+    #[test]
+    fn quad_slots_avoid_walls() {
+        // 右側が壁ならleaderを右上にする.
+        let slots = find_quad_slots(10, 10, |x, _y| x != 11).unwrap();
+        assert_eq!(slots, vec![(9, 10), (9, 11), (10, 11)]);
+
+        // 左下と右下が塞がっていれば上に組む.
+        let slots =
+            find_quad_slots(10, 10, |x, y| (x, y) != (11, 11) && (x, y) != (9, 11)).unwrap();
+        assert_eq!(slots, vec![(10, 9), (11, 9), (11, 10)]);
+    }
+
+    #[test]
+    fn quad_slots_skip_exits() {
+        let slots = find_quad_slots(48, 48, |_x, _y| true).unwrap();
+        assert_eq!(slots, vec![(47, 47), (48, 47), (47, 48)]);
+    }
+
+    #[test]
+    fn quad_slots_none_in_corridor() {
+        // 1マス幅の通路では組めない.
+        assert!(find_quad_slots(10, 10, |x, _y| x == 10).is_none());
+    }
+}
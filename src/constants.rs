@@ -3,3 +3,214 @@ pub const TERMINAL_KEEP_ENERGY: u32 = 500;
 pub const MARKET_CUT_VALUE: f64 = 0.5 as f64;
 pub const MARKET_MIN_PRICE: f64 = 0.1 as f64;
 pub const CAP_WORKER_CARRY_COEFF: f64 = 1.5 as f64;
+pub const MAX_NUM_OF_CREEPS: u32 = 14;
+
+// renew/recycle.
+pub const RENEW_MIN_BODY_COST: u32 = 1200;
+pub const RENEW_MIN_ROOM_ENERGY: u32 = 300;
+pub const RENEW_START_TICKS: u32 = 300;
+pub const RENEW_STOP_TICKS: u32 = 1200;
+pub const RENEW_MAX_RANGE: u32 = 10;
+pub const RECYCLE_OBSOLETE_RATIO: f64 = 0.3 as f64;
+pub const DYING_DEPOSIT_TICKS: u32 = 30;
+
+// safe mode.
+pub const SAFE_MODE_CRITICAL_HP_RATE: f64 = 0.5 as f64;
+pub const SAFE_MODE_CRITICAL_RANGE: u32 = 3;
+
+// room economy.
+pub const ECONOMY_MIN_HAULERS: u32 = 2;
+pub const ECONOMY_RECOVERED_CARRY_RATE: f64 = 0.5 as f64;
+pub const ECONOMY_RECOVERY_MAX_BODY_SET: u32 = 3;
+
+// task.
+pub const TASK_EXPIRE_TICKS: u32 = 50;
+pub const TASK_MAX_CREEPS_PER_SITE: u32 = 3;
+
+// static mining.
+pub const MINER_WORK_PARTS: u32 = 5;
+pub const MINER_MIN_ENERGY_CAPACITY: u32 = 550;
+pub const HAULER_ROUND_TRIP_MARGIN: u32 = 10;
+
+// source slot.
+pub const SOURCE_SLOT_REFRESH_TICKS: u32 = 1000;
+
+// remote mining.
+pub const REMOTE_MIN_RCL: u32 = 4;
+pub const REMOTE_MAX_ROOMS_PER_HOME: usize = 2;
+pub const REMOTE_RESERVE_MIN_TICKS: u32 = 1000;
+pub const REMOTE_RESERVER_MAX_CLAIM: u32 = 2;
+pub const REMOTE_ROAD_PLAN_INTERVAL: u32 = 500;
+pub const REMOTE_ROAD_MAX_SITES: u32 = 5;
+pub const REMOTE_MAX_DEFEND_DAMAGE: u32 = 300;
+pub const REMOTE_ABANDON_THREAT_TICKS: u32 = 1500;
+pub const REMOTE_ABANDON_TICKS: u32 = 20000;
+pub const REMOTE_DEFENDER_MAX_SET: u32 = 6;
+
+// expansion.
+pub const EXPANSION_MIN_HOME_RCL: u32 = 4;
+pub const EXPANSION_MIN_SOURCES: u32 = 1;
+pub const EXPANSION_MAX_DISTANCE: u32 = 5;
+pub const EXPANSION_SCORE_SOURCE: i32 = 10;
+pub const EXPANSION_SCORE_NEW_MINERAL: i32 = 5;
+pub const EXPANSION_SCORE_DISTANCE: i32 = 2;
+pub const EXPANSION_SCORE_EXIT: i32 = 1;
+pub const EXPANSION_SCORE_HOSTILE_NEIGHBOUR: i32 = 10;
+pub const EXPANSION_SPAWN_SEARCH_RANGE: u32 = 8;
+pub const EXPANSION_TIMEOUT_TICKS: u32 = 30000;
+pub const EXPANSION_RETRY_TICKS: u32 = 50000;
+pub const EXPANSION_CLAIMER_ENERGY: u32 = 650;
+pub const EXPANSION_NUM_PIONEERS: u32 = 3;
+pub const EXPANSION_PIONEER_MAX_SET: u32 = 5;
+
+// intel.
+pub const INTEL_SEGMENT_ID: u32 = 0;
+pub const INTEL_SEGMENT_MAX_SIZE: usize = 100 * 1024;
+pub const INTEL_SAVE_INTERVAL: u32 = 20;
+pub const INTEL_UPDATE_INTERVAL: u32 = 20;
+pub const INTEL_SCOUT_MIN_RCL: u32 = 3;
+pub const INTEL_NUM_SCOUTS: u32 = 1;
+pub const INTEL_SCOUT_MAX_DISTANCE: u32 = 6;
+pub const INTEL_OBSERVE_RANGE: u32 = 5;
+
+// route.
+pub const ROUTE_CACHE_TICKS: u32 = 1000;
+pub const ROUTE_COST_MY_ROOM: f64 = 1.0 as f64;
+pub const ROUTE_COST_HIGHWAY: f64 = 1.0 as f64;
+pub const ROUTE_COST_DEFAULT: f64 = 2.0 as f64;
+pub const ROUTE_COST_HOSTILE_RESERVED: f64 = 3.0 as f64;
+pub const ROUTE_COST_SOURCE_KEEPER: f64 = 10.0 as f64;
+
+// squad.
+pub const SQUAD_MAX_BODY_SET: u32 = 10;
+pub const SQUAD_RETREAT_HP_RATE: f64 = 0.5 as f64;
+pub const SQUAD_REENGAGE_HP_RATE: f64 = 0.9 as f64;
+pub const SQUAD_RETREAT_DAMAGE_RATE: f64 = 1.5 as f64;
+
+// ranged combat.
+pub const RANGED_KITING_RANGE: u32 = 3;
+
+// threat.
+pub const INVADER_USERNAME: &str = "Invader";
+pub const SOURCE_KEEPER_USERNAME: &str = "Source Keeper";
+pub const THREAT_SCORE_HEALER: u32 = 1000;
+pub const THREAT_SCORE_ATTACKER: u32 = 500;
+pub const THREAT_SCORE_DISMANTLER: u32 = 300;
+pub const THREAT_SCORE_HARVESTER: u32 = 100;
+pub const THREAT_HEAL_WEIGHT: u32 = 2;
+
+// tower.
+pub const TOWER_SIEGE_RESERVE_ENERGY: u32 = 500;
+
+// link.
+pub const LINK_STORAGE_RANGE: u32 = 2;
+pub const LINK_CONTROLLER_RANGE: u32 = 3;
+pub const LINK_SOURCE_RANGE: u32 = 2;
+pub const LINK_MIN_TRANSFER: u32 = 100;
+pub const LINK_CONTROLLER_REFILL_RATE: f64 = 0.5 as f64;
+
+// layout.
+pub const LAYOUT_EDGE_MARGIN: u32 = 2;
+pub const LAYOUT_MAX_DISTANCE: u32 = 50;
+pub const LAYOUT_CORE_DISTANCE: u32 = 3;
+pub const LAYOUT_MAX_ROOM_SITES: u32 = 5;
+pub const LAYOUT_ROAD_MIN_RCL: u32 = 3;
+pub const LAYOUT_PLACE_INTERVAL: u32 = 20;
+
+// road.
+pub const ROAD_HEAT_THRESHOLD: u32 = 30;
+pub const ROAD_UNUSED_THRESHOLD: u32 = 2;
+pub const ROAD_HEAT_DECAY_PERCENT: u32 = 50;
+pub const ROAD_DECAY_INTERVAL: u32 = 1500;
+pub const ROAD_PLAN_INTERVAL: u32 = 500;
+pub const ROAD_MIN_SAMPLE_TICKS: u32 = 3000;
+pub const ROAD_MAX_ROOM_SITES: u32 = 5;
+pub const ROAD_PLANNED_ROUTE_MIN_RCL: u32 = 2;
+pub const ROAD_MINERAL_MIN_RCL: u32 = 6;
+
+// rampart.
+pub const RAMPART_PROTECT_RANGE: u32 = 2;
+pub const RAMPART_CRITICAL_RANGE: u32 = 3;
+pub const RAMPART_MIN_RCL: u32 = 3;
+pub const RAMPART_MAX_ROOM_SITES: u32 = 5;
+pub const RAMPART_PLAN_INTERVAL: u32 = 100;
+
+// repair.
+pub const REPAIR_STORAGE_LOW_ENERGY: u32 = 20000;
+pub const REPAIR_STORAGE_NORMAL_ENERGY: u32 = 100000;
+pub const REPAIR_STORAGE_HIGH_ENERGY: u32 = 300000;
+pub const REPAIR_INFRASTRUCTURE_RATE_PERCENT: u32 = 80;
+pub const REPAIR_INFRASTRUCTURE_BUDGET: u32 = 2000;
+pub const REPAIR_FORTIFICATION_BUDGET: u32 = 5000;
+pub const REPAIR_BUDGET_INTERVAL: u32 = 100;
+pub const REPAIR_CRITICAL_RAMPART_RATE: u32 = 2;
+pub const REPAIR_TOWER_ENERGY_COST: u32 = 10;
+
+// lab.
+pub const LAB_STOCK_TARGET_AMOUNT: u32 = 3000;
+pub const LAB_BATCH_AMOUNT: u32 = 1000;
+pub const LAB_MIN_REACTION_AMOUNT: u32 = 100;
+pub const LAB_MAX_CHAIN_DEPTH: u32 = 5;
+pub const LAB_REACTION_AMOUNT: u32 = 5;
+pub const LAB_REACTION_TIMEOUT: u32 = 3000;
+pub const LAB_PLAN_INTERVAL: u32 = 10;
+pub const LAB_INPUT_FILL_AMOUNT: u32 = 1000;
+pub const LAB_OUTPUT_COLLECT_AMOUNT: u32 = 500;
+pub const LAB_HAULER_MAX_SET: u32 = 5;
+
+// boost.
+pub const LAB_BOOST_MINERAL: u32 = 30;
+pub const LAB_BOOST_ENERGY: u32 = 20;
+pub const LAB_MAX_BOOST_LABS: u32 = 3;
+pub const LAB_BOOST_FILL_AMOUNT: u32 = 1500;
+pub const LAB_BOOST_ENERGY_FILL_AMOUNT: u32 = 1000;
+pub const BOOST_MAX_WAIT_TICKS: u32 = 200;
+
+// factory.
+pub const FACTORY_PLAN_INTERVAL: u32 = 100;
+pub const FACTORY_JOB_TIMEOUT: u32 = 5000;
+pub const FACTORY_BATTERY_TARGET_AMOUNT: u32 = 10000;
+pub const FACTORY_BAR_TARGET_AMOUNT: u32 = 3000;
+pub const FACTORY_ENERGY_RESERVE_AMOUNT: u32 = 200000;
+pub const FACTORY_MINERAL_RESERVE_AMOUNT: u32 = 6000;
+pub const FACTORY_INPUT_BATCHES: u32 = 5;
+pub const FACTORY_OUTPUT_COLLECT_AMOUNT: u32 = 500;
+pub const FACTORY_MIN_PROFIT: f64 = 100 as f64;
+
+// power.
+pub const POWER_PLAN_INTERVAL: u32 = 50;
+pub const POWER_MAX_OPS: u32 = 1;
+pub const POWER_MIN_AMOUNT: u32 = 1000;
+pub const POWER_MIN_RCL: u32 = 7;
+pub const POWER_MIN_STORAGE_ENERGY: u32 = 100000;
+pub const POWER_MAX_DISTANCE: u32 = 5;
+pub const POWER_MAX_PAIRS: u32 = 3;
+pub const POWER_TICKS_PER_ROOM: u32 = 50;
+pub const POWER_INTEL_MAX_AGE: u32 = 500;
+pub const POWER_BANK_HIT_BACK_PERCENT: u32 = 50;
+pub const POWER_BREAKER_MAX_SET: u32 = 8;
+pub const POWER_HAULER_DISPATCH_HITS: u32 = 500000;
+pub const POWER_HAULER_MAX_SET: u32 = 16;
+pub const POWER_HAULER_WAIT_RANGE: u32 = 4;
+pub const POWER_HAUL_TIMEOUT: u32 = 1000;
+pub const POWER_SPAWN_POWER_FILL_AMOUNT: u32 = 100;
+pub const POWER_SPAWN_ENERGY_RATIO: u32 = 50;
+pub const POWER_SPAWN_MIN_STORAGE_ENERGY: u32 = 150000;
+
+// deposit.
+pub const DEPOSIT_PLAN_INTERVAL: u32 = 50;
+pub const DEPOSIT_MAX_EXPEDITIONS: u32 = 2;
+pub const DEPOSIT_MIN_RCL: u32 = 6;
+pub const DEPOSIT_MAX_DISTANCE: u32 = 5;
+pub const DEPOSIT_TICKS_PER_ROOM: u32 = 50;
+pub const DEPOSIT_INTEL_MAX_AGE: u32 = 1000;
+pub const DEPOSIT_START_MAX_COOLDOWN: u32 = 40;
+pub const DEPOSIT_STOP_COOLDOWN: u32 = 100;
+pub const DEPOSIT_MIN_WORKING_TICKS: u32 = 500;
+pub const DEPOSIT_MIN_YIELD: u32 = 300;
+pub const DEPOSIT_MAX_HARVESTERS: u32 = 2;
+pub const DEPOSIT_MAX_HAULERS: u32 = 3;
+pub const DEPOSIT_HARVESTER_CARRY_PARTS: u32 = 4;
+pub const DEPOSIT_HARVESTER_MAX_SET: u32 = 20;
+pub const DEPOSIT_HAULER_MAX_SET: u32 = 16;
+pub const DEPOSIT_HAULER_RETURN_MARGIN: u32 = 50;
@@ -1,4 +1,17 @@
+use crate::attack::squad::*;
 use crate::constants::*;
+use crate::creeps::hauler::*;
+use crate::creeps::miner::*;
+use crate::manage::boost::*;
+use crate::manage::deposit::*;
+use crate::manage::economy::*;
+use crate::manage::expansion::*;
+use crate::manage::factory::*;
+use crate::manage::lab::*;
+use crate::manage::power::*;
+use crate::manage::remote::*;
+use crate::route::*;
+use crate::util::*;
 use std::usize;
 
 use log::*;
@@ -6,11 +19,587 @@ use screeps::constants::find::*;
 use screeps::Structure;
 
 use screeps::{
-    find, prelude::*, Attackable, IntoExpectedType, Part, ResourceType, ReturnCode,
-    RoomObjectProperties, StructureType,
+    find, memory::MemoryReference, prelude::*, Attackable, IntoExpectedType, Part, ResourceType,
+    ReturnCode, Room, RoomObjectProperties, SpawnOptions, StructureSpawn, StructureType,
 };
 
-const MAX_NUM_OF_CREEPS: u32 = 14;
+pub fn spawn_creep_with_memory(
+    spawn: &StructureSpawn,
+    body: &Vec<Part>,
+    memory: Option<MemoryReference>,
+) -> ReturnCode {
+    // create a unique name, spawn.
+    let name_base = screeps::game::time();
+    let mut additional = 0;
+    let res = loop {
+        let name = format!("{}-{}", name_base, additional);
+        debug!("try spawn {:?}", body);
+        let res = match &memory {
+            Some(memory) => spawn.spawn_creep_with_options(
+                &body,
+                &name,
+                &SpawnOptions::new().memory(memory.clone()),
+            ),
+            None => spawn.spawn_creep(&body, &name),
+        };
+
+        if res == ReturnCode::NameExists {
+            additional += 1;
+        } else {
+            break res;
+        }
+    };
+
+    if res != ReturnCode::Ok {
+        info!("couldn't spawn: {:?}", res);
+    }
+    if res == ReturnCode::Ok {
+        info!("spawn: {:?}", body);
+    }
+
+    return res;
+}
+
+pub fn make_role_memory(role: &str) -> MemoryReference {
+    let memory = MemoryReference::new();
+    memory.set("role", role);
+    return memory;
+}
+
+// このtickに出したcreepの(role, source_idや部屋名などの対象).
+// 次のtickまでgame::creepsに出てこないので、他のspawnが二重に出さないように数える.
+type SpawnQueue = Vec<(String, String)>;
+
+fn count_queued(queued: &SpawnQueue, role: &str, target: &str) -> u32 {
+    return queued
+        .iter()
+        .filter(|(r, t)| r.as_str() == role && t.as_str() == target)
+        .count() as u32;
+}
+
+// sourceごとに専属のminerとhaulerを出す. minerは死ぬ前に交代を出しておく.
+// 出した、またはそのためにenergyを貯めている場合はtrueを返す.
+fn spawn_mining_creeps(spawn: &StructureSpawn, room: &Room, queued: &mut SpawnQueue) -> bool {
+    let energy_capacity = room.energy_capacity_available();
+
+    if energy_capacity < MINER_MIN_ENERGY_CAPACITY {
+        return false;
+    }
+
+    for source in room.find(SOURCES) {
+        let source_id = source.id().to_string();
+
+        let body = make_miner_body(energy_capacity);
+        let need_miner =
+            if count_creeps_for_source("miner", source_id.as_str()) + count_queued("miner") <= 0 {
+                true
+            } else {
+                // 交代が着くまでの時間. WORKが重いので1マスに数tickかかる.
+                let path_length = find_path_between(&spawn.pos(), &source.pos(), 1)
+                    .load_local_path()
+                    .len() as u32;
+                let replace_ticks = body.len() as u32 * screeps::constants::CREEP_SPAWN_TIME
+                    + path_length * calc_ticks_per_tile(&body);
+
+                count_lasting_creeps_for_source("miner", source_id.as_str(), replace_ticks) <= 0
+            };
+
+        if need_miner == true {
+            // 交代用のenergyを他に使わせない.
+            let body_cost: u32 = body.iter().map(|p| p.cost()).sum();
+            if room.energy_available() < body_cost {
+                return true;
+            }
+
+            let memory = make_role_memory("miner");
+            memory.set("source_id", source_id.as_str());
+            if spawn_creep_with_memory(spawn, &body, Some(memory)) == ReturnCode::Ok {
+                queued.push((String::from("miner"), source_id.clone()));
+            }
+            return true;
+        }
+
+        if count_creeps_for_source("hauler", source_id.as_str())
+            + count_queued(queued, "hauler", source_id.as_str())
+            <= 0
+        {
+            let origin = match room.storage() {
+                Some(storage) => storage.pos(),
+                None => spawn.pos(),
+            };
+            let path_length = find_path_between(&origin, &source.pos(), 1)
+                .load_local_path()
+                .len() as u32;
+
+            let carry_parts = calc_hauler_carry_parts(path_length);
+            let body = make_hauler_body(carry_parts, energy_capacity);
+            let body_cost: u32 = body.iter().map(|p| p.cost()).sum();
+
+            info!(
+                "hauler for {:?}: path:{:?}, carry:{:?}",
+                source.pos(),
+                path_length,
+                carry_parts
+            );
+
+            if body.len() <= 0 || room.energy_available() < body_cost {
+                return false;
+            }
+
+            let memory = make_role_memory("hauler");
+            memory.set("source_id", source_id.as_str());
+            if spawn_creep_with_memory(spawn, &body, Some(memory)) == ReturnCode::Ok {
+                queued.push((String::from("hauler"), source_id.clone()));
+            }
+            return true;
+        }
+    }
+
+    return false;
+}
+
+fn make_remote_memory(role: &str, home: &Room, target_room: &str) -> MemoryReference {
+    let memory = make_role_memory(role);
+    memory.set("home_room", home.name().to_string().as_str());
+    memory.set("target_room", target_room);
+    return memory;
+}
+
+// 出せた場合はtrueを返す.
+fn spawn_if_affordable(
+    spawn: &StructureSpawn,
+    room: &Room,
+    body: &Vec<Part>,
+    memory: MemoryReference,
+) -> bool {
+    let body_cost: u32 = body.iter().map(|p| p.cost()).sum();
+
+    if body.len() > 0 && room.energy_available() >= body_cost {
+        return spawn_creep_with_memory(spawn, body, Some(memory)) == ReturnCode::Ok;
+    }
+
+    return false;
+}
+
+fn make_remote_defender_body(energy_capacity: u32) -> Vec<Part> {
+    let body_unit = [Part::Move, Part::Attack];
+    let heal_unit = [Part::Move, Part::Heal];
+    let body_cost: u32 = body_unit.iter().map(|p| p.cost()).sum();
+    let heal_cost: u32 = heal_unit.iter().map(|p| p.cost()).sum();
+
+    let mut body = Vec::new();
+    let mut cost = 0;
+
+    if energy_capacity >= body_cost + heal_cost {
+        body.extend(heal_unit.iter().cloned());
+        cost += heal_cost;
+    }
+
+    let mut set_num = 0;
+    while set_num < REMOTE_DEFENDER_MAX_SET && cost + body_cost <= energy_capacity {
+        body.extend(body_unit.iter().cloned());
+        cost += body_cost;
+        set_num += 1;
+    }
+
+    return body;
+}
+
+fn make_reserver_body(energy_capacity: u32) -> Vec<Part> {
+    let body_unit = [Part::Claim, Part::Move];
+    let body_cost: u32 = body_unit.iter().map(|p| p.cost()).sum();
+
+    let mut body = Vec::new();
+    let mut set_num = std::cmp::min(energy_capacity / body_cost, REMOTE_RESERVER_MAX_CLAIM);
+
+    while set_num > 0 {
+        body.extend(body_unit.iter().cloned());
+        set_num -= 1;
+    }
+
+    return body;
+}
+
+// remote部屋ごとにdefender, reserver, miner, haulerを出す.
+// 出した、またはそのためにenergyを貯めている場合はtrueを返す.
+fn spawn_remote_creeps(spawn: &StructureSpawn, room: &Room, queued: &mut SpawnQueue) -> bool {
+    let energy_capacity = room.energy_capacity_available();
+    let home = room.name().to_string();
+
+    let storage = match room.storage() {
+        Some(storage) => storage,
+        None => return false,
+    };
+
+    for (remote_name, remote) in load_remote_rooms().iter() {
+        if remote.home != home || is_remote_active(remote) == false {
+            continue;
+        }
+
+        // 敵がいればまず追い払う.
+        if remote.hostile_damage > 0 {
+            if count_creeps_for_room("remote_defender", remote_name)
+                + count_queued(queued, "remote_defender", remote_name)
+                <= 0
+            {
+                let body = make_remote_defender_body(energy_capacity);
+                let memory = make_remote_memory("remote_defender", room, remote_name);
+                if spawn_if_affordable(spawn, room, &body, memory) {
+                    queued.push((String::from("remote_defender"), remote_name.clone()));
+                }
+                return true;
+            }
+            continue;
+        }
+
+        let need_reserver = match remote_name
+            .parse()
+            .ok()
+            .and_then(|name| screeps::game::rooms::get(name))
+            .and_then(|remote_room| remote_room.controller())
+        {
+            Some(controller) => match get_reservation_ticks(&controller) {
+                Some(ticks) => ticks < REMOTE_RESERVE_MIN_TICKS,
+                None => true,
+            },
+            None => true,
+        };
+
+        if need_reserver
+            && count_creeps_for_room("reserver", remote_name)
+                + count_queued(queued, "reserver", remote_name)
+                <= 0
+        {
+            let body = make_reserver_body(energy_capacity);
+            if body.len() <= 0 {
+                continue;
+            }
+
+            let memory = make_remote_memory("reserver", room, remote_name);
+            if spawn_if_affordable(spawn, room, &body, memory) {
+                queued.push((String::from("reserver"), remote_name.clone()));
+            }
+            return true;
+        }
+
+        if remote.hostile_reserved == true {
+            continue;
+        }
+
+        for source_id in remote.sources.iter() {
+            if count_creeps_for_source("miner", source_id.as_str())
+                + count_queued(queued, "miner", source_id.as_str())
+                <= 0
+            {
+                let body = make_miner_body(energy_capacity);
+                let memory = make_remote_memory("miner", room, remote_name);
+                memory.set("source_id", source_id.as_str());
+                if spawn_if_affordable(spawn, room, &body, memory) {
+                    queued.push((String::from("miner"), source_id.clone()));
+                }
+                return true;
+            }
+
+            if count_creeps_for_source("hauler", source_id.as_str())
+                + count_queued(queued, "hauler", source_id.as_str())
+                <= 0
+            {
+                // sourceが見えるまではminerを待つ.
+                let source = match get_source(source_id.as_str()) {
+                    Some(source) => source,
+                    None => continue,
+                };
+
+                let path_length = find_route_path_between(&storage.pos(), &source.pos(), 1)
+                    .load_local_path()
+                    .len() as u32;
+
+                let carry_parts = calc_hauler_carry_parts(path_length);
+                let body = make_remote_hauler_body(carry_parts, energy_capacity);
+
+                info!(
+                    "remote hauler for {:?}: path:{:?}, carry:{:?}",
+                    source.pos(),
+                    path_length,
+                    carry_parts
+                );
+
+                let memory = make_remote_memory("hauler", room, remote_name);
+                memory.set("source_id", source_id.as_str());
+                if spawn_if_affordable(spawn, room, &body, memory) {
+                    queued.push((String::from("hauler"), source_id.clone()));
+                }
+                return true;
+            }
+        }
+    }
+
+    return false;
+}
+
+fn make_pioneer_body(energy_capacity: u32) -> Vec<Part> {
+    let body_unit = [Part::Work, Part::Carry, Part::Move, Part::Move];
+    let body_cost: u32 = body_unit.iter().map(|p| p.cost()).sum();
+
+    let mut body = Vec::new();
+    let mut set_num = std::cmp::min(energy_capacity / body_cost, EXPANSION_PIONEER_MAX_SET);
+
+    while set_num > 0 {
+        body.extend(body_unit.iter().cloned());
+        set_num -= 1;
+    }
+
+    return body;
+}
+
+// 拡張先へclaimerとpioneerを出す.
+// 出した、またはそのためにenergyを貯めている場合はtrueを返す.
+fn spawn_expansion_creeps(spawn: &StructureSpawn, room: &Room, queued: &mut SpawnQueue) -> bool {
+    let expansion = match load_expansion() {
+        Some(expansion) => expansion,
+        None => return false,
+    };
+
+    if expansion.home != room.name().to_string() {
+        return false;
+    }
+
+    let energy_capacity = room.energy_capacity_available();
+    let target_room = expansion.room.as_str();
+
+    let is_claimed = target_room
+        .parse()
+        .ok()
+        .and_then(|name| screeps::game::rooms::get(name))
+        .and_then(|target| target.controller())
+        .map(|controller| controller.my())
+        .unwrap_or(false);
+
+    if is_claimed == false
+        && count_creeps_for_room("claimer", target_room)
+            + count_queued(queued, "claimer", target_room)
+            <= 0
+        && energy_capacity >= EXPANSION_CLAIMER_ENERGY
+    {
+        let body = vec![Part::Claim, Part::Move];
+        let memory = make_remote_memory("claimer", room, target_room);
+        if spawn_if_affordable(spawn, room, &body, memory) {
+            queued.push((String::from("claimer"), target_room.to_string()));
+        }
+        return true;
+    }
+
+    if count_creeps_for_room("pioneer", target_room) + count_queued(queued, "pioneer", target_room)
+        < EXPANSION_NUM_PIONEERS
+    {
+        let body = make_pioneer_body(energy_capacity);
+        let memory = make_remote_memory("pioneer", room, target_room);
+        if spawn_if_affordable(spawn, room, &body, memory) {
+            queued.push((String::from("pioneer"), target_room.to_string()));
+        }
+        return true;
+    }
+
+    return false;
+}
+
+// 編成中のsquadに足りないmemberを出す.
+fn spawn_squad_creeps(spawn: &StructureSpawn, room: &Room, queued: &mut SpawnQueue) -> bool {
+    let energy_capacity = room.energy_capacity_available();
+
+    for (squad_id, squad) in load_squads().iter() {
+        if squad.home != room.name().to_string() || squad.state != SquadState::FORMING {
+            continue;
+        }
+
+        // このtickに出したmemberはまだsquadに入っていないので、次のtickまで待つ.
+        if count_queued(queued, "squad", squad_id.as_str()) > 0 {
+            continue;
+        }
+
+        let squad_role = match find_missing_role(squad) {
+            Some(squad_role) => squad_role,
+            None => continue,
+        };
+
+        let body = make_squad_body(squad_role, energy_capacity);
+        let memory = make_role_memory("squad");
+        memory.set("squad_id", squad_id.as_str());
+        memory.set(
+            "squad_role",
+            serde_json::to_string(&squad_role).unwrap().as_str(),
+        );
+        // 戦闘用は出撃前にlabでboostする. power bankは回復と釣り合わせた数で組むのでboostしない.
+        if squad.formation != Formation::POWER {
+            set_boost_request(&memory, &body);
+        }
+        if spawn_if_affordable(spawn, room, &body, memory) {
+            queued.push((String::from("squad"), squad_id.clone()));
+        }
+        return true;
+    }
+
+    return false;
+}
+
+// 偵察用. 全体で決まった数だけ.
+fn spawn_scout(spawn: &StructureSpawn, room: &Room, queued: &mut SpawnQueue) -> bool {
+    let level = room.controller().map(|c| c.level()).unwrap_or(0);
+    if level < INTEL_SCOUT_MIN_RCL {
+        return false;
+    }
+
+    let num_scouts = screeps::game::creeps::values()
+        .iter()
+        .filter(|creep| {
+            creep.memory().string("role").unwrap_or(None) == Some(String::from("scout"))
+        })
+        .count() as u32;
+
+    // 部屋を問わず数えるので対象は空.
+    if num_scouts + count_queued(queued, "scout", "") >= INTEL_NUM_SCOUTS {
+        return false;
+    }
+
+    let body = vec![Part::Move];
+    let memory = make_remote_memory("scout", room, room.name().to_string().as_str());
+    if spawn_if_affordable(spawn, room, &body, memory) {
+        queued.push((String::from("scout"), String::new()));
+    }
+    return true;
+}
+
+fn make_lab_hauler_body(energy_capacity: u32) -> Vec<Part> {
+    let body_unit = [Part::Carry, Part::Carry, Part::Move];
+    let body_cost: u32 = body_unit.iter().map(|p| p.cost()).sum();
+
+    let mut body = Vec::new();
+    let mut set_num = std::cmp::min(energy_capacity / body_cost, LAB_HAULER_MAX_SET);
+
+    while set_num > 0 {
+        body.extend(body_unit.iter().cloned());
+        set_num -= 1;
+    }
+
+    return body;
+}
+
+// lab/factory/power spawnの仕事がある部屋に1体だけ.
+fn spawn_lab_hauler(spawn: &StructureSpawn, room: &Room, queued: &mut SpawnQueue) -> bool {
+    let room_name = room.name().to_string();
+
+    if count_creeps_for_room("lab_hauler", room_name.as_str())
+        + count_queued(queued, "lab_hauler", room_name.as_str())
+        > 0
+    {
+        return false;
+    }
+
+    if get_lab_reaction(&room.name()).is_none()
+        && get_lab_haul_task(room).is_none()
+        && get_factory_haul_task(room).is_none()
+        && get_power_spawn_haul_task(room).is_none()
+    {
+        return false;
+    }
+
+    let body = make_lab_hauler_body(room.energy_capacity_available());
+    let memory = make_remote_memory("lab_hauler", room, room_name.as_str());
+    if spawn_if_affordable(spawn, room, &body, memory) {
+        queued.push((String::from("lab_hauler"), room_name));
+    }
+    return true;
+}
+
+fn make_power_hauler_body(energy_capacity: u32) -> Vec<Part> {
+    let body_unit = [Part::Carry, Part::Move];
+    let body_cost: u32 = body_unit.iter().map(|p| p.cost()).sum();
+
+    let mut body = Vec::new();
+    let mut set_num = std::cmp::min(energy_capacity / body_cost, POWER_HAULER_MAX_SET);
+
+    while set_num > 0 {
+        body.extend(body_unit.iter().cloned());
+        set_num -= 1;
+    }
+
+    return body;
+}
+
+// power bankが壊れそうになったら、powerを運びきれる数だけ.
+fn spawn_power_haulers(spawn: &StructureSpawn, room: &Room, queued: &mut SpawnQueue) -> bool {
+    let body = make_power_hauler_body(room.energy_capacity_available());
+    let capacity = body.iter().filter(|p| **p == Part::Carry).count() as u32
+        * screeps::constants::CARRY_CAPACITY;
+    if capacity <= 0 {
+        return false;
+    }
+
+    for (bank_id, op) in load_power_ops().iter() {
+        if op.home != room.name().to_string() || check_power_hauler_needed(op) == false {
+            continue;
+        }
+
+        let needed = (op.power + capacity - 1) / capacity;
+        if count_creeps_for_room("power_hauler", op.room.as_str())
+            + count_queued(queued, "power_hauler", op.room.as_str())
+            >= needed
+        {
+            continue;
+        }
+
+        let memory = make_remote_memory("power_hauler", room, op.room.as_str());
+        memory.set("power_op", bank_id.as_str());
+        if spawn_if_affordable(spawn, room, &body, memory) {
+            queued.push((String::from("power_hauler"), op.room.clone()));
+        }
+        return true;
+    }
+
+    return false;
+}
+
+// 遠征ごとにharvesterを揃えてから、運べるだけのhauler.
+fn spawn_deposit_creeps(spawn: &StructureSpawn, room: &Room, queued: &mut SpawnQueue) -> bool {
+    let energy_capacity = room.energy_capacity_available();
+
+    for (deposit_id, expedition) in load_deposit_expeditions().iter() {
+        if expedition.home != room.name().to_string() {
+            continue;
+        }
+
+        if count_deposit_creeps("deposit_harvester", deposit_id.as_str())
+            + count_queued(queued, "deposit_harvester", deposit_id.as_str())
+            < expedition.num_harvesters
+        {
+            let body = make_deposit_harvester_body(energy_capacity);
+            let memory = make_remote_memory("deposit_harvester", room, expedition.room.as_str());
+            memory.set("deposit_id", deposit_id.as_str());
+            if spawn_if_affordable(spawn, room, &body, memory) {
+                queued.push((String::from("deposit_harvester"), deposit_id.clone()));
+            }
+            return true;
+        }
+
+        let body = make_deposit_hauler_body(energy_capacity);
+        let capacity = body.iter().filter(|p| **p == Part::Carry).count() as u32
+            * screeps::constants::CARRY_CAPACITY;
+
+        if count_deposit_creeps("deposit_hauler", deposit_id.as_str())
+            + count_queued(queued, "deposit_hauler", deposit_id.as_str())
+            < calc_needed_haulers(expedition, capacity)
+        {
+            let memory = make_remote_memory("deposit_hauler", room, expedition.room.as_str());
+            memory.set("deposit_id", deposit_id.as_str());
+            if spawn_if_affordable(spawn, room, &body, memory) {
+                queued.push((String::from("deposit_hauler"), deposit_id.clone()));
+            }
+            return true;
+        }
+    }
+
+    return false;
+}
 
 pub fn do_spawn() {
     if screeps::game::creeps::values().len() >= MAX_NUM_OF_CREEPS as usize {
@@ -62,31 +651,74 @@ pub fn do_spawn() {
         .unwrap_or(Some(0))
         .unwrap_or(0);
 
+    let mut queued: SpawnQueue = Vec::new();
+
     for spawn in screeps::game::spawns::values() {
         info!("running spawn {}", spawn.name());
 
-        // check got attacked.
-        if (spawn.hits() < spawn.hits_max())
-            || ((num_total_creep as u32) < MAX_NUM_OF_CREEPS / 3)
-            || ((opt_num_attackable_short + opt_num_attackable_long) <= 0)
-        {
-            info!("got attacked!!");
-
-            let my_controller = spawn
-                .room()
-                .expect("room is not visible to you")
-                .controller();
+        if spawn.is_spawning() == true {
+            continue;
+        }
 
-            match my_controller {
-                Some(controller) => {
-                    controller.activate_safe_mode();
-                }
-                None => {
-                    //nothint to do.
+        // 全滅・立ち上げ中は手持ちのenergyで最小構成のharvesterを出す.
+        let room = spawn.room().expect("room is not visible to you");
+        let economy_state = get_economy_state(&room);
+
+        if economy_state != EconomyState::NORMAL {
+            let stats = get_room_economy_stats(&room);
+            let room_name = room.name().to_string();
+
+            if stats.num_haulers + count_queued(&queued, "harvester_spawn", room_name.as_str())
+                < ECONOMY_MIN_HAULERS
+            {
+                info!("economy {:?}: spawn bootstrap harvester", economy_state);
+
+                let body = make_bootstrap_body(room.energy_available(), economy_state);
+                if body.len() > 0
+                    && spawn_creep_with_memory(
+                        &spawn,
+                        &body,
+                        Some(make_role_memory("harvester_spawn")),
+                    ) == ReturnCode::Ok
+                {
+                    queued.push((String::from("harvester_spawn"), room_name));
+                    continue;
                 }
             }
         }
 
+        if spawn_mining_creeps(&spawn, &room, &mut queued) == true {
+            continue;
+        }
+
+        if spawn_squad_creeps(&spawn, &room, &mut queued) == true {
+            continue;
+        }
+
+        if spawn_power_haulers(&spawn, &room, &mut queued) == true {
+            continue;
+        }
+
+        if spawn_deposit_creeps(&spawn, &room, &mut queued) == true {
+            continue;
+        }
+
+        if spawn_remote_creeps(&spawn, &room, &mut queued) == true {
+            continue;
+        }
+
+        if spawn_expansion_creeps(&spawn, &room, &mut queued) == true {
+            continue;
+        }
+
+        if spawn_scout(&spawn, &room, &mut queued) == true {
+            continue;
+        }
+
+        if spawn_lab_hauler(&spawn, &room, &mut queued) == true {
+            continue;
+        }
+
         //check energy can be used.
         let all_structures = spawn
             .room()
@@ -217,27 +849,7 @@ pub fn do_spawn() {
         }
 
         if body.len() > 0 {
-            // create a unique name, spawn.
-            let name_base = screeps::game::time();
-            let mut additional = 0;
-            let res = loop {
-                let name = format!("{}-{}", name_base, additional);
-                debug!("try spawn {:?}", body);
-                let res = spawn.spawn_creep(&body, &name);
-
-                if res == ReturnCode::NameExists {
-                    additional += 1;
-                } else {
-                    break res;
-                }
-            };
-
-            if res != ReturnCode::Ok {
-                info!("couldn't spawn: {:?}", res);
-            }
-            if res == ReturnCode::Ok {
-                info!("spawn: {:?}", body);
-            }
+            spawn_creep_with_memory(&spawn, &body, None);
         }
     }
 }
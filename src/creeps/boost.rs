@@ -0,0 +1,76 @@
+use crate::constants::*;
+use crate::manage::boost::*;
+use crate::util::*;
+use log::*;
+use screeps::constants::*;
+use screeps::{prelude::*, Creep, ResourceType, ReturnCode};
+
+// boostが終わっていないcreepはlabへ向かう. 動かした場合はtrue.
+pub fn run_boosting(creep: &Creep) -> bool {
+    let mut parts = get_boost_request(creep);
+    if parts.len() <= 0 {
+        return false;
+    }
+
+    // 待ちすぎたら諦めて出撃する.
+    let ticks_to_live = creep.ticks_to_live().unwrap_or(CREEP_LIFE_TIME);
+    if ticks_to_live + BOOST_MAX_WAIT_TICKS < CREEP_LIFE_TIME {
+        warn!("give up boosting {}: {:?}", creep.name(), parts);
+        creep.memory().del("boost_parts");
+        return false;
+    }
+
+    let room = creep.room().expect("room is not visible to you");
+    let part = parts[0];
+    let num_parts = count_active_parts(creep, part);
+
+    // 化合物が足りなければそのpartは諦める.
+    let compound = match select_boost_compound(&room, part, num_parts) {
+        Some(compound) if num_parts > 0 => compound,
+        _ => {
+            info!("no boost for {:?}, skip {}", part, creep.name());
+            parts.remove(0);
+            save_boost_request(creep, &parts);
+            return parts.len() > 0;
+        }
+    };
+
+    let lab = get_boost_lab_structures(&room.name())
+        .into_iter()
+        .find(|(_lab, c)| *c == compound)
+        .map(|(lab, _c)| lab);
+
+    let lab = match lab {
+        Some(lab) => lab,
+        None => {
+            // labの割り当て待ち.
+            return true;
+        }
+    };
+
+    if lab.store_of(compound) < num_parts * LAB_BOOST_MINERAL
+        || lab.store_of(ResourceType::Energy) < num_parts * LAB_BOOST_ENERGY
+    {
+        // lab haulerが詰めるのを待つ.
+        if creep.pos().get_range_to(&lab.pos()) > 2 {
+            move_near(creep, &lab.pos(), 2);
+        }
+        return true;
+    }
+
+    let r = lab.boost_creep(creep, None);
+
+    if r == ReturnCode::Ok {
+        info!("boosted {}: {:?} with {:?}", creep.name(), part, compound);
+        parts.remove(0);
+        save_boost_request(creep, &parts);
+    } else if r == ReturnCode::NotInRange {
+        move_near(creep, &lab.pos(), 1);
+    } else {
+        warn!("couldn't boost {}: {:?}", creep.name(), r);
+        parts.remove(0);
+        save_boost_request(creep, &parts);
+    }
+
+    return true;
+}
@@ -1,3 +1,4 @@
+use crate::creeps::task::*;
 use crate::util::*;
 use log::*;
 use screeps::constants::find::*;
@@ -8,6 +9,15 @@ use screeps::{
 
 use crate::creeps::repairer::*;
 
+// このroleが担当できるtask.
+pub const BUILDER_TASK_KINDS: [TaskKind; 5] = [
+    TaskKind::PICKUP,
+    TaskKind::WITHDRAW,
+    TaskKind::HARVEST,
+    TaskKind::BUILD,
+    TaskKind::REPAIR,
+];
+
 pub fn run_builder(creep: &Creep) {
     let name = creep.name();
     info!("running builder {}", creep.name());
@@ -0,0 +1,232 @@
+use crate::constants::*;
+use crate::creeps::remote::*;
+use crate::manage::deposit::*;
+use crate::util::*;
+use log::*;
+use screeps::constants::find::*;
+use screeps::constants::*;
+use screeps::{prelude::*, Creep, Position, ReturnCode, RoomName, Structure};
+
+fn get_deposit_id(creep: &Creep) -> Option<String> {
+    return creep.memory().string("deposit_id").unwrap_or(None);
+}
+
+fn get_deposit_pos(expedition: &DepositExpedition) -> Option<Position> {
+    let room_name: RoomName = expedition.room.parse().ok()?;
+    return Some(Position::new(expedition.x, expedition.y, room_name));
+}
+
+// 隣にいる同じdepositのhaulerに渡す.
+fn transfer_to_hauler(creep: &Creep, deposit_id: &str) -> bool {
+    let room = creep.room().expect("room is not visible to you");
+
+    let hauler = room.find(MY_CREEPS).into_iter().find(|other| {
+        other.memory().string("role").unwrap_or(None) == Some(String::from("deposit_hauler"))
+            && other
+                .memory()
+                .string("deposit_id")
+                .unwrap_or(None)
+                .as_ref()
+                .map(|s| s.as_str())
+                == Some(deposit_id)
+            && other.pos().get_range_to(&creep.pos()) <= 1
+            && other.store_free_capacity(None) > 0
+    });
+
+    let hauler = match hauler {
+        Some(hauler) => hauler,
+        None => return false,
+    };
+
+    let resource = match creep.store_types().into_iter().next() {
+        Some(resource) => resource,
+        None => return false,
+    };
+
+    let r = creep.transfer_all(&hauler, resource);
+    if r != ReturnCode::Ok {
+        debug!("couldn't transfer to deposit hauler: {:?}", r);
+    }
+    return r == ReturnCode::Ok;
+}
+
+pub fn run_deposit_harvester(creep: &Creep) {
+    info!("running deposit harvester {}", creep.name());
+
+    let deposit_id = match get_deposit_id(creep) {
+        Some(deposit_id) => deposit_id,
+        None => return,
+    };
+
+    let expedition = match get_deposit_expedition(deposit_id.as_str()) {
+        Some(expedition) => expedition,
+        None => {
+            // 遠征が終わったらrecycle.
+            creep.memory().set("recycling", true);
+            return;
+        }
+    };
+
+    if move_to_target_room(creep) == true {
+        return;
+    }
+
+    if creep.store_used_capacity(None) > 0 {
+        transfer_to_hauler(creep, deposit_id.as_str());
+    }
+
+    let room = creep.room().expect("room is not visible to you");
+    let deposit = room
+        .find(DEPOSITS)
+        .into_iter()
+        .find(|deposit| deposit.id().to_string() == deposit_id);
+
+    let deposit = match deposit {
+        Some(deposit) => deposit,
+        None => {
+            if let Some(pos) = get_deposit_pos(&expedition) {
+                move_near(creep, &pos, 1);
+            }
+            return;
+        }
+    };
+
+    if creep.pos().get_range_to(&deposit.pos()) > 1 {
+        move_near(creep, &deposit.pos(), 1);
+        return;
+    }
+
+    // いっぱいならhaulerを待つ.
+    if creep.store_free_capacity(None) <= 0 || deposit.cooldown() > 0 {
+        return;
+    }
+
+    let r = creep.harvest(&deposit);
+    if r != ReturnCode::Ok {
+        debug!("couldn't harvest deposit: {:?}", r);
+    }
+}
+
+// terminal -> storageの順.
+fn run_deposit_hauler_delivering(creep: &Creep) {
+    if move_to_home_room(creep) == true {
+        return;
+    }
+
+    let room = creep.room().expect("room is not visible to you");
+
+    let target = room
+        .terminal()
+        .filter(|terminal| terminal.store_free_capacity(None) > 0)
+        .map(|terminal| Structure::Terminal(terminal))
+        .or_else(|| room.storage().map(|storage| Structure::Storage(storage)));
+
+    let target = match target {
+        Some(target) => target,
+        None => {
+            warn!("deposit hauler has no place to deliver");
+            return;
+        }
+    };
+
+    let resource = match creep.store_types().into_iter().next() {
+        Some(resource) => resource,
+        None => return,
+    };
+
+    let r = match target.as_transferable() {
+        Some(transferable) => creep.transfer_all(transferable, resource),
+        None => ReturnCode::InvalidTarget,
+    };
+
+    if r == ReturnCode::NotInRange {
+        move_near(creep, &target.pos(), 1);
+    } else if r != ReturnCode::Ok {
+        info!("couldn't deliver {:?}: {:?}", resource, r);
+    }
+}
+
+// harvesterの隣で受け取る.
+fn run_deposit_hauler_gathering(creep: &Creep, deposit_id: &str, expedition: &DepositExpedition) {
+    if move_to_target_room(creep) == true {
+        return;
+    }
+
+    let room = creep.room().expect("room is not visible to you");
+
+    let harvester = room
+        .find(MY_CREEPS)
+        .into_iter()
+        .filter(|other| {
+            other.memory().string("role").unwrap_or(None) == Some(String::from("deposit_harvester"))
+                && other
+                    .memory()
+                    .string("deposit_id")
+                    .unwrap_or(None)
+                    .as_ref()
+                    .map(|s| s.as_str())
+                    == Some(deposit_id)
+        })
+        .max_by_key(|other| other.store_used_capacity(None));
+
+    let target_pos = match harvester {
+        Some(harvester) => harvester.pos(),
+        None => match get_deposit_pos(expedition) {
+            Some(pos) => pos,
+            None => return,
+        },
+    };
+
+    if creep.pos().get_range_to(&target_pos) > 1 {
+        move_near(creep, &target_pos, 1);
+    }
+}
+
+pub fn run_deposit_hauler(creep: &Creep) {
+    info!("running deposit hauler {}", creep.name());
+
+    let deposit_id = match get_deposit_id(creep) {
+        Some(deposit_id) => deposit_id,
+        None => return,
+    };
+
+    let expedition = get_deposit_expedition(deposit_id.as_str());
+
+    let carried = creep.store_used_capacity(None);
+    let mut is_delivering = creep.memory().bool("delivering");
+
+    if carried <= 0 {
+        is_delivering = false;
+    } else {
+        let is_full = creep.store_free_capacity(None) <= 0;
+
+        // 帰りの時間が無くなる前に戻る.
+        let is_dying = match expedition.as_ref() {
+            Some(expedition) => {
+                creep.ticks_to_live().unwrap_or(CREEP_LIFE_TIME)
+                    < expedition.distance * DEPOSIT_TICKS_PER_ROOM + DEPOSIT_HAULER_RETURN_MARGIN
+            }
+            None => true,
+        };
+
+        if is_full == true || is_dying == true {
+            is_delivering = true;
+        }
+    }
+    creep.memory().set("delivering", is_delivering);
+
+    if is_delivering == true {
+        run_deposit_hauler_delivering(creep);
+        return;
+    }
+
+    match expedition {
+        Some(expedition) => {
+            run_deposit_hauler_gathering(creep, deposit_id.as_str(), &expedition);
+        }
+        None => {
+            // 終わったらrecycle.
+            creep.memory().set("recycling", true);
+        }
+    }
+}
@@ -0,0 +1,107 @@
+use crate::creeps::remote::*;
+use crate::util::*;
+use log::*;
+use screeps::constants::find::*;
+use screeps::{prelude::*, Creep, ReturnCode, StructureType};
+
+pub fn run_claimer(creep: &Creep) {
+    info!("running claimer {}", creep.name());
+
+    if move_to_target_room(creep) == true {
+        return;
+    }
+
+    let room = creep.room().expect("room is not visible to you");
+    let controller = match room.controller() {
+        Some(controller) => controller,
+        None => {
+            warn!("no controller in expansion room {:?}", room.name());
+            return;
+        }
+    };
+
+    if controller.my() == true {
+        // 役目は終わり.
+        creep.suicide();
+        return;
+    }
+
+    let r = creep.claim_controller(&controller);
+
+    if r == ReturnCode::NotInRange {
+        move_near(creep, &controller.pos(), 1);
+    } else if r != ReturnCode::Ok {
+        info!("couldn't claim controller: {:?}", r);
+    }
+}
+
+fn run_pioneer_harvesting(creep: &Creep) {
+    let room = creep.room().expect("room is not visible to you");
+
+    let source = room
+        .find(SOURCES_ACTIVE)
+        .into_iter()
+        .min_by_key(|source| source.pos().get_range_to(&creep.pos()));
+
+    match source {
+        Some(source) => {
+            let r = creep.harvest(&source);
+            if r == ReturnCode::NotInRange {
+                move_near(creep, &source.pos(), 1);
+            }
+        }
+        None => {}
+    }
+}
+
+fn run_pioneer_working(creep: &Creep) {
+    let room = creep.room().expect("room is not visible to you");
+
+    // spawnを最優先で建てる.
+    let mut construction_sites = room.find(MY_CONSTRUCTION_SITES);
+    construction_sites.sort_by_key(|site| {
+        (
+            site.structure_type() != StructureType::Spawn,
+            site.pos().get_range_to(&creep.pos()),
+        )
+    });
+
+    if let Some(construction_site) = construction_sites.first() {
+        let r = creep.build(construction_site);
+        if r == ReturnCode::NotInRange {
+            move_near(creep, &construction_site.pos(), 3);
+        }
+        return;
+    }
+
+    if let Some(controller) = room.controller() {
+        let r = creep.upgrade_controller(&controller);
+        if r == ReturnCode::NotInRange {
+            move_near(creep, &controller.pos(), 3);
+        }
+    }
+}
+
+pub fn run_pioneer(creep: &Creep) {
+    info!("running pioneer {}", creep.name());
+
+    if move_to_target_room(creep) == true {
+        return;
+    }
+
+    // spawnができたら普通のharvesterとして働く.
+    let room = creep.room().expect("room is not visible to you");
+    if room.find(MY_SPAWNS).len() > 0 {
+        info!("pioneer {} -> harvester", creep.name());
+        creep.memory().set("role", "harvester");
+        creep.memory().del("target_room");
+        creep.memory().del("home_room");
+        return;
+    }
+
+    if creep.memory().bool("harvesting") {
+        run_pioneer_harvesting(creep);
+    } else {
+        run_pioneer_working(creep);
+    }
+}
@@ -1,3 +1,5 @@
+use crate::creeps::task::*;
+use crate::manage::logistics::*;
 use crate::util::*;
 use log::*;
 use screeps::constants::find::*;
@@ -9,10 +11,23 @@ use std::cmp::*;
 
 use crate::creeps::builder::*;
 
+// このroleが担当できるtask.
+pub const HARVESTER_TASK_KINDS: [TaskKind; 4] = [
+    TaskKind::PICKUP,
+    TaskKind::HARVEST,
+    TaskKind::WITHDRAW,
+    TaskKind::TRANSFER,
+];
+
 pub fn run_harvester(creep: &Creep) {
     let name = creep.name();
     info!("running harvester {}", creep.name());
 
+    // 配送先はまずlogisticsに聞く.
+    if run_logistics_creep(creep) == true {
+        return;
+    }
+
     debug!("check spawns {}", name);
     let my_spawns = &creep
         .room()
@@ -176,6 +191,10 @@ pub fn run_harvester_spawn(creep: &Creep) {
     let name = creep.name();
     info!("running harvester_spawn {}", creep.name());
 
+    if run_logistics_creep(creep) == true {
+        return;
+    }
+
     debug!("check spawns {}", name);
 
     let my_spawns = &creep
@@ -342,7 +361,7 @@ pub fn run_harvester_spawn(creep: &Creep) {
         if res == ReturnCode::Ok {
             return;
         }
-    }    
+    }
 
     // terminal
     debug!("check terminal {}", name);
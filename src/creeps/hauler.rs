@@ -0,0 +1,187 @@
+use crate::constants::*;
+use crate::creeps::harvester::*;
+use crate::creeps::miner::*;
+use crate::creeps::remote::*;
+use crate::manage::logistics::*;
+use crate::util::*;
+use log::*;
+use screeps::constants::find::*;
+use screeps::constants::*;
+use screeps::{prelude::*, Creep, Part, ResourceType, ReturnCode, Structure};
+
+// 往復にかかる時間分のenergyを運べるだけのCARRY.
+pub fn calc_hauler_carry_parts(path_length: u32) -> u32 {
+    let energy_per_tick = SOURCE_ENERGY_CAPACITY as f64 / ENERGY_REGEN_TIME as f64;
+    let round_trip = (path_length * 2) as f64 + HAULER_ROUND_TRIP_MARGIN as f64;
+    let need_capacity = energy_per_tick * round_trip;
+
+    return (need_capacity / CARRY_CAPACITY as f64).ceil() as u32;
+}
+
+// road前提でCARRY:MOVE=2:1.
+pub fn make_hauler_body(carry_parts: u32, energy_capacity: u32) -> Vec<Part> {
+    let body_unit = [Part::Carry, Part::Carry, Part::Move];
+    let body_cost: u32 = body_unit.iter().map(|p| p.cost()).sum();
+
+    let mut body = Vec::new();
+    let mut cost = 0;
+    let mut set_num = (carry_parts + 1) / 2;
+
+    while set_num > 0
+        && cost + body_cost <= energy_capacity
+        && ((body.len() + body_unit.len()) <= screeps::constants::MAX_CREEP_SIZE as usize)
+    {
+        body.extend(body_unit.iter().cloned());
+        cost += body_cost;
+        set_num -= 1;
+    }
+
+    return body;
+}
+
+// remote用. 道の修理用にWORKを1つ足す.
+pub fn make_remote_hauler_body(carry_parts: u32, energy_capacity: u32) -> Vec<Part> {
+    let work_unit = [Part::Work, Part::Carry, Part::Move];
+    let work_cost: u32 = work_unit.iter().map(|p| p.cost()).sum();
+
+    if energy_capacity < work_cost {
+        return Vec::new();
+    }
+
+    let mut body = work_unit.to_vec();
+    body.extend(make_hauler_body(
+        carry_parts.saturating_sub(1),
+        energy_capacity - work_cost,
+    ));
+
+    return body;
+}
+
+fn run_hauler_gathering(creep: &Creep, source_id: &str) {
+    let source = match get_source(source_id) {
+        Some(source) => source,
+        None => {
+            debug!("source is not visible {}", creep.name());
+            move_to_target_room(creep);
+            return;
+        }
+    };
+
+    let room = source.room().expect("room is not visible to you");
+
+    // sourceの周りに落ちているもの.
+    for resource in room.find(DROPPED_RESOURCES) {
+        if resource.resource_type() != ResourceType::Energy {
+            continue;
+        }
+
+        if resource.pos().get_range_to(&source.pos()) > 1 {
+            continue;
+        }
+
+        let r = creep.pickup(&resource);
+        if r == ReturnCode::Ok {
+            return;
+        }
+    }
+
+    // 採掘用container.
+    let mut wait_pos = source.pos();
+    let mut wait_range = 2;
+
+    for structure in room.find(STRUCTURES) {
+        if let Structure::Container(container) = structure {
+            if container.pos().is_near_to(&source) == false {
+                continue;
+            }
+
+            wait_pos = container.pos();
+            wait_range = 1;
+
+            if container.store_of(ResourceType::Energy) <= 0 {
+                continue;
+            }
+
+            let r = creep.withdraw_all(&container, ResourceType::Energy);
+            if r == ReturnCode::Ok {
+                creep.memory().set("harvested_from_storage", true);
+                set_withdrawn_from(creep, &container.id().to_string(), LOGISTICS_RANK_SOURCE);
+                return;
+            }
+        }
+    }
+
+    // minerの横で待つ.
+    if creep.pos().get_range_to(&wait_pos) > wait_range {
+        let res = find_path(&creep, &wait_pos, wait_range);
+
+        if res.load_local_path().len() > 0 {
+            let res = creep.move_by_path_search_result(&res);
+            if res != ReturnCode::Ok {
+                info!("couldn't move to source: {:?}", res);
+            }
+        }
+    }
+}
+
+fn run_hauler_delivering(creep: &Creep) {
+    // remoteからの帰り道ではroadの面倒も見る.
+    if move_to_home_room(creep) == true {
+        maintain_remote_road(creep);
+        return;
+    }
+
+    // 配送先はlogisticsで決める.
+    if run_logistics_creep(creep) == true {
+        return;
+    }
+
+    // 割り当てが無ければstorageへ.
+    if let Some(storage) = creep.room().expect("room is not visible to you").storage() {
+        if storage.my() == true && storage.store_free_capacity(Some(ResourceType::Energy)) > 0 {
+            let r = creep.transfer_all(&storage, ResourceType::Energy);
+
+            if r == ReturnCode::Ok {
+                info!("transferd to storage!!");
+                return;
+            }
+
+            if r == ReturnCode::NotInRange {
+                let res = find_path(&creep, &storage.pos(), 1);
+
+                if res.load_local_path().len() > 0 {
+                    let res = creep.move_by_path_search_result(&res);
+                    if res != ReturnCode::Ok {
+                        info!("couldn't move to storage: {:?}", res);
+                    }
+                }
+                return;
+            }
+        }
+    }
+
+    // storageがなければ普通のharvesterと同じように配る.
+    run_harvester(creep);
+}
+
+pub fn run_hauler(creep: &Creep) {
+    info!("running hauler {}", creep.name());
+
+    let source_id = match creep.memory().string("source_id").unwrap_or(None) {
+        Some(source_id) => source_id,
+        None => {
+            warn!("hauler has no source_id {}", creep.name());
+            return;
+        }
+    };
+
+    if retreat_from_remote(creep) == true {
+        return;
+    }
+
+    if creep.memory().bool("harvesting") {
+        run_hauler_gathering(creep, source_id.as_str());
+    } else {
+        run_hauler_delivering(creep);
+    }
+}
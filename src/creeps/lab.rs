@@ -0,0 +1,114 @@
+use crate::manage::boost::*;
+use crate::manage::factory::*;
+use crate::manage::lab::*;
+use crate::manage::power::*;
+use crate::util::*;
+use log::*;
+use screeps::{prelude::*, Creep, ResourceType, ReturnCode, Room, Structure};
+
+// 材料ならinput lab/factoryへ、それ以外はstorage(いっぱいならterminal)へ.
+fn get_deliver_target(room: &Room, resource: ResourceType) -> Option<Structure> {
+    if let (Some(setup), Some(reaction)) = (get_lab_setup(room), get_lab_reaction(&room.name())) {
+        for lab in [&setup.inputs.0, &setup.inputs.1].iter() {
+            if get_input_reagent(&setup, &reaction, lab) != Some(resource) {
+                continue;
+            }
+
+            let mineral = get_lab_mineral(lab);
+            if (mineral == None || mineral == Some(resource))
+                && lab.store_free_capacity(Some(resource)) > 0
+            {
+                return Some(Structure::Lab((*lab).clone()));
+            }
+        }
+    }
+
+    // boost labには化合物とenergyを入れる.
+    for (lab, compound) in get_boost_lab_structures(&room.name()) {
+        let is_wanted = resource == compound || resource == ResourceType::Energy;
+        if is_wanted == true && lab.store_free_capacity(Some(resource)) > 0 {
+            return Some(Structure::Lab(lab));
+        }
+    }
+
+    if let Some(target) = get_factory_deliver_target(room, resource) {
+        return Some(target);
+    }
+
+    if let Some(target) = get_power_deliver_target(room, resource) {
+        return Some(target);
+    }
+
+    if let Some(storage) = room.storage() {
+        if storage.store_free_capacity(None) > 0 {
+            return Some(Structure::Storage(storage));
+        }
+    }
+
+    return room
+        .terminal()
+        .map(|terminal| Structure::Terminal(terminal));
+}
+
+fn run_lab_hauler_delivering(creep: &Creep, room: &Room, resource: ResourceType) {
+    let target = match get_deliver_target(room, resource) {
+        Some(target) => target,
+        None => {
+            warn!("lab hauler has no place to deliver {:?}", resource);
+            return;
+        }
+    };
+
+    let r = match target.as_transferable() {
+        Some(transferable) => creep.transfer_all(transferable, resource),
+        None => ReturnCode::InvalidTarget,
+    };
+
+    if r == ReturnCode::NotInRange {
+        move_near(creep, &target.pos(), 1);
+    } else if r != ReturnCode::Ok {
+        info!("couldn't deliver {:?}: {:?}", resource, r);
+    }
+}
+
+pub fn run_lab_hauler(creep: &Creep) {
+    info!("running lab hauler {}", creep.name());
+
+    let room = creep.room().expect("room is not visible to you");
+
+    // 持っている物を先に届ける.
+    if let Some(resource) = creep.store_types().into_iter().next() {
+        run_lab_hauler_delivering(creep, &room, resource);
+        return;
+    }
+
+    // labの仕事が先. なければfactory, power spawnの仕事.
+    let task = match get_lab_haul_task(&room)
+        .or_else(|| get_factory_haul_task(&room))
+        .or_else(|| get_power_spawn_haul_task(&room))
+    {
+        Some(task) => task,
+        None => {
+            // 仕事がなければstorageの近くで待つ.
+            if let Some(storage) = room.storage() {
+                if creep.pos().get_range_to(&storage.pos()) > 2 {
+                    move_near(creep, &storage.pos(), 2);
+                }
+            }
+            return;
+        }
+    };
+
+    let amount = std::cmp::min(task.amount, creep.store_free_capacity(None) as u32);
+
+    let r = match task.from.as_withdrawable() {
+        Some(withdrawable) => creep.withdraw_amount(withdrawable, task.resource, amount),
+        None => ReturnCode::InvalidTarget,
+    };
+
+    if r == ReturnCode::NotInRange {
+        move_near(creep, &task.from.pos(), 1);
+    } else if r != ReturnCode::Ok {
+        info!("couldn't withdraw {:?}: {:?}", task.resource, r);
+    }
+}
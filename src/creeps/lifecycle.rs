@@ -0,0 +1,288 @@
+use crate::constants::*;
+use crate::util::*;
+use log::*;
+use screeps::constants::find::*;
+use screeps::{
+    constants::CREEP_LIFE_TIME, prelude::*, Creep, Part, ResourceType, ReturnCode, Structure,
+    StructureSpawn,
+};
+
+fn get_body_cost(creep: &Creep) -> u32 {
+    creep.body().iter().map(|b| b.part.cost()).sum()
+}
+
+fn find_nearest_idle_spawn(creep: &Creep, max_range: u32) -> Option<StructureSpawn> {
+    let my_spawns = creep
+        .room()
+        .expect("room is not visible to you")
+        .find(MY_SPAWNS);
+
+    let mut nearest: Option<(StructureSpawn, u32)> = None;
+
+    for my_spawn in my_spawns {
+        if my_spawn.is_spawning() == true {
+            continue;
+        }
+
+        let range = creep.pos().get_range_to(&my_spawn.pos());
+        if range > max_range {
+            continue;
+        }
+
+        match &nearest {
+            Some((_, nearest_range)) => {
+                if range < *nearest_range {
+                    nearest = Some((my_spawn, range));
+                }
+            }
+
+            None => {
+                nearest = Some((my_spawn, range));
+            }
+        }
+    }
+
+    return nearest.map(|(s, _)| s);
+}
+
+fn should_renew(creep: &Creep, ticks_to_live: u32) -> bool {
+    if ticks_to_live > RENEW_START_TICKS {
+        return false;
+    }
+
+    // CLAIMパーツ持ちはrenewできない.
+    if creep.get_active_bodyparts(Part::Claim) > 0 {
+        return false;
+    }
+
+    if get_body_cost(creep) < RENEW_MIN_BODY_COST {
+        return false;
+    }
+
+    let room = creep.room().expect("room is not visible to you");
+    if room.energy_available() < RENEW_MIN_ROOM_ENERGY {
+        return false;
+    }
+
+    return find_nearest_idle_spawn(creep, RENEW_MAX_RANGE).is_some();
+}
+
+fn should_recycle(creep: &Creep) -> bool {
+    // 専属のroleは体の大きさが役割で決まっているので入れ替えない.
+    if check_generic_role(creep) == false {
+        return false;
+    }
+
+    let room = creep.room().expect("room is not visible to you");
+
+    // 上限まで湧いている時だけ、古い小さいcreepを入れ替える.
+    if count_generic_creeps(room.name()) < MAX_NUM_OF_CREEPS {
+        return false;
+    }
+
+    if (get_body_cost(creep) as f64)
+        >= (room.energy_capacity_available() as f64 * RECYCLE_OBSOLETE_RATIO)
+    {
+        return false;
+    }
+
+    if room.find(MY_SPAWNS).len() <= 0 {
+        return false;
+    }
+
+    // 同時にrecycleするのは部屋ごとに1体まで.
+    for my_creep in room.find(MY_CREEPS) {
+        if my_creep.name() != creep.name() && my_creep.memory().bool("recycling") == true {
+            return false;
+        }
+    }
+
+    return true;
+}
+
+fn run_renewing(creep: &Creep, ticks_to_live: u32) -> bool {
+    if ticks_to_live >= RENEW_STOP_TICKS {
+        info!("renew finished {}", creep.name());
+        creep.memory().del("renewing");
+        return false;
+    }
+
+    match find_nearest_idle_spawn(creep, RENEW_MAX_RANGE) {
+        Some(my_spawn) => {
+            let r = my_spawn.renew_creep(creep);
+
+            if r == ReturnCode::Ok {
+                info!("renew creep {}!!", creep.name());
+                return true;
+            }
+
+            if r == ReturnCode::NotInRange {
+                let res = find_path(&creep, &my_spawn.pos(), 1);
+
+                if res.load_local_path().len() > 0 {
+                    let res = creep.move_by_path_search_result(&res);
+                    if res == ReturnCode::Ok {
+                        return true;
+                    }
+
+                    info!("couldn't move to renew: {:?}", res);
+                }
+                return true;
+            }
+
+            // energy不足など.
+            info!("couldn't renew: {:?}", r);
+            creep.memory().del("renewing");
+            return false;
+        }
+
+        None => {
+            // spawnが忙しくなったので諦める.
+            creep.memory().del("renewing");
+            return false;
+        }
+    }
+}
+
+fn run_recycling(creep: &Creep) -> bool {
+    match find_nearest_idle_spawn(creep, u32::MAX) {
+        Some(my_spawn) => {
+            // 手持ちのresourceは先に返しておく.
+            if creep.store_used_capacity(None) > 0 {
+                let r = creep.transfer_all(&my_spawn, ResourceType::Energy);
+                if r == ReturnCode::Ok {
+                    return true;
+                }
+            }
+
+            let r = my_spawn.recycle_creep(creep);
+
+            if r == ReturnCode::Ok {
+                info!("recycle creep {}!!", creep.name());
+                return true;
+            }
+
+            if r == ReturnCode::NotInRange {
+                let res = find_path(&creep, &my_spawn.pos(), 1);
+
+                if res.load_local_path().len() > 0 {
+                    let res = creep.move_by_path_search_result(&res);
+                    if res != ReturnCode::Ok {
+                        info!("couldn't move to recycle: {:?}", res);
+                    }
+                }
+                return true;
+            }
+
+            warn!("couldn't recycle: {:?}", r);
+            return false;
+        }
+
+        None => {
+            return false;
+        }
+    }
+}
+
+fn run_dying(creep: &Creep) -> bool {
+    if creep.store_used_capacity(None) <= 0 {
+        return false;
+    }
+
+    let resource_type_list = creep.store_types();
+
+    // 一番近い預け先へ運ぶ.
+    let mut nearest: Option<(Structure, ResourceType, u32)> = None;
+
+    for structure in creep
+        .room()
+        .expect("room is not visible to you")
+        .find(STRUCTURES)
+    {
+        let resource_type = match resource_type_list
+            .iter()
+            .find(|resource_type| check_transferable(&structure, resource_type, None))
+        {
+            Some(resource_type) => *resource_type,
+            None => continue,
+        };
+
+        let range = creep.pos().get_range_to(&structure.pos());
+
+        match &nearest {
+            Some((_, _, nearest_range)) => {
+                if range < *nearest_range {
+                    nearest = Some((structure, resource_type, range));
+                }
+            }
+
+            None => {
+                nearest = Some((structure, resource_type, range));
+            }
+        }
+    }
+
+    let (structure, resource_type, range) = match nearest {
+        Some(nearest) => nearest,
+        None => return false,
+    };
+
+    if range <= 1 {
+        if let Some(transf) = structure.as_transferable() {
+            let r = creep.transfer_all(transf, resource_type);
+
+            if r == ReturnCode::Ok {
+                info!("deposit before expiring {}!!", creep.name());
+                return true;
+            }
+
+            info!("couldn't deposit: {:?}", r);
+        }
+        return false;
+    }
+
+    let res = find_path(&creep, &structure.pos(), 1);
+
+    if res.load_local_path().len() > 0 {
+        let res = creep.move_by_path_search_result(&res);
+        if res == ReturnCode::Ok {
+            return true;
+        }
+
+        info!("couldn't move to deposit: {:?}", res);
+    }
+
+    return false;
+}
+
+// creepの寿命管理(renew/recycle/死ぬ前の荷下ろし).
+// このtickの行動を消費した場合はtrueを返す.
+pub fn run_lifecycle(creep: &Creep) -> bool {
+    let ticks_to_live = creep.ticks_to_live().unwrap_or(CREEP_LIFE_TIME);
+
+    if creep.memory().bool("recycling") == true {
+        return run_recycling(creep);
+    }
+
+    if creep.memory().bool("renewing") == true {
+        return run_renewing(creep, ticks_to_live);
+    }
+
+    if should_renew(creep, ticks_to_live) {
+        info!("start renewing {}", creep.name());
+        creep.memory().set("renewing", true);
+        return run_renewing(creep, ticks_to_live);
+    }
+
+    if ticks_to_live <= DYING_DEPOSIT_TICKS {
+        return run_dying(creep);
+    }
+
+    if should_recycle(creep) {
+        info!("start recycling {}", creep.name());
+        creep.memory().set("recycling", true);
+        return run_recycling(creep);
+    }
+
+    return false;
+}
@@ -0,0 +1,201 @@
+use crate::constants::*;
+use crate::creeps::remote::*;
+use crate::util::*;
+use log::*;
+use screeps::constants::find::*;
+use screeps::{
+    game, local::ObjectId, prelude::*, Creep, Part, Position, ReturnCode, Source, Structure,
+    StructureType,
+};
+
+pub fn get_source(source_id: &str) -> Option<Source> {
+    let id: ObjectId<Source> = match source_id.parse() {
+        Ok(id) => id,
+        Err(_err) => return None,
+    };
+
+    return game::get_object_typed(id).unwrap_or(None);
+}
+
+pub fn count_creeps_for_source(role: &str, source_id: &str) -> u32 {
+    let mut count = 0;
+
+    for creep in screeps::game::creeps::values() {
+        let creep_role = creep.memory().string("role").unwrap_or(None);
+        let creep_source_id = creep.memory().string("source_id").unwrap_or(None);
+
+        if creep_role.as_ref().map(|r| r.as_str()) == Some(role)
+            && creep_source_id.as_ref().map(|s| s.as_str()) == Some(source_id)
+        {
+            count += 1;
+        }
+    }
+
+    return count;
+}
+
+// 寿命がreplace_ticksより長いcreepだけ数える. spawn中のcreepも数える.
+pub fn count_lasting_creeps_for_source(role: &str, source_id: &str, replace_ticks: u32) -> u32 {
+    let mut count = 0;
+
+    for creep in screeps::game::creeps::values() {
+        let creep_role = creep.memory().string("role").unwrap_or(None);
+        let creep_source_id = creep.memory().string("source_id").unwrap_or(None);
+        let ticks_to_live = creep
+            .ticks_to_live()
+            .unwrap_or(screeps::constants::CREEP_LIFE_TIME);
+
+        if creep_role.as_ref().map(|r| r.as_str()) == Some(role)
+            && creep_source_id.as_ref().map(|s| s.as_str()) == Some(source_id)
+            && (creep.spawning() || ticks_to_live > replace_ticks)
+        {
+            count += 1;
+        }
+    }
+
+    return count;
+}
+
+// sourceの隣のcontainer. なければ最初の歩ける隣接マス.
+pub fn find_mining_pos(source: &Source) -> Option<Position> {
+    let room = source.room().expect("room is not visible to you");
+
+    for structure in room.find(STRUCTURES) {
+        if let Structure::Container(container) = structure {
+            if container.pos().is_near_to(source) {
+                return Some(container.pos());
+            }
+        }
+    }
+
+    for construction_site in room.find(MY_CONSTRUCTION_SITES) {
+        if construction_site.structure_type() == StructureType::Container
+            && construction_site.pos().is_near_to(source)
+        {
+            return Some(construction_site.pos());
+        }
+    }
+
+    let terrain = room.get_terrain();
+
+    for x_pos_offset in 0..=2 {
+        for y_pos_offset in 0..=2 {
+            if x_pos_offset == 1 && y_pos_offset == 1 {
+                continue;
+            }
+
+            let x = source.pos().x() as i32 + x_pos_offset - 1;
+            let y = source.pos().y() as i32 + y_pos_offset - 1;
+
+            if x <= 0 || y <= 0 || x >= 49 || y >= 49 {
+                continue;
+            }
+
+            if terrain.get(x as u32, y as u32) != screeps::constants::Terrain::Wall {
+                return Some(Position::new(x as u32, y as u32, room.name()));
+            }
+        }
+    }
+
+    return None;
+}
+
+// 1体でsourceを枯らせるだけのWORK(5個で3000/300).
+pub fn make_miner_body(energy_capacity: u32) -> Vec<Part> {
+    let mut body = vec![Part::Move];
+    let mut cost = Part::Move.cost();
+
+    let mut work_num = 0;
+    while work_num < MINER_WORK_PARTS && cost + Part::Work.cost() <= energy_capacity {
+        body.push(Part::Work);
+        cost += Part::Work.cost();
+        work_num += 1;
+    }
+
+    return body;
+}
+
+// 平地を1マス進むのにかかるtick. MOVE以外の部品1つごとにfatigueが2溜まり, MOVE1つで2減る.
+// 空のCARRYはfatigueにならないので数えない.
+pub fn calc_ticks_per_tile(body: &Vec<Part>) -> u32 {
+    let num_move = body.iter().filter(|part| **part == Part::Move).count() as u32;
+    let num_heavy = body
+        .iter()
+        .filter(|part| **part != Part::Move && **part != Part::Carry)
+        .count() as u32;
+
+    if num_move <= 0 {
+        return num_heavy;
+    }
+
+    return std::cmp::max((num_heavy + num_move - 1) / num_move, 1);
+}
+
+pub fn run_miner(creep: &Creep) {
+    info!("running miner {}", creep.name());
+
+    let source_id = match creep.memory().string("source_id").unwrap_or(None) {
+        Some(source_id) => source_id,
+        None => {
+            warn!("miner has no source_id {}", creep.name());
+            return;
+        }
+    };
+
+    if retreat_from_remote(creep) == true {
+        return;
+    }
+
+    let source = match get_source(source_id.as_str()) {
+        Some(source) => source,
+        None => {
+            // sourceが見えない. remoteなら部屋まで行く.
+            debug!("source is not visible {}", creep.name());
+            move_to_target_room(creep);
+            return;
+        }
+    };
+
+    let mining_pos = match find_mining_pos(&source) {
+        Some(mining_pos) => mining_pos,
+        None => {
+            warn!("no mining position {:?}", source.pos());
+            return;
+        }
+    };
+
+    if creep.pos() != mining_pos {
+        let res = find_path(&creep, &mining_pos, 0);
+
+        if res.load_local_path().len() > 0 {
+            let res = creep.move_by_path_search_result(&res);
+            if res != ReturnCode::Ok {
+                info!("couldn't move to mining position: {:?}", res);
+            }
+        }
+        return;
+    }
+
+    // containerがなければ置いておく(建てるのはbuilder).
+    let room = creep.room().expect("room is not visible to you");
+    let has_container = room
+        .look_for_at(screeps::look::STRUCTURES, &mining_pos)
+        .iter()
+        .any(|s| s.structure_type() == StructureType::Container)
+        || room
+            .look_for_at(screeps::look::CONSTRUCTION_SITES, &mining_pos)
+            .len()
+            > 0;
+
+    if has_container == false {
+        let r = room.create_construction_site(&mining_pos, StructureType::Container);
+        if r != ReturnCode::Ok {
+            debug!("couldn't place mining container: {:?}", r);
+        }
+    }
+
+    let r = creep.harvest(&source);
+    if r != ReturnCode::Ok && r != ReturnCode::NotEnough {
+        info!("couldn't harvest: {:?}", r);
+    }
+}
@@ -1,9 +1,26 @@
+mod boost;
 mod builder;
+mod deposit;
+mod expansion;
 mod harvester;
+pub mod hauler;
+mod lab;
+mod lifecycle;
+pub mod miner;
+mod power;
+pub mod remote;
 mod repairer;
+mod scout;
+pub mod task;
 mod upgrader;
 
+use crate::attack::ranged::*;
 use crate::constants::*;
+use crate::defence::threat::*;
+use crate::manage::economy::*;
+use crate::manage::logistics::*;
+use crate::manage::source::*;
+use crate::plan::road::*;
 use crate::util::*;
 use log::*;
 use screeps::constants::find::*;
@@ -20,6 +37,18 @@ enum AttackerKind {
     NONE,
 }
 
+// 目的地の隣にsourceがあれば、そのslotを確保する.
+fn claim_source_near(creep: &Creep, target_pos: &Position) {
+    if let Some(room) = screeps::game::rooms::get(target_pos.room_name()) {
+        for source in room.find(find::SOURCES) {
+            if target_pos.is_near_to(&source) {
+                claim_source(creep, &source);
+                return;
+            }
+        }
+    }
+}
+
 fn reset_source_target(
     creep: &Creep,
     is_harvester: bool,
@@ -27,6 +56,8 @@ fn reset_source_target(
 ) -> (SearchResults, Position) {
     debug!("harvesting : reset_source_target");
 
+    release_source(creep);
+
     if is_harvester == true {
         // active sourceをチェック.
         let res = find_nearest_active_source(&creep, harvest_kind, false);
@@ -37,6 +68,7 @@ fn reset_source_target(
 
         if res.load_local_path().len() > 0 && res.incomplete == false {
             let last_pos = *(res.load_local_path().last().unwrap());
+            claim_source_near(creep, &last_pos);
             let json_str = serde_json::to_string(&last_pos).unwrap();
             creep.memory().set("target_pos", json_str);
             creep.memory().set("target_pos_count", 20);
@@ -103,6 +135,7 @@ fn reset_source_target(
 
         if res.load_local_path().len() > 0 && res.incomplete == false {
             let last_pos = *(res.load_local_path().last().unwrap());
+            claim_source_near(creep, &last_pos);
             let json_str = serde_json::to_string(&last_pos).unwrap();
             creep.memory().set("target_pos", json_str);
             creep.memory().set("target_pos_count", 10);
@@ -146,11 +179,13 @@ fn reset_source_target(
 }
 
 fn attacker_routine(creep: &Creep, kind: &AttackerKind) -> bool {
+    // 遠距離は引き撃ちする.
+    if *kind == AttackerKind::RANGED {
+        return run_ranged_combat(creep);
+    }
+
     debug!("check enemies {}", creep.name());
-    let enemies = creep
-        .room()
-        .expect("room is not visible to you")
-        .find(HOSTILE_CREEPS);
+    let enemies = get_hostile_creeps(&creep.room().expect("room is not visible to you"));
 
     if enemies.len() == 0 {
         return false;
@@ -215,6 +250,16 @@ fn attacker_routine(creep: &Creep, kind: &AttackerKind) -> bool {
     return false;
 }
 
+fn get_role_task_kinds(role: &str) -> &'static [task::TaskKind] {
+    match role {
+        "harvester" | "harvester_spawn" => &harvester::HARVESTER_TASK_KINDS,
+        "builder" => &builder::BUILDER_TASK_KINDS,
+        "upgrader" => &upgrader::UPGRADER_TASK_KINDS,
+        "repairer" => &repairer::REPAIRER_TASK_KINDS,
+        _ => &[],
+    }
+}
+
 fn get_role_and_attacker_kind(creep: &Creep) -> (String, AttackerKind) {
     let mut attacker_kind: AttackerKind = AttackerKind::NONE;
     let role = creep.memory().string("role");
@@ -304,6 +349,12 @@ pub fn creep_loop() {
                 num_carrier_mineral += 1;
             }
 
+            "miner" | "hauler" | "reserver" | "remote_defender" | "claimer" | "pioneer"
+            | "scout" | "squad" | "lab_hauler" | "power_hauler" | "deposit_harvester"
+            | "deposit_hauler" => {
+                // source専属/remote専属.
+            }
+
             "builder" => {
                 num_builder += 1;
             }
@@ -326,15 +377,92 @@ pub fn creep_loop() {
         }
     }
 
-    // if no harvester, clear role.
-    if (num_harvester + num_harvester_spawn <= 2)
-        && (screeps::game::creeps::values().len() > (num_harvester + num_harvester_spawn) as usize)
-    {
-        for creep in screeps::game::creeps::values() {
-            creep.memory().del("role");
+    // 経済がNORMAL以外の部屋では、作業系creepをharvester_spawnに回す.
+    // 立て直したら元の役割に戻す.
+    for creep in screeps::game::creeps::values() {
+        if creep.get_active_bodyparts(Part::Carry) <= 0 {
+            continue;
         }
+
+        let room = creep.room().expect("room is not visible to you");
+        if get_economy_state(&room) == EconomyState::NORMAL {
+            let economy_role = match creep.memory().string("economy_role").unwrap_or(None) {
+                Some(economy_role) => economy_role,
+                None => continue,
+            };
+            creep.memory().del("economy_role");
+
+            if get_role_and_attacker_kind(&creep).0 != String::from("harvester_spawn") {
+                continue;
+            }
+
+            match economy_role.as_str() {
+                "builder" => {
+                    num_builder += 1;
+                }
+
+                "upgrader" => {
+                    num_upgrader += 1;
+                }
+
+                "repairer" => {
+                    num_repairer += 1;
+                }
+
+                _ => {
+                    continue;
+                }
+            }
+
+            info!(
+                "economy recovered: {} harvester_spawn -> {}",
+                creep.name(),
+                economy_role
+            );
+            creep.memory().set("role", economy_role.as_str());
+            num_harvester_spawn -= 1;
+            cap_worker_carry -= creep.store_capacity(None) as u128;
+            continue;
+        }
+
+        if get_room_economy_stats(&room).num_haulers >= ECONOMY_MIN_HAULERS {
+            continue;
+        }
+
+        let role_string = get_role_and_attacker_kind(&creep).0;
+
+        match role_string.as_str() {
+            "builder" => {
+                num_builder -= 1;
+            }
+
+            "upgrader" => {
+                num_upgrader -= 1;
+            }
+
+            "repairer" => {
+                num_repairer -= 1;
+            }
+
+            _ => {
+                continue;
+            }
+        }
+
+        info!(
+            "economy recovery: {} {} -> harvester_spawn",
+            creep.name(),
+            role_string
+        );
+        creep.memory().set("economy_role", role_string.as_str());
+        creep.memory().set("role", "harvester_spawn");
+        add_economy_hauler(&room, creep.store_capacity(None));
+        num_harvester_spawn += 1;
+        cap_worker_carry += creep.store_capacity(None) as u128;
     }
 
+    task::dispatch_tasks(get_role_task_kinds);
+
     for creep in screeps::game::creeps::values() {
         let name = creep.name();
         info!(
@@ -343,6 +471,8 @@ pub fn creep_loop() {
             screeps::game::cpu::get_used()
         );
 
+        record_traffic(&creep);
+
         let mut attacker_kind: AttackerKind = AttackerKind::NONE;
         let mut role_string = String::from("none");
 
@@ -425,6 +555,25 @@ pub fn creep_loop() {
             continue;
         }
 
+        //// squadのmemberは解散するまで寿命管理しない.
+        let is_squad_member = role_string == String::from("squad")
+            && creep.memory().string("squad_id").unwrap_or(None).is_some();
+
+        //// renew/recycle check.
+        if is_squad_member == false && lifecycle::run_lifecycle(&creep) == true {
+            continue;
+        }
+
+        //// boost check.
+        if boost::run_boosting(&creep) == true {
+            continue;
+        }
+
+        //// squadのmemberはattack::squadで動かす.
+        if role_string == String::from("squad") {
+            continue;
+        }
+
         //// atacker check.
         if attacker_kind != AttackerKind::NONE {
             let result = attacker_routine(&creep, &attacker_kind);
@@ -447,6 +596,7 @@ pub fn creep_loop() {
                     && (creep.store_used_capacity(None) > 0))
             {
                 creep.memory().set("harvesting", false);
+                release_source(&creep);
                 creep.memory().del("target_pos");
                 creep.memory().del("will_harvest_from_storage");
                 creep.memory().del("nothing_to_harvest");
@@ -458,10 +608,72 @@ pub fn creep_loop() {
                 creep.memory().del("harvested_from_storage");
                 creep.memory().del("harvested_from_terminal");
                 creep.memory().del("harvested_from_link");
+                clear_withdrawn_from(&creep);
                 creep.memory().del("nothing_to_harvest");
             }
         }
 
+        //// task check.
+        if task::run_task(&creep) == true {
+            continue;
+        }
+
+        //// source専属のroleは独自に動く.
+        if role_string == String::from("miner") {
+            miner::run_miner(&creep);
+            continue;
+        }
+
+        if role_string == String::from("hauler") {
+            hauler::run_hauler(&creep);
+            continue;
+        }
+
+        if role_string == String::from("reserver") {
+            remote::run_reserver(&creep);
+            continue;
+        }
+
+        if role_string == String::from("remote_defender") {
+            remote::run_remote_defender(&creep);
+            continue;
+        }
+
+        if role_string == String::from("claimer") {
+            expansion::run_claimer(&creep);
+            continue;
+        }
+
+        if role_string == String::from("pioneer") {
+            expansion::run_pioneer(&creep);
+            continue;
+        }
+
+        if role_string == String::from("scout") {
+            scout::run_scout(&creep);
+            continue;
+        }
+
+        if role_string == String::from("lab_hauler") {
+            lab::run_lab_hauler(&creep);
+            continue;
+        }
+
+        if role_string == String::from("power_hauler") {
+            power::run_power_hauler(&creep);
+            continue;
+        }
+
+        if role_string == String::from("deposit_harvester") {
+            deposit::run_deposit_harvester(&creep);
+            continue;
+        }
+
+        if role_string == String::from("deposit_hauler") {
+            deposit::run_deposit_hauler(&creep);
+            continue;
+        }
+
         if creep.memory().bool("harvesting") {
             debug!("harvesting {}", name);
 
@@ -678,6 +890,11 @@ pub fn creep_loop() {
                                             break;
                                         }
                                         creep.memory().set("harvested_from_storage", true);
+                                        set_withdrawn_from(
+                                            &creep,
+                                            &container.id().to_string(),
+                                            LOGISTICS_RANK_SOURCE,
+                                        );
                                         is_harvested = true;
                                         break;
                                     }
@@ -689,6 +906,11 @@ pub fn creep_loop() {
                                             break;
                                         }
                                         creep.memory().set("harvested_from_storage", true);
+                                        set_withdrawn_from(
+                                            &creep,
+                                            &storage.id().to_string(),
+                                            LOGISTICS_RANK_STORAGE,
+                                        );
                                         is_harvested = true;
                                         break;
                                     }
@@ -715,6 +937,11 @@ pub fn creep_loop() {
                                                     break;
                                                 }
                                                 creep.memory().set("harvested_from_terminal", true);
+                                                set_withdrawn_from(
+                                                    &creep,
+                                                    &terminal.id().to_string(),
+                                                    LOGISTICS_RANK_TERMINAL,
+                                                );
                                                 is_harvested = true;
                                                 break;
                                             }
@@ -728,23 +955,16 @@ pub fn creep_loop() {
                                             break;
                                         }
                                         creep.memory().set("harvested_from_link", true);
+                                        set_withdrawn_from(
+                                            &creep,
+                                            &link.id().to_string(),
+                                            LOGISTICS_RANK_SOURCE,
+                                        );
                                         is_harvested = true;
                                         break;
                                     }
 
-                                    Structure::Lab(lab) => {
-                                        if harvest_kind == ResourceKind::MINELALS {
-                                            let r = creep.withdraw_all(lab, *resource_type);
-                                            if r != ReturnCode::Ok {
-                                                warn!("couldn't withdraw from lab: {:?}", r);
-                                                break;
-                                            }
-                                            creep.memory().set("harvested_from_storage", true);
-                                            is_harvested = true;
-                                            break;
-                                        }
-                                    }
-
+                                    // labはlab haulerが管理する.
                                     _ => {
                                         //do nothing
                                     }
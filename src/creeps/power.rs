@@ -0,0 +1,144 @@
+use crate::constants::*;
+use crate::creeps::remote::*;
+use crate::manage::power::*;
+use crate::util::*;
+use log::*;
+use screeps::constants::find::*;
+use screeps::{prelude::*, Creep, Position, ResourceType, ReturnCode, RoomName, Structure};
+
+// power spawn -> storage -> terminalの順.
+fn run_power_hauler_delivering(creep: &Creep) {
+    if move_to_home_room(creep) == true {
+        return;
+    }
+
+    let room = creep.room().expect("room is not visible to you");
+
+    let target = get_power_deliver_target(&room, ResourceType::Power)
+        .or_else(|| room.storage().map(|storage| Structure::Storage(storage)))
+        .or_else(|| {
+            room.terminal()
+                .map(|terminal| Structure::Terminal(terminal))
+        });
+
+    let target = match target {
+        Some(target) => target,
+        None => {
+            warn!("power hauler has no place to deliver");
+            return;
+        }
+    };
+
+    let r = match target.as_transferable() {
+        Some(transferable) => creep.transfer_all(transferable, ResourceType::Power),
+        None => ReturnCode::InvalidTarget,
+    };
+
+    if r == ReturnCode::NotInRange {
+        move_near(creep, &target.pos(), 1);
+    } else if r != ReturnCode::Ok {
+        info!("couldn't deliver power: {:?}", r);
+    }
+}
+
+// 落ちているpowerを拾う. なければ廃墟から.
+fn run_power_hauler_gathering(creep: &Creep, op: &PowerOp) {
+    if move_to_target_room(creep) == true {
+        return;
+    }
+
+    let room = creep.room().expect("room is not visible to you");
+
+    let dropped = room
+        .find(DROPPED_RESOURCES)
+        .into_iter()
+        .filter(|resource| resource.resource_type() == ResourceType::Power)
+        .min_by_key(|resource| resource.pos().get_range_to(&creep.pos()));
+
+    if let Some(resource) = dropped {
+        let r = creep.pickup(&resource);
+        if r == ReturnCode::NotInRange {
+            move_near(creep, &resource.pos(), 1);
+        } else if r != ReturnCode::Ok {
+            info!("couldn't pickup power: {:?}", r);
+        }
+        return;
+    }
+
+    let ruin = room
+        .find(RUINS)
+        .into_iter()
+        .filter(|ruin| ruin.store_of(ResourceType::Power) > 0)
+        .min_by_key(|ruin| ruin.pos().get_range_to(&creep.pos()));
+
+    if let Some(ruin) = ruin {
+        let r = creep.withdraw_all(&ruin, ResourceType::Power);
+        if r == ReturnCode::NotInRange {
+            move_near(creep, &ruin.pos(), 1);
+        } else if r != ReturnCode::Ok {
+            info!("couldn't withdraw power: {:?}", r);
+        }
+        return;
+    }
+
+    // 壊れるまで少し離れて待つ.
+    let room_name: RoomName = match op.room.parse() {
+        Ok(room_name) => room_name,
+        Err(_err) => return,
+    };
+    let bank_pos = Position::new(op.x, op.y, room_name);
+    if creep.pos().get_range_to(&bank_pos) > POWER_HAULER_WAIT_RANGE {
+        move_near(creep, &bank_pos, POWER_HAULER_WAIT_RANGE);
+    }
+}
+
+pub fn run_power_hauler(creep: &Creep) {
+    info!("running power hauler {}", creep.name());
+
+    let op = creep
+        .memory()
+        .string("power_op")
+        .unwrap_or(None)
+        .and_then(|bank_id| get_power_op(bank_id.as_str()));
+
+    let carried = creep.store_of(ResourceType::Power);
+    let mut is_delivering = creep.memory().bool("delivering");
+
+    if carried <= 0 {
+        is_delivering = false;
+    } else {
+        let is_full = creep.store_free_capacity(None) <= 0;
+        let is_finished = match op.as_ref() {
+            Some(op) => {
+                op.state == PowerOpState::HAULING
+                    && creep
+                        .room()
+                        .map(|room| {
+                            room.name().to_string() == op.room && count_remaining_power(&room) <= 0
+                        })
+                        .unwrap_or(false)
+            }
+            None => true,
+        };
+
+        if is_full == true || is_finished == true {
+            is_delivering = true;
+        }
+    }
+    creep.memory().set("delivering", is_delivering);
+
+    if is_delivering == true {
+        run_power_hauler_delivering(creep);
+        return;
+    }
+
+    match op {
+        Some(op) => {
+            run_power_hauler_gathering(creep, &op);
+        }
+        None => {
+            // 終わったらrecycle.
+            creep.memory().set("recycling", true);
+        }
+    }
+}
@@ -0,0 +1,160 @@
+use crate::manage::remote::*;
+use crate::route::*;
+use crate::util::*;
+use log::*;
+use screeps::constants::find::*;
+use screeps::{prelude::*, Creep, Part, ReturnCode, RoomName, Structure};
+
+fn get_memory_room(creep: &Creep, key: &str) -> Option<RoomName> {
+    return creep
+        .memory()
+        .string(key)
+        .unwrap_or(None)
+        .and_then(|room_name| room_name.parse().ok());
+}
+
+fn move_to_room(creep: &Creep, room_name: RoomName) {
+    let res = find_path_to_room(creep, room_name);
+
+    if res.load_local_path().len() > 0 {
+        let res = creep.move_by_path_search_result(&res);
+        if res != ReturnCode::Ok {
+            info!("couldn't move to room: {:?}", res);
+        }
+    }
+}
+
+// target_roomにいなければ向かう. 移動中ならtrue.
+pub fn move_to_target_room(creep: &Creep) -> bool {
+    let target_room = match get_memory_room(creep, "target_room") {
+        Some(target_room) => target_room,
+        None => return false,
+    };
+
+    if creep.room().expect("room is not visible to you").name() == target_room {
+        return false;
+    }
+
+    move_to_room(creep, target_room);
+    return true;
+}
+
+// homeにいなければ帰る. 移動中ならtrue.
+pub fn move_to_home_room(creep: &Creep) -> bool {
+    let home_room = match get_memory_room(creep, "home_room") {
+        Some(home_room) => home_room,
+        None => return false,
+    };
+
+    if creep.room().expect("room is not visible to you").name() == home_room {
+        return false;
+    }
+
+    move_to_room(creep, home_room);
+    return true;
+}
+
+// remoteに敵がいる間、または放棄した後はhomeへ退避する.
+pub fn retreat_from_remote(creep: &Creep) -> bool {
+    let target_room = match creep.memory().string("target_room").unwrap_or(None) {
+        Some(target_room) => target_room,
+        None => return false,
+    };
+
+    if check_remote_threat(target_room.as_str()) == false
+        && check_remote_abandoned(target_room.as_str()) == false
+    {
+        return false;
+    }
+
+    info!("retreat from remote room {}: {}", creep.name(), target_room);
+    move_to_home_room(creep);
+    return true;
+}
+
+// 通り道のroadを作る・直す.
+pub fn maintain_remote_road(creep: &Creep) -> bool {
+    if creep.get_active_bodyparts(Part::Work) <= 0 {
+        return false;
+    }
+
+    let room = creep.room().expect("room is not visible to you");
+
+    for construction_site in room.find(MY_CONSTRUCTION_SITES) {
+        if construction_site.pos().get_range_to(&creep.pos()) > 3 {
+            continue;
+        }
+
+        let r = creep.build(&construction_site);
+        if r == ReturnCode::Ok {
+            return true;
+        }
+    }
+
+    for structure in room.look_for_at(screeps::look::STRUCTURES, &creep.pos()) {
+        if let Structure::Road(road) = structure {
+            if road.hits() < road.hits_max() {
+                let r = creep.repair(&road);
+                if r == ReturnCode::Ok {
+                    return true;
+                }
+            }
+        }
+    }
+
+    return false;
+}
+
+pub fn run_reserver(creep: &Creep) {
+    info!("running reserver {}", creep.name());
+
+    if move_to_target_room(creep) == true {
+        return;
+    }
+
+    let room = creep.room().expect("room is not visible to you");
+    let controller = match room.controller() {
+        Some(controller) => controller,
+        None => {
+            warn!("no controller in remote room {:?}", room.name());
+            return;
+        }
+    };
+
+    // 他人の予約は先に剥がす.
+    let r = match get_reservation_ticks(&controller) {
+        Some(_ticks) => creep.reserve_controller(&controller),
+        None => creep.attack_controller(&controller),
+    };
+
+    if r == ReturnCode::NotInRange {
+        let res = find_path(&creep, &controller.pos(), 1);
+
+        if res.load_local_path().len() > 0 {
+            let res = creep.move_by_path_search_result(&res);
+            if res != ReturnCode::Ok {
+                info!("couldn't move to controller: {:?}", res);
+            }
+        }
+    } else if r != ReturnCode::Ok {
+        info!("couldn't reserve controller: {:?}", r);
+    }
+}
+
+// 敵はattacker_routineで殴るので、ここではremoteへ向かうだけ.
+pub fn run_remote_defender(creep: &Creep) {
+    info!("running remote defender {}", creep.name());
+
+    let target_room = creep
+        .memory()
+        .string("target_room")
+        .unwrap_or(None)
+        .unwrap_or(String::from("none"));
+
+    if check_remote_threat(target_room.as_str()) == true {
+        move_to_target_room(creep);
+        return;
+    }
+
+    move_to_home_room(creep);
+}
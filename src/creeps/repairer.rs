@@ -1,6 +1,9 @@
 use crate::constants::*;
 use std::u128;
 
+use crate::creeps::task::*;
+use crate::manage::repair::*;
+use crate::plan::rampart::*;
 use crate::util::*;
 use log::*;
 use screeps::constants::find::*;
@@ -11,6 +14,15 @@ use screeps::{
 
 use crate::creeps::upgrader::*;
 
+// このroleが担当できるtask.
+pub const REPAIRER_TASK_KINDS: [TaskKind; 5] = [
+    TaskKind::PICKUP,
+    TaskKind::WITHDRAW,
+    TaskKind::HARVEST,
+    TaskKind::REPAIR,
+    TaskKind::UPGRADE,
+];
+
 pub fn run_repairer(creep: &Creep) {
     let name = creep.name();
     info!("running repairer {}", creep.name());
@@ -33,14 +45,17 @@ pub fn run_repairer(creep: &Creep) {
         }
     }
 
-    let structures = &creep
+    let mut structures = creep
         .room()
         .expect("room is not visible to you")
         .find(STRUCTURES);
 
+    // 外周の要になるrampartを先に見る.
+    structures.sort_by_key(|structure| check_critical_rampart(structure) == false);
+
     let mut is_skip_repair = false;
 
-    let room_name = &creep.room().expect("room is not visible to you").name();
+    let schedule = make_repair_schedule(&creep.room().expect("room is not visible to you"));
 
     // 残り時間が短いものを優先.
     for structure in structures.iter() {
@@ -65,30 +80,26 @@ pub fn run_repairer(creep: &Creep) {
         }
     }
 
-    // 残りhpが少ない物を優先.
+    // 目標HPに対して減っている物を優先.
+    let repair_target = find_repair_target(&structures, &schedule);
+
     if is_skip_repair == false {
-        let stats = get_hp_average(room_name);
-        let threshold = stats.1 + (stats.0 - stats.1) / 1000;
-
-        for structure in structures.iter() {
-            if check_repairable(structure) {
-                if get_hp(structure).unwrap_or(0) as u128 <= (threshold + 1) {
-                    let r = creep.repair(structure);
-
-                    if r == ReturnCode::Ok {
-                        info!(
-                            "repair my_structure!!:{:?},{:?},{:?}",
-                            structure.structure_type(),
-                            structure.pos().x(),
-                            structure.pos().y()
-                        );
-                        return;
-                    }
-
-                    if r == ReturnCode::NotInRange {
-                        is_skip_repair = true;
-                    }
-                }
+        if let Some(structure) = repair_target.as_ref() {
+            let r = creep.repair(structure);
+
+            if r == ReturnCode::Ok {
+                info!(
+                    "repair my_structure!!:{:?},{:?},{:?}",
+                    structure.structure_type(),
+                    structure.pos().x(),
+                    structure.pos().y()
+                );
+                record_repair_spent(
+                    &schedule.room_name,
+                    structure.structure_type(),
+                    creep.get_active_bodyparts(Part::Work),
+                );
+                return;
             }
         }
     }
@@ -105,18 +116,16 @@ pub fn run_repairer(creep: &Creep) {
         return;
     }
 
-    // 残りhpが少ない物を優先.
-    let stats = get_hp_average(room_name);
-    let threshold = stats.1 + (stats.0 - stats.1) / 1000;
+    if let Some(structure) = repair_target.as_ref() {
+        let res = find_path(&creep, &structure.pos(), 3);
 
-    let res = find_nearest_repairable_item_hp(&creep, (threshold + 1) as u32);
-
-    if res.load_local_path().len() > 0 {
-        let res = creep.move_by_path_search_result(&res);
-        if res != ReturnCode::Ok {
-            info!("couldn't move to repair: {:?}", res);
+        if res.load_local_path().len() > 0 {
+            let res = creep.move_by_path_search_result(&res);
+            if res != ReturnCode::Ok {
+                info!("couldn't move to repair: {:?}", res);
+            }
+            return;
         }
-        return;
     }
 
     run_upgrader(creep);
@@ -0,0 +1,87 @@
+use crate::constants::*;
+use crate::creeps::remote::*;
+use crate::manage::intel::*;
+use log::*;
+use screeps::{game, prelude::*, Creep, RoomName};
+
+// 危ない部屋には入らない.
+fn check_scoutable(room_name: &str) -> bool {
+    match get_room_intel(room_name) {
+        Some(room_intel) => {
+            if check_source_keeper_room(&room_intel) {
+                return false;
+            }
+
+            return room_intel
+                .hostile_structures
+                .iter()
+                .any(|structure| structure.structure_type == "Tower")
+                == false;
+        }
+        None => {}
+    }
+
+    return true;
+}
+
+// 隣の部屋のうち、一番長く見ていない所.
+fn choose_next_room(creep: &Creep) -> Option<String> {
+    let current_room = creep.room().expect("room is not visible to you").name();
+    let home_room: Option<RoomName> = creep
+        .memory()
+        .string("home_room")
+        .unwrap_or(None)
+        .and_then(|room_name| room_name.parse().ok());
+
+    let mut best: Option<(u32, String)> = None;
+
+    for exit_room_name in game::map::describe_exits(current_room).values() {
+        let room_key = exit_room_name.to_string();
+
+        if let Some(home_room) = home_room {
+            let distance = game::map::get_room_linear_distance(home_room, *exit_room_name, false);
+            if distance > INTEL_SCOUT_MAX_DISTANCE {
+                continue;
+            }
+        }
+
+        if check_scoutable(room_key.as_str()) == false {
+            continue;
+        }
+
+        let last_seen = get_room_intel(room_key.as_str())
+            .map(|room_intel| room_intel.last_seen)
+            .unwrap_or(0);
+
+        let is_older = match &best {
+            Some((best_last_seen, _room)) => last_seen < *best_last_seen,
+            None => true,
+        };
+
+        if is_older {
+            best = Some((last_seen, room_key));
+        }
+    }
+
+    return best.map(|(_last_seen, room)| room);
+}
+
+pub fn run_scout(creep: &Creep) {
+    info!("running scout {}", creep.name());
+
+    if move_to_target_room(creep) == true {
+        return;
+    }
+
+    // 着いたら次の部屋へ.
+    match choose_next_room(creep) {
+        Some(next_room) => {
+            debug!("scout {} -> {}", creep.name(), next_room);
+            creep.memory().set("target_room", next_room.as_str());
+            move_to_target_room(creep);
+        }
+        None => {
+            warn!("scout {} has nowhere to go", creep.name());
+        }
+    }
+}
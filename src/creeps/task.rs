@@ -0,0 +1,509 @@
+use crate::constants::*;
+use crate::manage::logistics::*;
+use crate::manage::repair::*;
+use crate::manage::source::*;
+use crate::util::*;
+use log::*;
+use screeps::constants::find::*;
+use screeps::{
+    game, local::ObjectId, prelude::*, ConstructionSite, Creep, HasId, Part, Position, Resource,
+    ResourceType, ReturnCode, Room, SizedRoomObject, Source, Structure, StructureController,
+    StructureType,
+};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+#[derive(PartialEq, Eq, Clone, Copy, Debug, Hash, Serialize, Deserialize)]
+pub enum TaskKind {
+    HARVEST,
+    WITHDRAW,
+    TRANSFER,
+    BUILD,
+    REPAIR,
+    UPGRADE,
+    PICKUP,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Task {
+    pub kind: TaskKind,
+    pub target: String,
+    pub expire: u32,
+}
+
+struct TaskCandidate {
+    kind: TaskKind,
+    target: String,
+    pos: Position,
+    capacity: u32,
+}
+
+impl TaskKind {
+    // 資源を集める側のtaskかどうか.
+    pub fn is_gathering(&self) -> bool {
+        match self {
+            TaskKind::HARVEST | TaskKind::WITHDRAW | TaskKind::PICKUP => true,
+            _ => false,
+        }
+    }
+
+    fn action_range(&self) -> u32 {
+        match self {
+            TaskKind::BUILD | TaskKind::REPAIR | TaskKind::UPGRADE => 3,
+            _ => 1,
+        }
+    }
+
+    // 予約の単位がresource量か人数か.
+    fn is_amount_based(&self) -> bool {
+        match self {
+            TaskKind::WITHDRAW | TaskKind::TRANSFER | TaskKind::PICKUP => true,
+            _ => false,
+        }
+    }
+}
+
+fn get_target<T: HasId + SizedRoomObject>(target: &str) -> Option<T> {
+    let id: ObjectId<T> = match target.parse() {
+        Ok(id) => id,
+        Err(_err) => return None,
+    };
+
+    return game::get_object_typed(id).unwrap_or(None);
+}
+
+pub fn load_task(creep: &Creep) -> Option<Task> {
+    let json_str = creep.memory().string("task").unwrap_or(None)?;
+    let task: Task = serde_json::from_str(json_str.as_str()).ok()?;
+
+    if task.expire < game::time() {
+        clear_task(creep);
+        return None;
+    }
+
+    return Some(task);
+}
+
+fn save_task(creep: &Creep, task: &Task) {
+    let json_str = serde_json::to_string(task).unwrap();
+    creep.memory().set("task", json_str);
+}
+
+pub fn clear_task(creep: &Creep) {
+    // HARVESTはsourceのclaimも返す.
+    let is_harvest = creep
+        .memory()
+        .string("task")
+        .unwrap_or(None)
+        .and_then(|json_str| serde_json::from_str::<Task>(json_str.as_str()).ok())
+        .map(|task| task.kind == TaskKind::HARVEST)
+        .unwrap_or(false);
+
+    if is_harvest == true {
+        release_source(creep);
+    }
+
+    creep.memory().del("task");
+}
+
+fn calc_reserve_amount(creep: &Creep, kind: &TaskKind) -> u32 {
+    if kind.is_amount_based() == false {
+        return 1;
+    }
+
+    if kind.is_gathering() {
+        return std::cmp::max(creep.store_free_capacity(Some(ResourceType::Energy)), 0) as u32;
+    }
+
+    return creep.store_of(ResourceType::Energy);
+}
+
+fn collect_candidates(room: &Room) -> Vec<TaskCandidate> {
+    let mut candidates = Vec::<TaskCandidate>::new();
+
+    for resource in room.find(DROPPED_RESOURCES) {
+        if resource.resource_type() == ResourceType::Energy {
+            candidates.push(TaskCandidate {
+                kind: TaskKind::PICKUP,
+                target: resource.id().to_string(),
+                pos: resource.pos(),
+                capacity: resource.amount(),
+            });
+        }
+    }
+
+    for source in room.find(SOURCES_ACTIVE) {
+        let free_slots = calc_source_free_slots(&source);
+        if free_slots <= 0 {
+            continue;
+        }
+
+        candidates.push(TaskCandidate {
+            kind: TaskKind::HARVEST,
+            target: source.id().to_string(),
+            pos: source.pos(),
+            capacity: free_slots,
+        });
+    }
+
+    for construction_site in room.find(MY_CONSTRUCTION_SITES) {
+        candidates.push(TaskCandidate {
+            kind: TaskKind::BUILD,
+            target: construction_site.id().to_string(),
+            pos: construction_site.pos(),
+            capacity: TASK_MAX_CREEPS_PER_SITE,
+        });
+    }
+
+    if let Some(controller) = room.controller() {
+        if controller.my() == true {
+            candidates.push(TaskCandidate {
+                kind: TaskKind::UPGRADE,
+                target: controller.id().to_string(),
+                pos: controller.pos(),
+                capacity: u32::MAX,
+            });
+        }
+    }
+
+    let schedule = make_repair_schedule(room);
+    let is_infrastructure_ok = check_repair_budget(&schedule, RepairCategory::INFRASTRUCTURE);
+    let is_fortification_ok = check_repair_budget(&schedule, RepairCategory::FORTIFICATION);
+
+    for structure in room.find(STRUCTURES) {
+        let structure_type = structure.structure_type();
+
+        // 補給先.
+        if structure_type == StructureType::Spawn
+            || structure_type == StructureType::Extension
+            || structure_type == StructureType::Tower
+        {
+            if check_transferable(&structure, &ResourceType::Energy, None) {
+                if let Some(has_store) = structure.as_has_store() {
+                    candidates.push(TaskCandidate {
+                        kind: TaskKind::TRANSFER,
+                        target: structure.id().to_string(),
+                        pos: structure.pos(),
+                        capacity: has_store.store_free_capacity(Some(ResourceType::Energy)) as u32,
+                    });
+                }
+            }
+        }
+
+        // 引き出し元.
+        if structure_type == StructureType::Container || structure_type == StructureType::Storage {
+            if check_stored(&structure, &ResourceType::Energy, 0) {
+                if let Some(has_store) = structure.as_has_store() {
+                    candidates.push(TaskCandidate {
+                        kind: TaskKind::WITHDRAW,
+                        target: structure.id().to_string(),
+                        pos: structure.pos(),
+                        capacity: has_store.store_of(ResourceType::Energy),
+                    });
+                }
+            }
+        }
+
+        // 修理.
+        let is_budget_ok = match get_repair_category(structure_type) {
+            RepairCategory::INFRASTRUCTURE => is_infrastructure_ok,
+            RepairCategory::FORTIFICATION => is_fortification_ok,
+        };
+
+        if check_repairable(&structure) {
+            if get_live_tickcount(&structure).unwrap_or(10000) <= REPAIRER_DYING_THRESHOLD
+                || (is_budget_ok == true && check_repair_needed(&structure, &schedule))
+            {
+                candidates.push(TaskCandidate {
+                    kind: TaskKind::REPAIR,
+                    target: structure.id().to_string(),
+                    pos: structure.pos(),
+                    capacity: 1,
+                });
+            }
+        }
+    }
+
+    return candidates;
+}
+
+fn choose_task(
+    creep: &Creep,
+    task_kinds: &[TaskKind],
+    candidates: &Vec<TaskCandidate>,
+    reservations: &HashMap<String, u32>,
+) -> Option<Task> {
+    let is_gathering = if creep.store_used_capacity(None) <= 0 {
+        true
+    } else if creep.store_free_capacity(None) <= 0 {
+        false
+    } else {
+        creep.memory().bool("harvesting")
+    };
+
+    for kind in task_kinds.iter() {
+        if kind.is_gathering() != is_gathering {
+            continue;
+        }
+
+        let mut best: Option<(&TaskCandidate, u32)> = None;
+
+        for candidate in candidates.iter() {
+            if candidate.kind != *kind {
+                continue;
+            }
+
+            let reserved = *reservations.get(&candidate.target).unwrap_or(&0);
+            if reserved >= candidate.capacity {
+                continue;
+            }
+
+            let range = creep.pos().get_range_to(&candidate.pos);
+
+            match best {
+                Some((_, best_range)) => {
+                    if range < best_range {
+                        best = Some((candidate, range));
+                    }
+                }
+
+                None => {
+                    best = Some((candidate, range));
+                }
+            }
+        }
+
+        if let Some((candidate, _)) = best {
+            return Some(Task {
+                kind: candidate.kind,
+                target: candidate.target.clone(),
+                expire: game::time() + TASK_EXPIRE_TICKS,
+            });
+        }
+    }
+
+    return None;
+}
+
+// 部屋ごとにtaskを割り当てる.
+// 同じ対象に複数のcreepが群がらないように、割り当て済みの分を予約として差し引く.
+pub fn dispatch_tasks(get_task_kinds: fn(&str) -> &'static [TaskKind]) {
+    for room in screeps::game::rooms::values() {
+        let my_creeps = room.find(MY_CREEPS);
+        if my_creeps.len() <= 0 {
+            continue;
+        }
+
+        let mut reservations = HashMap::<String, u32>::new();
+        let mut idle_creeps = Vec::<(Creep, &'static [TaskKind])>::new();
+
+        for my_creep in my_creeps {
+            if my_creep.spawning() {
+                continue;
+            }
+
+            match load_task(&my_creep) {
+                // HARVESTはsourceのclaimで数えているので予約しない.
+                Some(task) if task.kind == TaskKind::HARVEST => {}
+
+                Some(task) => {
+                    let amount = calc_reserve_amount(&my_creep, &task.kind);
+                    *reservations.entry(task.target.clone()).or_insert(0) += amount;
+                }
+
+                None => {
+                    let role = my_creep
+                        .memory()
+                        .string("role")
+                        .unwrap_or(None)
+                        .unwrap_or(String::from("none"));
+                    let task_kinds = get_task_kinds(role.as_str());
+
+                    if task_kinds.len() > 0 {
+                        idle_creeps.push((my_creep, task_kinds));
+                    }
+                }
+            }
+        }
+
+        if idle_creeps.len() <= 0 {
+            continue;
+        }
+
+        let candidates = collect_candidates(&room);
+
+        for (my_creep, task_kinds) in idle_creeps.iter() {
+            if let Some(task) = choose_task(my_creep, task_kinds, &candidates, &reservations) {
+                debug!("assign task {}: {:?}", my_creep.name(), task);
+
+                let amount = calc_reserve_amount(my_creep, &task.kind);
+                *reservations.entry(task.target.clone()).or_insert(0) += amount;
+                save_task(my_creep, &task);
+
+                if task.kind == TaskKind::HARVEST {
+                    if let Some(source) = get_target::<Source>(&task.target) {
+                        claim_source(my_creep, &source);
+                    }
+                }
+            }
+        }
+    }
+}
+
+fn move_to_target(creep: &Creep, target_pos: &Position, range: u32) -> bool {
+    let res = find_path(&creep, target_pos, range);
+
+    if res.load_local_path().len() > 0 {
+        let res = creep.move_by_path_search_result(&res);
+        if res == ReturnCode::Ok {
+            return true;
+        }
+
+        info!("couldn't move to task target: {:?}", res);
+    }
+
+    return false;
+}
+
+// 割り当てられたtaskを実行する.
+// このtickの行動を消費した場合はtrueを返す.
+pub fn run_task(creep: &Creep) -> bool {
+    let task = match load_task(creep) {
+        Some(task) => task,
+        None => return false,
+    };
+
+    let (r, target_pos): (ReturnCode, Option<Position>) = match task.kind {
+        TaskKind::HARVEST => match get_target::<Source>(&task.target) {
+            Some(source) => (creep.harvest(&source), Some(source.pos())),
+            None => (ReturnCode::NotFound, None),
+        },
+
+        TaskKind::PICKUP => match get_target::<Resource>(&task.target) {
+            Some(resource) => (creep.pickup(&resource), Some(resource.pos())),
+            None => (ReturnCode::NotFound, None),
+        },
+
+        TaskKind::WITHDRAW => match get_target::<Structure>(&task.target) {
+            Some(structure) => match structure.as_withdrawable() {
+                Some(withdrawable) => {
+                    let r = creep.withdraw_all(withdrawable, ResourceType::Energy);
+
+                    //storage系から引き出した分をそこへ戻さないようにする.
+                    if r == ReturnCode::Ok {
+                        creep.memory().set("harvested_from_storage", true);
+                        set_withdrawn_from(
+                            creep,
+                            &task.target,
+                            get_logistics_rank(structure.structure_type()),
+                        );
+                    }
+                    (r, Some(structure.pos()))
+                }
+                None => (ReturnCode::InvalidTarget, None),
+            },
+            None => (ReturnCode::NotFound, None),
+        },
+
+        TaskKind::TRANSFER => match get_target::<Structure>(&task.target) {
+            Some(structure) => match structure.as_transferable() {
+                Some(transferable) => (
+                    creep.transfer_all(transferable, ResourceType::Energy),
+                    Some(structure.pos()),
+                ),
+                None => (ReturnCode::InvalidTarget, None),
+            },
+            None => (ReturnCode::NotFound, None),
+        },
+
+        TaskKind::BUILD => match get_target::<ConstructionSite>(&task.target) {
+            Some(construction_site) => (
+                creep.build(&construction_site),
+                Some(construction_site.pos()),
+            ),
+            None => (ReturnCode::NotFound, None),
+        },
+
+        TaskKind::REPAIR => match get_target::<Structure>(&task.target) {
+            Some(structure) => {
+                // 目標HPに届くか予算を使い切ったら終わり. 壊れそうなものだけは予算を見ない.
+                let schedule =
+                    make_repair_schedule(&structure.room().expect("room is not visible to you"));
+                let is_reached =
+                    get_hp(&structure).unwrap_or(0) >= get_target_hp(&structure, &schedule);
+                let is_over_budget = get_live_tickcount(&structure).unwrap_or(10000)
+                    > REPAIRER_DYING_THRESHOLD
+                    && check_repair_budget(
+                        &schedule,
+                        get_repair_category(structure.structure_type()),
+                    ) == false;
+
+                if check_repairable(&structure) && is_reached == false && is_over_budget == false {
+                    let r = creep.repair(&structure);
+                    if r == ReturnCode::Ok {
+                        record_repair_spent(
+                            &structure
+                                .room()
+                                .expect("room is not visible to you")
+                                .name()
+                                .to_string(),
+                            structure.structure_type(),
+                            creep.get_active_bodyparts(Part::Work),
+                        );
+                    }
+                    (r, Some(structure.pos()))
+                } else {
+                    (ReturnCode::Full, None)
+                }
+            }
+            None => (ReturnCode::NotFound, None),
+        },
+
+        TaskKind::UPGRADE => match get_target::<StructureController>(&task.target) {
+            Some(controller) => (
+                creep.upgrade_controller(&controller),
+                Some(controller.pos()),
+            ),
+            None => (ReturnCode::NotFound, None),
+        },
+    };
+
+    match r {
+        ReturnCode::Ok => {
+            // 1回で終わるtaskは終了. 継続するものは満杯/空になったら終了.
+            let is_finished = match task.kind {
+                TaskKind::HARVEST => creep.store_free_capacity(None) <= 0,
+                TaskKind::BUILD | TaskKind::REPAIR | TaskKind::UPGRADE => {
+                    creep.store_of(ResourceType::Energy) <= 0
+                }
+                _ => true,
+            };
+
+            if is_finished {
+                clear_task(creep);
+            }
+            return true;
+        }
+
+        ReturnCode::NotInRange => match target_pos {
+            Some(target_pos) => {
+                if move_to_target(creep, &target_pos, task.kind.action_range()) {
+                    return true;
+                }
+
+                clear_task(creep);
+                return false;
+            }
+            None => {
+                clear_task(creep);
+                return false;
+            }
+        },
+
+        _ => {
+            debug!("task finished {}: {:?},{:?}", creep.name(), task, r);
+            clear_task(creep);
+            return false;
+        }
+    }
+}
@@ -1,3 +1,4 @@
+use crate::creeps::task::*;
 use crate::util::*;
 use log::*;
 
@@ -6,6 +7,14 @@ use screeps::{
     RoomObjectProperties,
 };
 
+// このroleが担当できるtask.
+pub const UPGRADER_TASK_KINDS: [TaskKind; 4] = [
+    TaskKind::PICKUP,
+    TaskKind::WITHDRAW,
+    TaskKind::HARVEST,
+    TaskKind::UPGRADE,
+];
+
 pub fn run_upgrader(creep: &Creep) {
     let name = creep.name();
     info!("running upgrader {}", creep.name());
@@ -1 +1,3 @@
+pub mod safe_mode;
+pub mod threat;
 pub mod tower;
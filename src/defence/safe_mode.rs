@@ -0,0 +1,169 @@
+use crate::constants::*;
+use crate::defence::threat::*;
+use crate::defence::tower::*;
+use log::*;
+use screeps::constants::find::*;
+use screeps::constants::*;
+use screeps::Structure;
+use screeps::{prelude::*, Creep, Part, Room};
+
+fn calc_defence_damage(room: &Room, enemies: &Vec<Creep>) -> u32 {
+    let mut damage: u32 = 0;
+
+    // towerは一番近い敵に対する火力で見積もる.
+    for structure in room.find(MY_STRUCTURES) {
+        if let Structure::Tower(my_tower) = structure {
+            if my_tower.store_of(ResourceType::Energy) < TOWER_ENERGY_COST {
+                continue;
+            }
+
+            let mut min_range = u32::MAX;
+            for enemy in enemies.iter() {
+                min_range = std::cmp::min(min_range, my_tower.pos().get_range_to(&enemy.pos()));
+            }
+
+            damage += calc_tower_damage(min_range);
+        }
+    }
+
+    for my_creep in room.find(MY_CREEPS) {
+        damage += my_creep.get_active_bodyparts(Part::Attack) * ATTACK_POWER;
+        damage += my_creep.get_active_bodyparts(Part::RangedAttack) * RANGED_ATTACK_POWER;
+    }
+
+    return damage;
+}
+
+fn check_critical_structures(room: &Room, enemies: &Vec<Creep>) -> bool {
+    for structure in room.find(MY_STRUCTURES) {
+        let structure_type = structure.structure_type();
+
+        if structure_type != StructureType::Spawn
+            && structure_type != StructureType::Tower
+            && structure_type != StructureType::Storage
+            && structure_type != StructureType::Terminal
+        {
+            continue;
+        }
+
+        if let Some(attackable) = structure.as_attackable() {
+            if (attackable.hits() as f64)
+                < (attackable.hits_max() as f64 * SAFE_MODE_CRITICAL_HP_RATE)
+            {
+                info!(
+                    "safe mode: critical structure damaged:{:?},{:?}/{:?}",
+                    structure_type,
+                    attackable.hits(),
+                    attackable.hits_max()
+                );
+                return true;
+            }
+        }
+
+        // 重要施設のすぐ近くまで来ている. dismantleは隣接している時だけ.
+        for enemy in enemies.iter() {
+            let range = structure.pos().get_range_to(&enemy.pos());
+
+            if (range <= SAFE_MODE_CRITICAL_RANGE && calc_hostile_damage(enemy) > 0)
+                || (range <= 1 && calc_hostile_dismantle(enemy) > 0)
+            {
+                info!(
+                    "safe mode: hostile {:?} is close to {:?}",
+                    enemy.name(),
+                    structure_type
+                );
+                return true;
+            }
+        }
+    }
+
+    return false;
+}
+
+fn check_safe_mode(room: &Room) -> bool {
+    let controller = match room.controller() {
+        Some(controller) => controller,
+        None => return false,
+    };
+
+    if controller.my() == false {
+        return false;
+    }
+
+    let enemies: Vec<Creep> = get_ranked_hostiles(room)
+        .into_iter()
+        .filter(|hostile| hostile.damage > 0 || hostile.kind == HostileKind::DISMANTLER)
+        .map(|hostile| hostile.creep)
+        .collect();
+
+    if enemies.len() <= 0 {
+        return false;
+    }
+
+    if controller.safe_mode().unwrap_or(0) > 0 {
+        debug!("safe mode: {:?} already in safe mode", room.name());
+        return false;
+    }
+
+    if controller.safe_mode_available() <= 0 {
+        info!("safe mode: {:?} no safe mode available", room.name());
+        return false;
+    }
+
+    if controller.safe_mode_cooldown().unwrap_or(0) > 0 {
+        info!("safe mode: {:?} safe mode is in cooldown", room.name());
+        return false;
+    }
+
+    let mut hostile_damage: u32 = 0;
+    let mut hostile_heal: u32 = 0;
+    for enemy in enemies.iter() {
+        hostile_damage += calc_hostile_damage(enemy);
+        hostile_heal += calc_hostile_heal(enemy);
+    }
+
+    let defence_damage = calc_defence_damage(room, &enemies);
+
+    info!(
+        "safe mode: {:?} hostiles:{:?}, damage:{:?}, heal:{:?}, defence:{:?}",
+        room.name(),
+        enemies.len(),
+        hostile_damage,
+        hostile_heal,
+        defence_damage
+    );
+
+    // 防衛側で押し切れるなら使わない.
+    if defence_damage > hostile_heal && defence_damage >= hostile_damage {
+        info!("safe mode: {:?} defence can handle hostiles", room.name());
+        return false;
+    }
+
+    if check_critical_structures(room, &enemies) == false {
+        info!(
+            "safe mode: {:?} critical structures are not threatened",
+            room.name()
+        );
+        return false;
+    }
+
+    return true;
+}
+
+pub fn run_safe_mode() {
+    for room in screeps::game::rooms::values() {
+        if check_safe_mode(&room) == false {
+            continue;
+        }
+
+        if let Some(controller) = room.controller() {
+            let r = controller.activate_safe_mode();
+
+            if r == ReturnCode::Ok {
+                warn!("safe mode activated: {:?}", room.name());
+            } else {
+                warn!("couldn't activate safe mode: {:?},{:?}", room.name(), r);
+            }
+        }
+    }
+}
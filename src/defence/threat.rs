@@ -0,0 +1,312 @@
+use crate::constants::*;
+use log::*;
+use screeps::constants::find::*;
+use screeps::constants::*;
+use screeps::{prelude::*, Creep, Part, ResourceType, Room};
+
+use lazy_static::lazy_static;
+use std::sync::RwLock;
+
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
+pub enum HostileKind {
+    SCOUT,
+    INVADER,
+    HARVESTER,
+    HEALER,
+    DISMANTLER,
+    ATTACKER,
+}
+
+#[derive(Clone, Debug)]
+pub struct HostileThreat {
+    pub creep: Creep,
+    pub kind: HostileKind,
+    pub owner: String,
+    pub damage: u32,
+    pub heal: u32,
+    pub is_boosted: bool,
+    pub score: u32,
+}
+
+// tickごとに1回だけmemoryから読む.
+#[derive(Default)]
+struct AllyData {
+    allies: Vec<String>,
+    updated: Option<u32>,
+}
+
+lazy_static! {
+    static ref ALLY_CACHE: RwLock<AllyData> = RwLock::new(AllyData::default());
+}
+
+// memoryの"allies"にjsonの配列で名前を書いておく.
+fn load_allies() -> Vec<String> {
+    let json_str = screeps::memory::root()
+        .string("allies")
+        .unwrap_or(None)
+        .unwrap_or(String::from("[]"));
+
+    return serde_json::from_str(json_str.as_str()).unwrap_or(Vec::new());
+}
+
+fn refresh_ally_cache() {
+    let mut ally_cache = ALLY_CACHE.write().unwrap();
+
+    if ally_cache.updated == Some(screeps::game::time()) {
+        return;
+    }
+
+    ally_cache.allies = load_allies();
+    ally_cache.updated = Some(screeps::game::time());
+}
+
+pub fn get_allies() -> Vec<String> {
+    refresh_ally_cache();
+    return ALLY_CACHE.read().unwrap().allies.clone();
+}
+
+pub fn is_ally(owner: &str) -> bool {
+    refresh_ally_cache();
+    return ALLY_CACHE
+        .read()
+        .unwrap()
+        .allies
+        .iter()
+        .any(|ally| ally == owner);
+}
+
+// boostによる倍率.
+pub fn calc_boost_multiplier(part: Part, boost: Option<ResourceType>) -> u32 {
+    let boost = match boost {
+        Some(boost) => boost,
+        None => return 1,
+    };
+
+    match (part, boost) {
+        (Part::Attack, ResourceType::UtriumHydride) => 2,
+        (Part::Attack, ResourceType::UtriumAcid) => 3,
+        (Part::Attack, ResourceType::CatalyzedUtriumAcid) => 4,
+        (Part::RangedAttack, ResourceType::KeaniumOxide) => 2,
+        (Part::RangedAttack, ResourceType::KeaniumAlkalide) => 3,
+        (Part::RangedAttack, ResourceType::CatalyzedKeaniumAlkalide) => 4,
+        (Part::Heal, ResourceType::LemergiumOxide) => 2,
+        (Part::Heal, ResourceType::LemergiumAlkalide) => 3,
+        (Part::Heal, ResourceType::CatalyzedLemergiumAlkalide) => 4,
+        (Part::Work, ResourceType::ZynthiumHydride) => 2,
+        (Part::Work, ResourceType::ZynthiumAcid) => 3,
+        (Part::Work, ResourceType::CatalyzedZynthiumAcid) => 4,
+        _ => 1,
+    }
+}
+
+// boostされたTOUGHが受けるダメージの倍率.
+pub fn calc_tough_ratio(boost: Option<ResourceType>) -> f64 {
+    match boost {
+        Some(ResourceType::GhodiumOxide) => 0.7 as f64,
+        Some(ResourceType::GhodiumAlkalide) => 0.5 as f64,
+        Some(ResourceType::CatalyzedGhodiumAlkalide) => 0.3 as f64,
+        _ => 1 as f64,
+    }
+}
+
+// ダメージは先頭のpartから入る. boostされたTOUGHが残っている間は減らされる.
+// partsは(倍率, 残りhits)を先頭から並べたもの.
+pub fn calc_damage_after_tough(parts: &Vec<(f64, u32)>, damage: u32) -> u32 {
+    let mut remaining = damage as f64;
+    let mut actual = 0 as f64;
+
+    for (ratio, hits) in parts.iter() {
+        if remaining <= 0 as f64 || *ratio >= 1 as f64 {
+            break;
+        }
+
+        // このpartを壊すのに必要な元のダメージ.
+        let raw_hits = *hits as f64 / *ratio;
+        if remaining <= raw_hits {
+            actual += remaining * *ratio;
+            remaining = 0 as f64;
+        } else {
+            actual += *hits as f64;
+            remaining -= raw_hits;
+        }
+    }
+
+    return (actual + remaining) as u32;
+}
+
+pub fn calc_actual_damage(enemy: &Creep, damage: u32) -> u32 {
+    let parts = enemy
+        .body()
+        .iter()
+        .filter(|body_part| body_part.hits > 0)
+        .map(|body_part| {
+            let ratio = if body_part.part == Part::Tough {
+                calc_tough_ratio(body_part.boost)
+            } else {
+                1 as f64
+            };
+            (ratio, body_part.hits)
+        })
+        .collect();
+
+    return calc_damage_after_tough(&parts, damage);
+}
+
+// 敵creepの火力見積もり(damage/tick). WORKは建物にしか効かないので数えない.
+pub fn calc_hostile_damage(enemy: &Creep) -> u32 {
+    let mut damage: u32 = 0;
+
+    for body_part in enemy.body() {
+        if body_part.hits <= 0 {
+            continue;
+        }
+
+        let multiplier = calc_boost_multiplier(body_part.part, body_part.boost);
+
+        match body_part.part {
+            Part::Attack => {
+                damage += ATTACK_POWER * multiplier;
+            }
+
+            Part::RangedAttack => {
+                damage += RANGED_ATTACK_POWER * multiplier;
+            }
+
+            _ => {}
+        }
+    }
+
+    return damage;
+}
+
+// 隣接した建物へのdismantleの見積もり(damage/tick).
+pub fn calc_hostile_dismantle(enemy: &Creep) -> u32 {
+    let mut damage: u32 = 0;
+
+    for body_part in enemy.body() {
+        if body_part.hits > 0 && body_part.part == Part::Work {
+            damage += DISMANTLE_POWER * calc_boost_multiplier(body_part.part, body_part.boost);
+        }
+    }
+
+    return damage;
+}
+
+pub fn calc_hostile_heal(enemy: &Creep) -> u32 {
+    let mut heal: u32 = 0;
+
+    for body_part in enemy.body() {
+        if body_part.hits > 0 && body_part.part == Part::Heal {
+            heal += HEAL_POWER * calc_boost_multiplier(body_part.part, body_part.boost);
+        }
+    }
+
+    return heal;
+}
+
+pub fn classify_hostile(enemy: &Creep) -> HostileKind {
+    let owner = enemy.owner_name();
+    if owner == INVADER_USERNAME || owner == SOURCE_KEEPER_USERNAME {
+        return HostileKind::INVADER;
+    }
+
+    let num_attack =
+        enemy.get_active_bodyparts(Part::Attack) + enemy.get_active_bodyparts(Part::RangedAttack);
+    let num_heal = enemy.get_active_bodyparts(Part::Heal);
+    let num_work = enemy.get_active_bodyparts(Part::Work);
+    let num_carry = enemy.get_active_bodyparts(Part::Carry);
+
+    if num_attack > 0 {
+        return HostileKind::ATTACKER;
+    }
+
+    if num_heal > 0 {
+        return HostileKind::HEALER;
+    }
+
+    if num_work > 0 && num_carry > 0 {
+        return HostileKind::HARVESTER;
+    }
+
+    if num_work > 0 {
+        return HostileKind::DISMANTLER;
+    }
+
+    return HostileKind::SCOUT;
+}
+
+// 種類ごとの優先度. 回復役は先に落とす.
+fn calc_threat_score(kind: HostileKind, damage: u32, heal: u32) -> u32 {
+    let kind_score = match kind {
+        HostileKind::HEALER => THREAT_SCORE_HEALER,
+        HostileKind::ATTACKER | HostileKind::INVADER => THREAT_SCORE_ATTACKER,
+        HostileKind::DISMANTLER => THREAT_SCORE_DISMANTLER,
+        HostileKind::HARVESTER => THREAT_SCORE_HARVESTER,
+        HostileKind::SCOUT => 0,
+    };
+
+    return kind_score + damage + heal * THREAT_HEAL_WEIGHT;
+}
+
+pub fn assess_hostile(enemy: &Creep) -> HostileThreat {
+    let kind = classify_hostile(enemy);
+    let damage = calc_hostile_damage(enemy);
+    let heal = calc_hostile_heal(enemy);
+    let is_boosted = enemy
+        .body()
+        .iter()
+        .any(|body_part| body_part.boost.is_some());
+
+    return HostileThreat {
+        creep: enemy.clone(),
+        kind: kind,
+        owner: enemy.owner_name(),
+        damage: damage,
+        heal: heal,
+        is_boosted: is_boosted,
+        score: calc_threat_score(kind, damage, heal),
+    };
+}
+
+// 味方を除いた敵を危険な順に並べる.
+pub fn get_ranked_hostiles(room: &Room) -> Vec<HostileThreat> {
+    let allies = get_allies();
+
+    let mut hostiles: Vec<HostileThreat> = room
+        .find(HOSTILE_CREEPS)
+        .iter()
+        .filter(|enemy| allies.contains(&enemy.owner_name()) == false)
+        .map(|enemy| assess_hostile(enemy))
+        .collect();
+
+    hostiles.sort_by(|a, b| b.score.cmp(&a.score));
+
+    for hostile in hostiles.iter() {
+        debug!(
+            "hostile {}: {:?}, owner:{}, damage:{}, heal:{}, boosted:{}",
+            hostile.creep.name(),
+            hostile.kind,
+            hostile.owner,
+            hostile.damage,
+            hostile.heal,
+            hostile.is_boosted
+        );
+    }
+
+    return hostiles;
+}
+
+pub fn get_hostile_creeps(room: &Room) -> Vec<Creep> {
+    return get_ranked_hostiles(room)
+        .into_iter()
+        .map(|hostile| hostile.creep)
+        .collect();
+}
+
+// 攻撃・回復能力のある敵だけ.
+pub fn get_dangerous_hostiles(room: &Room) -> Vec<HostileThreat> {
+    return get_ranked_hostiles(room)
+        .into_iter()
+        .filter(|hostile| hostile.damage > 0 || hostile.heal > 0)
+        .collect();
+}
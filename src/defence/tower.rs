@@ -1,119 +1,213 @@
 use crate::constants::*;
+use crate::defence::threat::*;
+use crate::manage::repair::*;
+use crate::plan::rampart::*;
 use crate::util::*;
 use log::*;
 use screeps::constants::find::*;
+use screeps::constants::*;
 use screeps::Structure;
 use screeps::{
     find, game, pathfinder::SearchResults, prelude::*, Attackable, Creep, Part, ResourceType,
-    ReturnCode, RoomObjectProperties, StructureType,
+    ReturnCode, Room, RoomObjectProperties, StructureTower, StructureType,
 };
 
+// 距離減衰込みのtowerの攻撃力.
+pub fn calc_tower_damage(range: u32) -> u32 {
+    if range <= TOWER_OPTIMAL_RANGE {
+        return TOWER_POWER_ATTACK;
+    }
+
+    let effective_range = std::cmp::min(range, TOWER_FALLOFF_RANGE);
+    let falloff = TOWER_FALLOFF * (effective_range - TOWER_OPTIMAL_RANGE) as f64
+        / (TOWER_FALLOFF_RANGE - TOWER_OPTIMAL_RANGE) as f64;
+
+    return (TOWER_POWER_ATTACK as f64 * (1 as f64 - falloff)) as u32;
+}
+
+// 敵の周りの回復役による回復量(隣接は全量, 射程3までは1/3).
+pub fn calc_group_heal(target: &Creep, hostiles: &Vec<HostileThreat>) -> u32 {
+    let mut heal = 0;
+
+    for hostile in hostiles.iter() {
+        let range = hostile.creep.pos().get_range_to(&target.pos());
+
+        if range <= 1 {
+            heal += hostile.heal;
+        } else if range <= 3 {
+            heal += hostile.heal / 3;
+        }
+    }
+
+    return heal;
+}
+
+// boostされたTOUGHで減る分も引く.
+pub fn calc_towers_damage(towers: &Vec<StructureTower>, target: &Creep) -> u32 {
+    let damage: u32 = towers
+        .iter()
+        .map(|tower| calc_tower_damage(tower.pos().get_range_to(&target.pos())))
+        .sum();
+
+    return calc_actual_damage(target, damage);
+}
+
+// 全towerの合計火力が回復を上回る中で、一番危険な敵.
+fn select_tower_target(
+    towers: &Vec<StructureTower>,
+    hostiles: &Vec<HostileThreat>,
+) -> Option<Creep> {
+    for hostile in hostiles.iter() {
+        let damage = calc_towers_damage(towers, &hostile.creep);
+        let heal = calc_group_heal(&hostile.creep, hostiles);
+
+        debug!(
+            "tower target {}: damage:{}, heal:{}",
+            hostile.creep.name(),
+            damage,
+            heal
+        );
+
+        if damage > heal {
+            return Some(hostile.creep.clone());
+        }
+    }
+
+    return None;
+}
+
+fn run_tower_attack(room: &Room, towers: &Vec<StructureTower>) -> bool {
+    let hostiles = get_ranked_hostiles(room);
+
+    if hostiles.len() <= 0 {
+        return false;
+    }
+
+    let target = match select_tower_target(towers, &hostiles) {
+        Some(target) => target,
+        None => {
+            // 削り切れないなら撃たない(energy切れ狙いの対策).
+            info!("tower hold fire: {:?} hostiles heal out", room.name());
+            return false;
+        }
+    };
+
+    for my_tower in towers.iter() {
+        let r = my_tower.attack(&target);
+
+        if r == ReturnCode::Ok {
+            info!("attack to enemy!!");
+        }
+    }
+
+    return true;
+}
+
 pub fn run_tower() {
-    for game_structure in screeps::game::structures::values() {
-        let mut is_done = false;
+    for room in screeps::game::rooms::values() {
+        let towers: Vec<StructureTower> = room
+            .find(MY_STRUCTURES)
+            .into_iter()
+            .filter_map(|structure| match structure {
+                Structure::Tower(my_tower) => Some(my_tower),
+                _ => None,
+            })
+            .filter(|my_tower| my_tower.store_of(ResourceType::Energy) >= TOWER_ENERGY_COST)
+            .collect();
 
-        if check_my_structure(&game_structure) == true {
-            match game_structure {
-                Structure::Tower(my_tower) => {
-                    debug!("check enemies {}", my_tower.id());
-                    let enemies = my_tower
-                        .room()
-                        .expect("room is not visible to you")
-                        .find(HOSTILE_CREEPS);
+        if towers.len() <= 0 {
+            continue;
+        }
 
-                    let room_name = my_tower.room().expect("room is not visible to you").name();
+        if run_tower_attack(&room, &towers) == true {
+            continue;
+        }
 
-                    for enemy in enemies {
-                        debug!("try attack enemy {}", my_tower.id());
-                        let r = my_tower.attack(&enemy);
+        // 攻撃・回復できる敵がいる間は籠城用にenergyを残す.
+        let is_siege = get_dangerous_hostiles(&room).len() > 0;
 
-                        if r == ReturnCode::Ok {
-                            info!("attack to enemy!!");
-                            is_done = true;
-                            break;
-                        }
-                    }
+        for my_tower in towers.iter() {
+            run_tower_maintenance(my_tower, is_siege);
+        }
+    }
+}
 
-                    if is_done {
-                        continue;
-                    }
+fn run_tower_maintenance(my_tower: &StructureTower, is_siege: bool) {
+    let mut is_done = false;
 
-                    debug!("heal creeps {}", my_tower.id());
-                    let my_creeps = my_tower
-                        .room()
-                        .expect("room is not visible to you")
-                        .find(MY_CREEPS);
-
-                    for my_creep in my_creeps {
-                        if my_creep.hits() < my_creep.hits_max() {
-                            debug!("heal my creep {}", my_tower.id());
-                            let r = my_tower.heal(&my_creep);
-
-                            if r == ReturnCode::Ok {
-                                info!("heal my creep!!");
-                                is_done = true;
-                                break;
-                            }
-                        }
-                    }
-                    if is_done {
-                        continue;
-                    }
+    if is_siege && my_tower.store_of(ResourceType::Energy) <= TOWER_SIEGE_RESERVE_ENERGY {
+        debug!("tower keeps energy for siege {}", my_tower.id());
+        return;
+    }
 
-                    if my_tower.store_of(ResourceType::Energy)
-                        > (my_tower.store_capacity(Some(ResourceType::Energy)) * 2 / 3)
-                    {
-                        debug!("repair structure {}", my_tower.id());
-
-                        let my_structures = my_tower
-                            .room()
-                            .expect("room is not visible to you")
-                            .find(STRUCTURES);
-
-                        // 残り時間が短いものを優先.
-                        for structure in my_structures.iter() {
-                            if structure.structure_type() != StructureType::Wall {
-                                if check_repairable(structure) {
-                                    if get_live_tickcount(structure).unwrap_or(10000)
-                                        <= REPAIRER_DYING_THRESHOLD
-                                    {
-                                        let r = my_tower.repair(structure);
-                                        if r == ReturnCode::Ok {
-                                            info!("repair my structure!!");
-                                            is_done = true;
-                                            break;
-                                        }
-                                    }
-                                }
-                            }
-                        }
-                        if is_done {
-                            continue;
-                        }
+    debug!("heal creeps {}", my_tower.id());
+    let my_creeps = my_tower
+        .room()
+        .expect("room is not visible to you")
+        .find(MY_CREEPS);
 
-                        // HPが低い物を確認.
-                        let stats = get_hp_average(&room_name);
-                        let threshold = stats.1 + (stats.0 - stats.1) / 1000;
-
-                        for structure in my_structures.iter() {
-                            if check_repairable(structure) {
-                                if get_hp(structure).unwrap_or(0) <= (threshold + 1) as u32 {
-                                    let r = my_tower.repair(structure);
-                                    if r == ReturnCode::Ok {
-                                        info!("repair my structure!!");
-                                        is_done = true;
-                                        break;
-                                    }
-                                }
-                            }
-                        }
-                        if is_done {
-                            continue;
+    for my_creep in my_creeps {
+        if my_creep.hits() < my_creep.hits_max() {
+            debug!("heal my creep {}", my_tower.id());
+            let r = my_tower.heal(&my_creep);
+
+            if r == ReturnCode::Ok {
+                info!("heal my creep!!");
+                is_done = true;
+                break;
+            }
+        }
+    }
+    if is_done {
+        return;
+    }
+
+    // 籠城中はrepairしない.
+    if is_siege == false
+        && my_tower.store_of(ResourceType::Energy)
+            > (my_tower.store_capacity(Some(ResourceType::Energy)) * 2 / 3)
+    {
+        debug!("repair structure {}", my_tower.id());
+
+        let mut my_structures = my_tower
+            .room()
+            .expect("room is not visible to you")
+            .find(STRUCTURES);
+
+        // 外周の要になるrampartを先に見る.
+        my_structures.sort_by_key(|structure| check_critical_rampart(structure) == false);
+
+        // 残り時間が短いものを優先.
+        for structure in my_structures.iter() {
+            if structure.structure_type() != StructureType::Wall {
+                if check_repairable(structure) {
+                    if get_live_tickcount(structure).unwrap_or(10000) <= REPAIRER_DYING_THRESHOLD {
+                        let r = my_tower.repair(structure);
+                        if r == ReturnCode::Ok {
+                            info!("repair my structure!!");
+                            is_done = true;
+                            break;
                         }
                     }
                 }
+            }
+        }
+        if is_done {
+            return;
+        }
+
+        // 目標HPに対して減っている物を確認.
+        let schedule = make_repair_schedule(&my_tower.room().expect("room is not visible to you"));
 
-                _ => {}
+        if let Some(structure) = find_repair_target(&my_structures, &schedule) {
+            let r = my_tower.repair(&structure);
+            if r == ReturnCode::Ok {
+                info!("repair my structure!!");
+                record_repair_spent(
+                    &schedule.room_name,
+                    structure.structure_type(),
+                    REPAIR_TOWER_ENERGY_COST,
+                );
             }
         }
     }
@@ -4,13 +4,15 @@ use log::*;
 
 use stdweb::js;
 
-//mod attack;
 //mod defence;
+mod attack;
 mod constants;
 mod create;
 mod creeps;
 mod defence;
 mod manage;
+mod plan;
+mod route;
 mod util;
 
 mod logging;
@@ -49,21 +51,67 @@ fn game_loop() {
 
     util::clear_init_flag();
 
+    info!("running intel cpu:{}", screeps::game::cpu::get_used());
+    manage::intel::run_intel();
+    manage::intel::run_observer();
+
     info!("running market cpu:{}", screeps::game::cpu::get_used());
     manage::market::run_market();
 
     info!("running links cpu:{}", screeps::game::cpu::get_used());
     manage::link::run_link();
 
+    info!("running boost cpu:{}", screeps::game::cpu::get_used());
+    manage::boost::run_boost();
+
+    info!("running labs cpu:{}", screeps::game::cpu::get_used());
+    manage::lab::run_lab();
+
+    info!("running factory cpu:{}", screeps::game::cpu::get_used());
+    manage::factory::run_factory();
+
+    info!("running layout cpu:{}", screeps::game::cpu::get_used());
+    plan::layout::run_layout();
+
+    info!("running roads cpu:{}", screeps::game::cpu::get_used());
+    plan::road::run_road_planner();
+
+    info!("running ramparts cpu:{}", screeps::game::cpu::get_used());
+    plan::rampart::run_rampart_planner();
+
+    info!("running economy cpu:{}", screeps::game::cpu::get_used());
+    manage::economy::run_economy();
+
+    info!("running remote cpu:{}", screeps::game::cpu::get_used());
+    manage::remote::run_remote();
+
+    info!("running expansion cpu:{}", screeps::game::cpu::get_used());
+    manage::expansion::run_expansion();
+
+    info!("running deposit cpu:{}", screeps::game::cpu::get_used());
+    manage::deposit::run_deposit();
+
+    info!("running power cpu:{}", screeps::game::cpu::get_used());
+    manage::power::run_power();
+
+    info!("running squads cpu:{}", screeps::game::cpu::get_used());
+    attack::squad::run_squads();
+
     info!("running spawns cpu:{}", screeps::game::cpu::get_used());
     create::spawn::do_spawn();
 
+    info!("running logistics cpu:{}", screeps::game::cpu::get_used());
+    manage::logistics::run_logistics();
+
     info!("running creeps cpu:{}", screeps::game::cpu::get_used());
     creeps::creep_loop();
 
     info!("running towers cpu:{}", screeps::game::cpu::get_used());
     defence::tower::run_tower();
 
+    info!("running safe mode cpu:{}", screeps::game::cpu::get_used());
+    defence::safe_mode::run_safe_mode();
+
     let time = screeps::game::time();
 
     if time % 32 == 3 {
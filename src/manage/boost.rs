@@ -0,0 +1,245 @@
+use crate::constants::*;
+use crate::manage::lab::*;
+use log::*;
+use screeps::{
+    game, objects::StructureLab, prelude::*, Creep, MemoryReference, Part, ResourceType, Room,
+    RoomName,
+};
+use std::collections::HashMap;
+
+use lazy_static::lazy_static;
+use std::sync::RwLock;
+
+lazy_static! {
+    // 部屋ごとにboost用に押さえたlab(id)と入れる化合物.
+    static ref BOOST_LAB_CACHE: RwLock<HashMap<RoomName, Vec<(String, ResourceType)>>> =
+        RwLock::new(HashMap::new());
+}
+
+fn part_to_str(part: Part) -> Option<&'static str> {
+    match part {
+        Part::Attack => Some("attack"),
+        Part::RangedAttack => Some("ranged_attack"),
+        Part::Heal => Some("heal"),
+        Part::Tough => Some("tough"),
+        Part::Move => Some("move"),
+        Part::Work => Some("work"),
+        _ => None,
+    }
+}
+
+fn str_to_part(part_str: &str) -> Option<Part> {
+    match part_str {
+        "attack" => Some(Part::Attack),
+        "ranged_attack" => Some(Part::RangedAttack),
+        "heal" => Some(Part::Heal),
+        "tough" => Some(Part::Tough),
+        "move" => Some(Part::Move),
+        "work" => Some(Part::Work),
+        _ => None,
+    }
+}
+
+// partごとに使える化合物. 強い順.
+pub fn get_boost_compounds(part: Part) -> Vec<ResourceType> {
+    match part {
+        Part::Attack => vec![
+            ResourceType::CatalyzedUtriumAcid,
+            ResourceType::UtriumAcid,
+            ResourceType::UtriumHydride,
+        ],
+        Part::RangedAttack => vec![
+            ResourceType::CatalyzedKeaniumAlkalide,
+            ResourceType::KeaniumAlkalide,
+            ResourceType::KeaniumOxide,
+        ],
+        Part::Heal => vec![
+            ResourceType::CatalyzedLemergiumAlkalide,
+            ResourceType::LemergiumAlkalide,
+            ResourceType::LemergiumOxide,
+        ],
+        Part::Tough => vec![
+            ResourceType::CatalyzedGhodiumAlkalide,
+            ResourceType::GhodiumAlkalide,
+            ResourceType::GhodiumOxide,
+        ],
+        Part::Move => vec![
+            ResourceType::CatalyzedZynthiumAlkalide,
+            ResourceType::ZynthiumAlkalide,
+            ResourceType::ZynthiumOxide,
+        ],
+        Part::Work => vec![
+            ResourceType::CatalyzedZynthiumAcid,
+            ResourceType::ZynthiumAcid,
+            ResourceType::ZynthiumHydride,
+        ],
+        _ => Vec::new(),
+    }
+}
+
+// spawn時にboostしたいpartを書いておく.
+pub fn set_boost_request(memory: &MemoryReference, parts: &[Part]) {
+    let mut part_strs: Vec<&str> = parts.iter().filter_map(|part| part_to_str(*part)).collect();
+    part_strs.sort();
+    part_strs.dedup();
+
+    if part_strs.len() <= 0 {
+        return;
+    }
+
+    memory.set(
+        "boost_parts",
+        serde_json::to_string(&part_strs).unwrap().as_str(),
+    );
+}
+
+pub fn get_boost_request(creep: &Creep) -> Vec<Part> {
+    let json_str = match creep.memory().string("boost_parts").unwrap_or(None) {
+        Some(json_str) => json_str,
+        None => return Vec::new(),
+    };
+
+    let part_strs: Vec<String> = serde_json::from_str(json_str.as_str()).unwrap_or(Vec::new());
+    return part_strs
+        .iter()
+        .filter_map(|part_str| str_to_part(part_str.as_str()))
+        .collect();
+}
+
+pub fn save_boost_request(creep: &Creep, parts: &Vec<Part>) {
+    if parts.len() <= 0 {
+        creep.memory().del("boost_parts");
+        return;
+    }
+
+    let part_strs: Vec<&str> = parts.iter().filter_map(|part| part_to_str(*part)).collect();
+    creep.memory().set(
+        "boost_parts",
+        serde_json::to_string(&part_strs).unwrap().as_str(),
+    );
+}
+
+// labとstorage/terminalの合計.
+fn get_compound_stock(room: &Room, compound: ResourceType) -> u32 {
+    let mut amount = get_room_stock(room, compound);
+
+    if let Some(setup) = get_lab_setup(room) {
+        for lab in setup.outputs.iter() {
+            amount += lab.store_of(compound);
+        }
+    }
+
+    return amount;
+}
+
+// 部屋に十分ある一番強い化合物. なければNone.
+pub fn select_boost_compound(room: &Room, part: Part, num_parts: u32) -> Option<ResourceType> {
+    let needed = num_parts * LAB_BOOST_MINERAL;
+
+    return get_boost_compounds(part)
+        .into_iter()
+        .find(|compound| get_compound_stock(room, *compound) >= needed);
+}
+
+pub fn get_boost_labs(room_name: &RoomName) -> Vec<(String, ResourceType)> {
+    let boost_labs = BOOST_LAB_CACHE.read().unwrap();
+    return boost_labs.get(room_name).cloned().unwrap_or(Vec::new());
+}
+
+pub fn get_boost_lab_structures(room_name: &RoomName) -> Vec<(StructureLab, ResourceType)> {
+    return get_boost_labs(room_name)
+        .iter()
+        .filter_map(|(id, compound)| {
+            let lab = game::get_object_typed::<StructureLab>(id.parse().ok()?).unwrap_or(None)?;
+            Some((lab, *compound))
+        })
+        .collect();
+}
+
+pub fn get_boost_compound_for_lab(
+    room_name: &RoomName,
+    lab: &StructureLab,
+) -> Option<ResourceType> {
+    let lab_id = lab.id().to_string();
+
+    return get_boost_labs(room_name)
+        .iter()
+        .find(|(id, _compound)| *id == lab_id)
+        .map(|(_id, compound)| *compound);
+}
+
+pub fn count_active_parts(creep: &Creep, part: Part) -> u32 {
+    return creep
+        .body()
+        .iter()
+        .filter(|body_part| body_part.part == part && body_part.boost.is_none())
+        .count() as u32;
+}
+
+// boost待ちのcreepが要る化合物を集めて、output labの後ろから割り当てる.
+fn assign_boost_labs(room: &Room) {
+    let mut needed: Vec<ResourceType> = Vec::new();
+
+    for creep in game::creeps::values() {
+        if creep.room().map(|r| r.name()) != Some(room.name()) {
+            continue;
+        }
+
+        for part in get_boost_request(&creep) {
+            let num_parts = count_active_parts(&creep, part);
+            if let Some(compound) = select_boost_compound(room, part, num_parts) {
+                if needed.contains(&compound) == false {
+                    needed.push(compound);
+                }
+            }
+        }
+    }
+
+    let mut boost_labs = BOOST_LAB_CACHE.write().unwrap();
+
+    if needed.len() <= 0 {
+        boost_labs.remove(&room.name());
+        return;
+    }
+
+    let setup = match get_lab_setup(room) {
+        Some(setup) => setup,
+        None => return,
+    };
+
+    // 既に入っている化合物のlabを優先して使う.
+    let mut outputs: Vec<StructureLab> = setup.outputs.iter().rev().cloned().collect();
+    let mut assigned: Vec<(String, ResourceType)> = Vec::new();
+
+    for compound in needed.iter().take(LAB_MAX_BOOST_LABS as usize) {
+        let index = outputs
+            .iter()
+            .position(|lab| get_lab_mineral(lab) == Some(*compound))
+            .unwrap_or(0);
+
+        if index >= outputs.len() {
+            break;
+        }
+
+        let lab = outputs.remove(index);
+        assigned.push((lab.id().to_string(), *compound));
+    }
+
+    debug!("boost labs {}: {:?}", room.name(), assigned);
+    boost_labs.insert(room.name(), assigned);
+}
+
+pub fn run_boost() {
+    for room in game::rooms::values() {
+        let controller = match room.controller() {
+            Some(controller) => controller,
+            None => continue,
+        };
+
+        if controller.my() == false {
+            continue;
+        }
+
+        assign_boost_labs(&room);
+    }
+}
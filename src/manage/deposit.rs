@@ -0,0 +1,310 @@
+use crate::constants::*;
+use crate::manage::intel::*;
+use log::*;
+use screeps::constants::find::*;
+use screeps::constants::*;
+use screeps::{game, prelude::*, Part, Room, RoomName};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct DepositExpedition {
+    pub home: String,
+    pub room: String,
+    pub x: u32,
+    pub y: u32,
+    pub deposit_type: String,
+    pub last_cooldown: u32,
+    pub decay_at: u32,
+    pub distance: u32,
+    pub num_harvesters: u32,
+    pub created: u32,
+}
+
+// depositのidごと.
+type DepositExpeditionMap = HashMap<String, DepositExpedition>;
+
+pub fn load_deposit_expeditions() -> DepositExpeditionMap {
+    let json_str = screeps::memory::root()
+        .string("deposit_expeditions")
+        .unwrap_or(None)
+        .unwrap_or(String::from("{}"));
+
+    return serde_json::from_str(json_str.as_str()).unwrap_or(HashMap::new());
+}
+
+fn save_deposit_expeditions(expedition_map: &DepositExpeditionMap) {
+    let json_str = serde_json::to_string(expedition_map).unwrap();
+    screeps::memory::root().set("deposit_expeditions", json_str);
+}
+
+pub fn get_deposit_expedition(deposit_id: &str) -> Option<DepositExpedition> {
+    return load_deposit_expeditions().get(deposit_id).cloned();
+}
+
+pub fn count_deposit_creeps(role: &str, deposit_id: &str) -> u32 {
+    let mut count = 0;
+
+    for creep in game::creeps::values() {
+        let creep_role = creep.memory().string("role").unwrap_or(None);
+        let creep_deposit = creep.memory().string("deposit_id").unwrap_or(None);
+
+        if creep_role.as_ref().map(|r| r.as_str()) == Some(role)
+            && creep_deposit.as_ref().map(|s| s.as_str()) == Some(deposit_id)
+        {
+            count += 1;
+        }
+    }
+
+    return count;
+}
+
+// CARRYを少し持たせて、残りはWORK:MOVE=1:1.
+pub fn make_deposit_harvester_body(energy_capacity: u32) -> Vec<Part> {
+    let mut body = Vec::new();
+    let mut cost = 0;
+
+    for _i in 0..DEPOSIT_HARVESTER_CARRY_PARTS {
+        body.extend([Part::Carry, Part::Move].iter().cloned());
+        cost += Part::Carry.cost() + Part::Move.cost();
+    }
+
+    let body_unit = [Part::Work, Part::Move];
+    let body_cost: u32 = body_unit.iter().map(|p| p.cost()).sum();
+    let mut set_num = 0;
+
+    while set_num < DEPOSIT_HARVESTER_MAX_SET
+        && cost + body_cost <= energy_capacity
+        && ((body.len() + body_unit.len()) <= MAX_CREEP_SIZE as usize)
+    {
+        body.extend(body_unit.iter().cloned());
+        cost += body_cost;
+        set_num += 1;
+    }
+
+    return body;
+}
+
+pub fn make_deposit_hauler_body(energy_capacity: u32) -> Vec<Part> {
+    let body_unit = [Part::Carry, Part::Move];
+    let body_cost: u32 = body_unit.iter().map(|p| p.cost()).sum();
+
+    let mut body = Vec::new();
+    let mut set_num = std::cmp::min(energy_capacity / body_cost, DEPOSIT_HAULER_MAX_SET);
+
+    while set_num > 0 {
+        body.extend(body_unit.iter().cloned());
+        set_num -= 1;
+    }
+
+    return body;
+}
+
+// 1体が一生で掘れる量の見積もり. cooldownは掘るたびに伸びるので今の値で数える.
+fn calc_expected_yield(work_parts: u32, last_cooldown: u32, distance: u32) -> u32 {
+    let travel_time = distance * DEPOSIT_TICKS_PER_ROOM;
+    let working_time = CREEP_LIFE_TIME.saturating_sub(travel_time);
+
+    return working_time / (last_cooldown + 1) * work_parts;
+}
+
+// 行き帰りの間に掘れる分を運べるだけ.
+pub fn calc_needed_haulers(expedition: &DepositExpedition, hauler_capacity: u32) -> u32 {
+    if hauler_capacity <= 0 {
+        return 0;
+    }
+
+    let harvester_body = match expedition
+        .home
+        .parse::<RoomName>()
+        .ok()
+        .and_then(|name| game::rooms::get(name))
+    {
+        Some(home) => make_deposit_harvester_body(home.energy_capacity_available()),
+        None => return 0,
+    };
+    let work_parts = harvester_body.iter().filter(|p| **p == Part::Work).count() as u32;
+
+    let round_trip = expedition.distance * DEPOSIT_TICKS_PER_ROOM * 2;
+    let amount =
+        work_parts * expedition.num_harvesters * round_trip / (expedition.last_cooldown + 1);
+    let needed = (amount + hauler_capacity - 1) / hauler_capacity;
+
+    return std::cmp::min(std::cmp::max(needed, 1), DEPOSIT_MAX_HAULERS);
+}
+
+// terminalがあって一番近い部屋.
+fn find_deposit_home(room_name: RoomName) -> Option<(Room, u32)> {
+    let mut best: Option<(Room, u32)> = None;
+
+    for room in game::rooms::values() {
+        let controller = match room.controller() {
+            Some(controller) => controller,
+            None => continue,
+        };
+
+        if controller.my() == false
+            || controller.level() < DEPOSIT_MIN_RCL
+            || room.terminal().is_none()
+        {
+            continue;
+        }
+
+        let distance = game::map::get_room_linear_distance(room.name(), room_name, false);
+        if distance > DEPOSIT_MAX_DISTANCE {
+            continue;
+        }
+
+        let is_nearer = match &best {
+            Some((_room, best_distance)) => distance < *best_distance,
+            None => true,
+        };
+        if is_nearer == true {
+            best = Some((room, distance));
+        }
+    }
+
+    return best;
+}
+
+fn plan_deposit_expeditions(expedition_map: &mut DepositExpeditionMap) {
+    for (room_key, room_intel) in get_all_room_intel() {
+        if game::time() - room_intel.last_seen > DEPOSIT_INTEL_MAX_AGE {
+            continue;
+        }
+
+        for deposit in room_intel.deposits.iter() {
+            if expedition_map.len() >= DEPOSIT_MAX_EXPEDITIONS as usize {
+                return;
+            }
+
+            if expedition_map.contains_key(&deposit.id)
+                || deposit.last_cooldown > DEPOSIT_START_MAX_COOLDOWN
+            {
+                continue;
+            }
+
+            let room_name: RoomName = match room_key.parse() {
+                Ok(room_name) => room_name,
+                Err(_err) => continue,
+            };
+
+            let (home, distance) = match find_deposit_home(room_name) {
+                Some(home) => home,
+                None => continue,
+            };
+
+            // 着く前に消えるなら行かない.
+            let travel_time = distance * DEPOSIT_TICKS_PER_ROOM;
+            if deposit.decay_at < game::time() + travel_time + DEPOSIT_MIN_WORKING_TICKS {
+                continue;
+            }
+
+            let body = make_deposit_harvester_body(home.energy_capacity_available());
+            let work_parts = body.iter().filter(|p| **p == Part::Work).count() as u32;
+            let expected = calc_expected_yield(work_parts, deposit.last_cooldown, distance);
+            if expected < DEPOSIT_MIN_YIELD {
+                debug!(
+                    "deposit is not worth it: {}, {:?}, yield:{}",
+                    room_key, deposit, expected
+                );
+                continue;
+            }
+
+            let num_harvesters = std::cmp::min(deposit.free_tiles, DEPOSIT_MAX_HARVESTERS);
+            if num_harvesters <= 0 {
+                continue;
+            }
+
+            info!(
+                "deposit expedition planned: {} {} from {}, cooldown:{}, yield:{}",
+                room_key,
+                deposit.deposit_type,
+                home.name(),
+                deposit.last_cooldown,
+                expected
+            );
+
+            expedition_map.insert(
+                deposit.id.clone(),
+                DepositExpedition {
+                    home: home.name().to_string(),
+                    room: room_key.clone(),
+                    x: deposit.x,
+                    y: deposit.y,
+                    deposit_type: deposit.deposit_type.clone(),
+                    last_cooldown: deposit.last_cooldown,
+                    decay_at: deposit.decay_at,
+                    distance: distance,
+                    num_harvesters: num_harvesters,
+                    created: game::time(),
+                },
+            );
+        }
+    }
+}
+
+// 続けるならtrue.
+fn update_deposit_expedition(deposit_id: &str, expedition: &mut DepositExpedition) -> bool {
+    if game::time() > expedition.decay_at {
+        info!("deposit decayed: {}", expedition.room);
+        return false;
+    }
+
+    let room = match expedition
+        .room
+        .parse::<RoomName>()
+        .ok()
+        .and_then(|name| game::rooms::get(name))
+    {
+        Some(room) => room,
+        None => return true,
+    };
+
+    let deposit = room
+        .find(DEPOSITS)
+        .into_iter()
+        .find(|deposit| deposit.id().to_string() == deposit_id);
+
+    let deposit = match deposit {
+        Some(deposit) => deposit,
+        None => {
+            info!("deposit is gone: {}", expedition.room);
+            return false;
+        }
+    };
+
+    expedition.last_cooldown = deposit.last_cooldown();
+    expedition.decay_at = game::time() + deposit.ticks_to_decay();
+
+    if expedition.last_cooldown > DEPOSIT_STOP_COOLDOWN {
+        info!(
+            "deposit expedition stopped: {}, cooldown:{}",
+            expedition.room, expedition.last_cooldown
+        );
+        return false;
+    }
+
+    return true;
+}
+
+pub fn run_deposit() {
+    let mut expedition_map = load_deposit_expeditions();
+
+    if game::time() % DEPOSIT_PLAN_INTERVAL == 0 {
+        plan_deposit_expeditions(&mut expedition_map);
+    }
+
+    let mut finished = Vec::new();
+    for (deposit_id, expedition) in expedition_map.iter_mut() {
+        if update_deposit_expedition(deposit_id.as_str(), expedition) == false {
+            finished.push(deposit_id.clone());
+        }
+    }
+
+    for deposit_id in finished {
+        expedition_map.remove(&deposit_id);
+    }
+
+    save_deposit_expeditions(&expedition_map);
+}
@@ -0,0 +1,351 @@
+use crate::constants::*;
+use log::*;
+use screeps::constants::find::*;
+use screeps::{prelude::*, Part, Room};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+use lazy_static::lazy_static;
+use std::sync::RwLock;
+
+#[derive(PartialEq, Eq, Clone, Copy, Debug, Serialize, Deserialize)]
+pub enum EconomyState {
+    BOOTSTRAP,
+    NORMAL,
+    RECOVERY,
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct RoomEconomyStats {
+    pub num_creeps: u32,
+    pub num_haulers: u32,
+    pub hauler_carry: u32,
+    pub energy_available: u32,
+    pub energy_capacity: u32,
+}
+
+type EconomyStateMap = HashMap<String, EconomyState>;
+
+// 状態はmemoryから, 統計はroom.findから. どちらもtickごとに1回だけ作る.
+#[derive(Default)]
+struct EconomyData {
+    states: EconomyStateMap,
+    stats: HashMap<String, RoomEconomyStats>,
+    updated: Option<u32>,
+}
+
+lazy_static! {
+    static ref ECONOMY_CACHE: RwLock<EconomyData> = RwLock::new(EconomyData::default());
+}
+
+fn refresh_economy_cache() {
+    let mut economy_cache = ECONOMY_CACHE.write().unwrap();
+
+    if economy_cache.updated == Some(screeps::game::time()) {
+        return;
+    }
+
+    economy_cache.states = load_economy_state();
+    economy_cache.stats.clear();
+    economy_cache.updated = Some(screeps::game::time());
+}
+
+fn load_economy_state() -> EconomyStateMap {
+    let json_str = screeps::memory::root()
+        .string("economy_state")
+        .unwrap_or(None)
+        .unwrap_or(String::from("{}"));
+
+    return serde_json::from_str(json_str.as_str()).unwrap_or(HashMap::new());
+}
+
+fn save_economy_state(state_map: &EconomyStateMap) {
+    let json_str = serde_json::to_string(state_map).unwrap();
+    screeps::memory::root().set("economy_state", json_str);
+
+    refresh_economy_cache();
+    ECONOMY_CACHE.write().unwrap().states = state_map.clone();
+}
+
+pub fn is_hauler_role(role: &str) -> bool {
+    return role == "harvester" || role == "harvester_spawn" || role == "hauler";
+}
+
+fn calc_room_economy_stats(room: &Room) -> RoomEconomyStats {
+    let mut stats = RoomEconomyStats::default();
+
+    for my_creep in room.find(MY_CREEPS) {
+        stats.num_creeps += 1;
+
+        let role = my_creep
+            .memory()
+            .string("role")
+            .unwrap_or(None)
+            .unwrap_or(String::from("none"));
+
+        if is_hauler_role(role.as_str()) && my_creep.get_active_bodyparts(Part::Carry) > 0 {
+            stats.num_haulers += 1;
+            stats.hauler_carry += my_creep.store_capacity(None);
+        }
+    }
+
+    stats.energy_available = room.energy_available();
+    stats.energy_capacity = room.energy_capacity_available();
+
+    return stats;
+}
+
+pub fn get_room_economy_stats(room: &Room) -> RoomEconomyStats {
+    refresh_economy_cache();
+
+    let room_key = room.name().to_string();
+    if let Some(stats) = ECONOMY_CACHE.read().unwrap().stats.get(&room_key) {
+        return stats.clone();
+    }
+
+    let stats = calc_room_economy_stats(room);
+    ECONOMY_CACHE
+        .write()
+        .unwrap()
+        .stats
+        .insert(room_key, stats.clone());
+
+    return stats;
+}
+
+// このtickの中でharvesterに回した分を足す.
+pub fn add_economy_hauler(room: &Room, carry: u32) {
+    let stats = get_room_economy_stats(room);
+
+    let mut economy_cache = ECONOMY_CACHE.write().unwrap();
+    let cached = economy_cache
+        .stats
+        .entry(room.name().to_string())
+        .or_insert(stats);
+    cached.num_haulers += 1;
+    cached.hauler_carry += carry;
+}
+
+// 状態遷移.
+//   BOOTSTRAP : creepが全滅 or 新規の部屋. 手持ちのenergyで最小構成のharvesterを出す.
+//   RECOVERY  : harvesterが足りない. 最小構成から徐々に大きくする.
+//   NORMAL    : 通常運転.
+pub fn next_economy_state(current: EconomyState, stats: &RoomEconomyStats) -> EconomyState {
+    if stats.num_creeps <= 0 {
+        return EconomyState::BOOTSTRAP;
+    }
+
+    match current {
+        EconomyState::BOOTSTRAP => {
+            if stats.num_haulers > 0 {
+                return EconomyState::RECOVERY;
+            }
+            return EconomyState::BOOTSTRAP;
+        }
+
+        EconomyState::RECOVERY => {
+            if stats.num_haulers >= ECONOMY_MIN_HAULERS
+                && (stats.hauler_carry as f64
+                    >= stats.energy_capacity as f64 * ECONOMY_RECOVERED_CARRY_RATE)
+            {
+                return EconomyState::NORMAL;
+            }
+            return EconomyState::RECOVERY;
+        }
+
+        EconomyState::NORMAL => {
+            if stats.num_haulers <= 0 {
+                return EconomyState::RECOVERY;
+            }
+            return EconomyState::NORMAL;
+        }
+    }
+}
+
+pub fn get_economy_state(room: &Room) -> EconomyState {
+    refresh_economy_cache();
+
+    return *ECONOMY_CACHE
+        .read()
+        .unwrap()
+        .states
+        .get(&room.name().to_string())
+        .unwrap_or(&EconomyState::NORMAL);
+}
+
+// 手持ちのenergyで作れる最小構成のharvester.
+// RECOVERY中は現在のenergyに応じて少しずつ大きくする.
+pub fn make_bootstrap_body(energy_available: u32, state: EconomyState) -> Vec<Part> {
+    let body_unit = [Part::Work, Part::Carry, Part::Move];
+    let body_cost: u32 = body_unit.iter().map(|p| p.cost()).sum();
+
+    let mut body = Vec::new();
+
+    if energy_available < body_cost {
+        return body;
+    }
+
+    let mut max_set = 1;
+    if state == EconomyState::RECOVERY {
+        max_set = ECONOMY_RECOVERY_MAX_BODY_SET;
+    }
+
+    let mut set_num = std::cmp::min(energy_available / body_cost, max_set);
+
+    while set_num > 0
+        && ((body.len() + body_unit.len()) < screeps::constants::MAX_CREEP_SIZE as usize)
+    {
+        body.extend(body_unit.iter().cloned());
+        set_num -= 1;
+    }
+
+    return body;
+}
+
+pub fn run_economy() {
+    let mut state_map = load_economy_state();
+
+    for room in screeps::game::rooms::values() {
+        let controller = match room.controller() {
+            Some(controller) => controller,
+            None => continue,
+        };
+
+        if controller.my() == false {
+            continue;
+        }
+
+        let room_key = room.name().to_string();
+        let current = *state_map.get(&room_key).unwrap_or(&EconomyState::BOOTSTRAP);
+        let stats = get_room_economy_stats(&room);
+        let next = next_economy_state(current, &stats);
+
+        if next != current {
+            warn!(
+                "economy state changed: {:?}, {:?} -> {:?}, {:?}",
+                room.name(),
+                current,
+                next,
+                stats
+            );
+        } else {
+            debug!("economy state: {:?}, {:?}", room.name(), current);
+        }
+
+        state_map.insert(room_key, next);
+    }
+
+    save_economy_state(&state_map);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn make_test_stats(energy_capacity: u32) -> RoomEconomyStats {
+        return RoomEconomyStats {
+            num_creeps: 0,
+            num_haulers: 0,
+            hauler_carry: 0,
+            energy_available: energy_capacity,
+            energy_capacity: energy_capacity,
+        };
+    }
+
+    // spawnしたharvesterを数に足す.
+    fn add_harvester(stats: &mut RoomEconomyStats, body: &Vec<Part>) {
+        let carry_parts = body.iter().filter(|p| **p == Part::Carry).count() as u32;
+
+        stats.num_creeps += 1;
+        stats.num_haulers += 1;
+        stats.hauler_carry += carry_parts * screeps::constants::CARRY_CAPACITY;
+    }
+
+    #[test]
+    fn bootstrap_body() {
+        assert_eq!(make_bootstrap_body(199, EconomyState::BOOTSTRAP).len(), 0);
+        assert_eq!(
+            make_bootstrap_body(200, EconomyState::BOOTSTRAP),
+            vec![Part::Work, Part::Carry, Part::Move]
+        );
+
+        // BOOTSTRAP中はenergyがあっても最小構成.
+        assert_eq!(make_bootstrap_body(1000, EconomyState::BOOTSTRAP).len(), 3);
+        assert_eq!(
+            make_bootstrap_body(1000, EconomyState::RECOVERY).len(),
+            3 * ECONOMY_RECOVERY_MAX_BODY_SET as usize
+        );
+    }
+
+    #[test]
+    fn wipe_goes_to_bootstrap() {
+        let stats = make_test_stats(800);
+
+        for current in [
+            EconomyState::NORMAL,
+            EconomyState::RECOVERY,
+            EconomyState::BOOTSTRAP,
+        ]
+        .iter()
+        {
+            assert_eq!(
+                next_economy_state(*current, &stats),
+                EconomyState::BOOTSTRAP
+            );
+        }
+    }
+
+    #[test]
+    fn no_hauler_goes_to_recovery() {
+        let mut stats = make_test_stats(800);
+        stats.num_creeps = 3;
+
+        assert_eq!(
+            next_economy_state(EconomyState::NORMAL, &stats),
+            EconomyState::RECOVERY
+        );
+    }
+
+    #[test]
+    fn wipe_bootstrap_recovered() {
+        let mut stats = make_test_stats(550);
+        let mut state = next_economy_state(EconomyState::NORMAL, &stats);
+        let mut history = vec![state];
+        let mut last_body_len = 0;
+
+        // 1tickに1体spawnする. 手持ちは300だけ, 溜まったら容量まで.
+        for tick in 0..20 {
+            if state == EconomyState::NORMAL {
+                break;
+            }
+
+            let energy_available = if tick == 0 {
+                300
+            } else {
+                stats.energy_capacity
+            };
+            let body = make_bootstrap_body(energy_available, state);
+            assert!(body.len() > 0);
+            assert!(body.len() >= last_body_len);
+            last_body_len = body.len();
+
+            add_harvester(&mut stats, &body);
+
+            let next = next_economy_state(state, &stats);
+            if next != state {
+                history.push(next);
+            }
+            state = next;
+        }
+
+        assert_eq!(
+            history,
+            vec![
+                EconomyState::BOOTSTRAP,
+                EconomyState::RECOVERY,
+                EconomyState::NORMAL
+            ]
+        );
+        assert!(stats.num_haulers >= ECONOMY_MIN_HAULERS);
+    }
+}
@@ -0,0 +1,387 @@
+use crate::constants::*;
+use crate::manage::intel::*;
+use crate::manage::remote::*;
+use log::*;
+use screeps::constants::find::*;
+use screeps::{game, prelude::*, Position, Room, RoomName, StructureType, Terrain};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ExpansionCandidate {
+    pub num_sources: u32,
+    pub mineral: Option<String>,
+    pub num_exits: u32,
+    pub num_hostile_neighbours: u32,
+    pub failed_until: u32,
+    pub updated: u32,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Expansion {
+    pub room: String,
+    pub home: String,
+    pub started: u32,
+}
+
+type ExpansionCandidateMap = HashMap<String, ExpansionCandidate>;
+
+fn load_expansion_candidates() -> ExpansionCandidateMap {
+    let json_str = screeps::memory::root()
+        .string("expansion_candidates")
+        .unwrap_or(None)
+        .unwrap_or(String::from("{}"));
+
+    return serde_json::from_str(json_str.as_str()).unwrap_or(HashMap::new());
+}
+
+fn save_expansion_candidates(candidate_map: &ExpansionCandidateMap) {
+    let json_str = serde_json::to_string(candidate_map).unwrap();
+    screeps::memory::root().set("expansion_candidates", json_str);
+}
+
+pub fn load_expansion() -> Option<Expansion> {
+    let json_str = screeps::memory::root()
+        .string("expansion")
+        .unwrap_or(None)?;
+
+    return serde_json::from_str(json_str.as_str()).ok();
+}
+
+fn save_expansion(expansion: &Option<Expansion>) {
+    match expansion {
+        Some(expansion) => {
+            let json_str = serde_json::to_string(expansion).unwrap();
+            screeps::memory::root().set("expansion", json_str);
+        }
+        None => {
+            screeps::memory::root().del("expansion");
+        }
+    }
+}
+
+fn get_owned_rooms() -> Vec<Room> {
+    let mut owned_rooms = Vec::new();
+
+    for room in game::rooms::values() {
+        if let Some(controller) = room.controller() {
+            if controller.my() == true {
+                owned_rooms.push(room);
+            }
+        }
+    }
+
+    return owned_rooms;
+}
+
+fn get_owned_minerals() -> Vec<String> {
+    let mut minerals = Vec::new();
+
+    for room in get_owned_rooms() {
+        for mineral in room.find(MINERALS) {
+            minerals.push(format!("{:?}", mineral.mineral_type()));
+        }
+    }
+
+    return minerals;
+}
+
+// 隣の部屋が他人の部屋なら数える.
+fn count_hostile_neighbours(room_intel: &RoomIntel, my_name: &Option<String>) -> u32 {
+    let mut count = 0;
+
+    for exit_room_name in room_intel.exits.iter() {
+        if let Some(exit_intel) = get_room_intel(exit_room_name.as_str()) {
+            if check_hostile_owned(&exit_intel, my_name) {
+                count += 1;
+            }
+        }
+    }
+
+    return count;
+}
+
+pub fn check_expansion_room(room_intel: &RoomIntel, my_name: &Option<String>) -> bool {
+    let controller = match &room_intel.controller {
+        Some(controller) => controller,
+        None => return false,
+    };
+
+    if controller.owner.is_some() || check_source_keeper_room(room_intel) {
+        return false;
+    }
+
+    // 自分のremoteは候補にしてよい.
+    if controller.reservation.is_some() && controller.reservation != *my_name {
+        return false;
+    }
+
+    return room_intel.sources.len() > 0;
+}
+
+pub fn make_expansion_candidate(
+    room_intel: &RoomIntel,
+    my_name: &Option<String>,
+) -> ExpansionCandidate {
+    return ExpansionCandidate {
+        num_sources: room_intel.sources.len() as u32,
+        mineral: room_intel
+            .mineral
+            .as_ref()
+            .map(|mineral| mineral.mineral_type.clone()),
+        num_exits: room_intel.exits.len() as u32,
+        num_hostile_neighbours: count_hostile_neighbours(room_intel, my_name),
+        failed_until: 0,
+        updated: room_intel.last_seen,
+    };
+}
+
+// intelのある部屋を候補として記録する.
+fn update_expansion_candidates(candidate_map: &mut ExpansionCandidateMap) {
+    let my_name = get_my_username();
+
+    for (room_key, room_intel) in get_all_room_intel() {
+        if check_expansion_room(&room_intel, &my_name) == false {
+            candidate_map.remove(&room_key);
+            continue;
+        }
+
+        let failed_until = candidate_map
+            .get(&room_key)
+            .map(|candidate| candidate.failed_until)
+            .unwrap_or(0);
+
+        let mut candidate = make_expansion_candidate(&room_intel, &my_name);
+        candidate.failed_until = failed_until;
+        candidate_map.insert(room_key, candidate);
+    }
+}
+
+fn find_nearest_home(room_name: RoomName) -> Option<(Room, u32)> {
+    let mut nearest: Option<(Room, u32)> = None;
+
+    for room in get_owned_rooms() {
+        let level = room.controller().map(|c| c.level()).unwrap_or(0);
+        if level < EXPANSION_MIN_HOME_RCL || room.find(MY_SPAWNS).len() <= 0 {
+            continue;
+        }
+
+        let distance = game::map::get_room_linear_distance(room.name(), room_name, false);
+
+        let is_nearer = match &nearest {
+            Some((_room, nearest_distance)) => distance < *nearest_distance,
+            None => true,
+        };
+
+        if is_nearer {
+            nearest = Some((room, distance));
+        }
+    }
+
+    return nearest;
+}
+
+pub fn calc_expansion_score(
+    candidate: &ExpansionCandidate,
+    distance: u32,
+    owned_minerals: &Vec<String>,
+) -> i32 {
+    let mut score = candidate.num_sources as i32 * EXPANSION_SCORE_SOURCE;
+
+    if let Some(mineral) = &candidate.mineral {
+        if owned_minerals.contains(mineral) == false {
+            score += EXPANSION_SCORE_NEW_MINERAL;
+        }
+    }
+
+    score -= distance as i32 * EXPANSION_SCORE_DISTANCE;
+    score -= candidate.num_exits as i32 * EXPANSION_SCORE_EXIT;
+    score -= candidate.num_hostile_neighbours as i32 * EXPANSION_SCORE_HOSTILE_NEIGHBOUR;
+
+    return score;
+}
+
+fn choose_expansion(candidate_map: &ExpansionCandidateMap) -> Option<Expansion> {
+    let owned_minerals = get_owned_minerals();
+    let mut best: Option<(i32, Expansion)> = None;
+
+    for (room_key, candidate) in candidate_map.iter() {
+        if candidate.failed_until > game::time() {
+            continue;
+        }
+
+        if candidate.num_sources < EXPANSION_MIN_SOURCES {
+            continue;
+        }
+
+        let room_name: RoomName = match room_key.parse() {
+            Ok(room_name) => room_name,
+            Err(_err) => continue,
+        };
+
+        let (home, distance) = match find_nearest_home(room_name) {
+            Some(nearest) => nearest,
+            None => continue,
+        };
+
+        if distance > EXPANSION_MAX_DISTANCE {
+            continue;
+        }
+
+        let score = calc_expansion_score(candidate, distance, &owned_minerals);
+        debug!("expansion candidate: {}, score:{}", room_key, score);
+
+        let is_better = match &best {
+            Some((best_score, _expansion)) => score > *best_score,
+            None => true,
+        };
+
+        if is_better {
+            best = Some((
+                score,
+                Expansion {
+                    room: room_key.clone(),
+                    home: home.name().to_string(),
+                    started: game::time(),
+                },
+            ));
+        }
+    }
+
+    return best.map(|(_score, expansion)| expansion);
+}
+
+// sourceとcontrollerの真ん中あたりで、周りが開けている所.
+fn find_spawn_pos(room: &Room) -> Option<Position> {
+    let mut points = Vec::new();
+    for source in room.find(SOURCES) {
+        points.push(source.pos());
+    }
+    if let Some(controller) = room.controller() {
+        points.push(controller.pos());
+    }
+
+    if points.len() <= 0 {
+        return None;
+    }
+
+    let center_x = points.iter().map(|p| p.x()).sum::<u32>() / points.len() as u32;
+    let center_y = points.iter().map(|p| p.y()).sum::<u32>() / points.len() as u32;
+    let terrain = room.get_terrain();
+
+    for range in 0..=EXPANSION_SPAWN_SEARCH_RANGE as i32 {
+        for dx in -range..=range {
+            for dy in -range..=range {
+                let x = center_x as i32 + dx;
+                let y = center_y as i32 + dy;
+
+                if x < 3 || y < 3 || x > 46 || y > 46 {
+                    continue;
+                }
+
+                let is_open = (-1..=1).all(|ox| {
+                    (-1..=1)
+                        .all(|oy| terrain.get((x + ox) as u32, (y + oy) as u32) != Terrain::Wall)
+                });
+
+                if is_open {
+                    return Some(Position::new(x as u32, y as u32, room.name()));
+                }
+            }
+        }
+    }
+
+    return None;
+}
+
+fn place_spawn_site(room: &Room) {
+    let has_spawn_site = room
+        .find(MY_CONSTRUCTION_SITES)
+        .iter()
+        .any(|site| site.structure_type() == StructureType::Spawn);
+
+    if has_spawn_site == true {
+        return;
+    }
+
+    match find_spawn_pos(room) {
+        Some(pos) => {
+            let r = room.create_construction_site(&pos, StructureType::Spawn);
+            info!("place spawn site: {:?}, {:?}", pos, r);
+        }
+        None => {
+            warn!("no spawn position in {:?}", room.name());
+        }
+    }
+}
+
+// 進行中のexpansionを進める. 終わった・失敗したらNone.
+fn update_expansion(
+    expansion: Expansion,
+    candidate_map: &mut ExpansionCandidateMap,
+) -> Option<Expansion> {
+    if expansion.started + EXPANSION_TIMEOUT_TICKS < game::time() {
+        warn!("expansion timed out: {}", expansion.room);
+
+        if let Some(candidate) = candidate_map.get_mut(&expansion.room) {
+            candidate.failed_until = game::time() + EXPANSION_RETRY_TICKS;
+        }
+        return None;
+    }
+
+    let room = match expansion
+        .room
+        .parse::<RoomName>()
+        .ok()
+        .and_then(|name| game::rooms::get(name))
+    {
+        Some(room) => room,
+        None => return Some(expansion),
+    };
+
+    let controller = match room.controller() {
+        Some(controller) => controller,
+        None => return None,
+    };
+
+    if controller.my() == false {
+        if controller.owner_name().is_some() {
+            warn!("expansion target was taken: {}", expansion.room);
+            return None;
+        }
+        return Some(expansion);
+    }
+
+    if room.find(MY_SPAWNS).len() > 0 {
+        info!("expansion completed: {}", expansion.room);
+        return None;
+    }
+
+    place_spawn_site(&room);
+    return Some(expansion);
+}
+
+pub fn run_expansion() {
+    let mut candidate_map = load_expansion_candidates();
+    update_expansion_candidates(&mut candidate_map);
+
+    let expansion = match load_expansion() {
+        Some(expansion) => update_expansion(expansion, &mut candidate_map),
+        None => {
+            let num_owned = get_owned_rooms().len() as u32;
+
+            if num_owned < game::gcl::level() {
+                let expansion = choose_expansion(&candidate_map);
+                if let Some(expansion) = &expansion {
+                    warn!("start expansion: {} -> {}", expansion.home, expansion.room);
+                }
+                expansion
+            } else {
+                None
+            }
+        }
+    };
+
+    save_expansion(&expansion);
+    save_expansion_candidates(&candidate_map);
+}
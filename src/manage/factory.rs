@@ -0,0 +1,493 @@
+use crate::constants::*;
+use crate::manage::lab::*;
+use crate::manage::market::*;
+use log::*;
+use screeps::constants::find::*;
+use screeps::{
+    game, objects::StructureFactory, prelude::*, ResourceType, ReturnCode, Room, RoomName,
+    Structure,
+};
+use std::collections::HashMap;
+use std::convert::TryFrom;
+use stdweb::js;
+
+use lazy_static::lazy_static;
+use std::sync::RwLock;
+
+#[derive(Clone, Debug)]
+pub struct FactoryRecipe {
+    pub product: ResourceType,
+    pub amount: u32,
+    // Noneならどのfactoryでも作れる. Someならそのlevelのfactoryだけ.
+    pub level: Option<u32>,
+    pub components: Vec<(ResourceType, u32)>,
+}
+
+#[derive(Clone, Debug)]
+pub struct FactoryJob {
+    pub product: ResourceType,
+    pub started: u32,
+}
+
+lazy_static! {
+    static ref FACTORY_JOB_CACHE: RwLock<HashMap<RoomName, FactoryJob>> =
+        RwLock::new(HashMap::new());
+}
+
+fn make_recipe(
+    product: ResourceType,
+    amount: u32,
+    level: Option<u32>,
+    components: &[(ResourceType, u32)],
+) -> FactoryRecipe {
+    return FactoryRecipe {
+        product: product,
+        amount: amount,
+        level: level,
+        components: components.to_vec(),
+    };
+}
+
+// 圧縮とlevel 3までの商品.
+pub fn get_factory_recipes() -> Vec<FactoryRecipe> {
+    use ResourceType::*;
+
+    return vec![
+        make_recipe(Battery, 50, None, &[(Energy, 600)]),
+        make_recipe(UtriumBar, 100, None, &[(Utrium, 500), (Energy, 200)]),
+        make_recipe(LemergiumBar, 100, None, &[(Lemergium, 500), (Energy, 200)]),
+        make_recipe(ZynthiumBar, 100, None, &[(Zynthium, 500), (Energy, 200)]),
+        make_recipe(KeaniumBar, 100, None, &[(Keanium, 500), (Energy, 200)]),
+        make_recipe(GhodiumMelt, 100, None, &[(Ghodium, 500), (Energy, 200)]),
+        make_recipe(Oxidant, 100, None, &[(Oxygen, 500), (Energy, 200)]),
+        make_recipe(Reductant, 100, None, &[(Hydrogen, 500), (Energy, 200)]),
+        make_recipe(Purifier, 100, None, &[(Catalyst, 500), (Energy, 200)]),
+        make_recipe(
+            Wire,
+            20,
+            None,
+            &[(UtriumBar, 20), (Silicon, 100), (Energy, 40)],
+        ),
+        make_recipe(
+            Cell,
+            20,
+            None,
+            &[(LemergiumBar, 20), (Biomass, 100), (Energy, 40)],
+        ),
+        make_recipe(
+            Alloy,
+            20,
+            None,
+            &[(ZynthiumBar, 20), (Metal, 100), (Energy, 40)],
+        ),
+        make_recipe(
+            Condensate,
+            20,
+            None,
+            &[(KeaniumBar, 20), (Mist, 100), (Energy, 40)],
+        ),
+        make_recipe(
+            Composite,
+            20,
+            Some(1),
+            &[(UtriumBar, 20), (ZynthiumBar, 20), (Energy, 20)],
+        ),
+        make_recipe(
+            Crystal,
+            6,
+            Some(2),
+            &[
+                (LemergiumBar, 6),
+                (KeaniumBar, 6),
+                (Purifier, 6),
+                (Energy, 45),
+            ],
+        ),
+        make_recipe(
+            Liquid,
+            12,
+            Some(3),
+            &[
+                (Oxidant, 12),
+                (Reductant, 12),
+                (GhodiumMelt, 12),
+                (Energy, 90),
+            ],
+        ),
+        make_recipe(
+            Switch,
+            5,
+            Some(1),
+            &[(Wire, 40), (Oxidant, 95), (UtriumBar, 35), (Energy, 20)],
+        ),
+        make_recipe(
+            Phlegm,
+            2,
+            Some(1),
+            &[(Cell, 20), (Oxidant, 36), (LemergiumBar, 16), (Energy, 8)],
+        ),
+        make_recipe(
+            Tube,
+            2,
+            Some(1),
+            &[(Alloy, 40), (ZynthiumBar, 16), (Energy, 8)],
+        ),
+        make_recipe(
+            Concentrate,
+            3,
+            Some(1),
+            &[
+                (Condensate, 30),
+                (KeaniumBar, 15),
+                (Reductant, 54),
+                (Energy, 12),
+            ],
+        ),
+    ];
+}
+
+pub fn get_factory_recipe(product: ResourceType) -> Option<FactoryRecipe> {
+    return get_factory_recipes()
+        .into_iter()
+        .find(|recipe| recipe.product == product);
+}
+
+// 常に置いておきたい圧縮済みの量.
+fn get_bar_targets() -> Vec<(ResourceType, u32)> {
+    return vec![
+        (ResourceType::Battery, FACTORY_BATTERY_TARGET_AMOUNT),
+        (ResourceType::UtriumBar, FACTORY_BAR_TARGET_AMOUNT),
+        (ResourceType::LemergiumBar, FACTORY_BAR_TARGET_AMOUNT),
+        (ResourceType::ZynthiumBar, FACTORY_BAR_TARGET_AMOUNT),
+        (ResourceType::KeaniumBar, FACTORY_BAR_TARGET_AMOUNT),
+        (ResourceType::GhodiumMelt, FACTORY_BAR_TARGET_AMOUNT),
+        (ResourceType::Oxidant, FACTORY_BAR_TARGET_AMOUNT),
+        (ResourceType::Reductant, FACTORY_BAR_TARGET_AMOUNT),
+        (ResourceType::Purifier, FACTORY_BAR_TARGET_AMOUNT),
+    ];
+}
+
+fn check_bar(resource: ResourceType) -> bool {
+    return get_bar_targets()
+        .iter()
+        .any(|(bar, _target)| *bar == resource);
+}
+
+// energyと基本鉱物はlabやspawnの分を残す.
+fn get_reserve_amount(resource: ResourceType) -> u32 {
+    use ResourceType::*;
+
+    match resource {
+        Energy => FACTORY_ENERGY_RESERVE_AMOUNT,
+        Utrium | Lemergium | Zynthium | Keanium | Ghodium | Oxygen | Hydrogen | Catalyst => {
+            FACTORY_MINERAL_RESERVE_AMOUNT
+        }
+        _ => 0,
+    }
+}
+
+pub fn get_room_factory(room: &Room) -> Option<StructureFactory> {
+    return room
+        .find(MY_STRUCTURES)
+        .into_iter()
+        .filter_map(|structure| match structure {
+            Structure::Factory(factory) => Some(factory),
+            _ => None,
+        })
+        .next();
+}
+
+// levelのないfactoryはundefinedなので0にする.
+pub fn get_factory_level(factory: &StructureFactory) -> u32 {
+    let level = js! {
+        return @{factory.as_ref()}.level || 0;
+    };
+    return u32::try_from(level).unwrap_or(0);
+}
+
+// levelのある商品はpower creepのOPERATE_FACTORYが効いている間しか作れない.
+pub fn check_factory_operated(factory: &StructureFactory) -> bool {
+    let is_operated = js! {
+        var effects = @{factory.as_ref()}.effects || [];
+        return effects.some(function(e) {
+            return e.effect == PWR_OPERATE_FACTORY && e.ticksRemaining > 0;
+        });
+    };
+    return bool::try_from(is_operated).unwrap_or(false);
+}
+
+pub fn get_factory_job(room_name: &RoomName) -> Option<FactoryJob> {
+    let jobs = FACTORY_JOB_CACHE.read().unwrap();
+    return jobs.get(room_name).cloned();
+}
+
+fn get_available_amount(room: &Room, factory: &StructureFactory, resource: ResourceType) -> u32 {
+    let amount = get_room_stock(room, resource) + factory.store_of(resource);
+    return amount.saturating_sub(get_reserve_amount(resource));
+}
+
+fn check_recipe_available(room: &Room, factory: &StructureFactory, recipe: &FactoryRecipe) -> bool {
+    match recipe.level {
+        Some(level) => {
+            if get_factory_level(factory) != level || check_factory_operated(factory) == false {
+                return false;
+            }
+        }
+        None => {}
+    }
+
+    return recipe
+        .components
+        .iter()
+        .all(|(component, amount)| get_available_amount(room, factory, *component) >= *amount);
+}
+
+// 1回分の売値と材料費の差. 相場が分からなければNone.
+fn calc_recipe_profit(recipe: &FactoryRecipe) -> Option<f64> {
+    let mut profit = get_average_price(recipe.product)? * recipe.amount as f64;
+
+    for (component, amount) in recipe.components.iter() {
+        profit -= get_average_price(*component)? * *amount as f64;
+    }
+
+    return Some(profit);
+}
+
+// 圧縮の不足分を先に. なければ一番儲かる商品.
+fn plan_factory_job(room: &Room, factory: &StructureFactory) -> Option<FactoryJob> {
+    for (bar, target) in get_bar_targets() {
+        if get_room_stock(room, bar) + factory.store_of(bar) >= target {
+            continue;
+        }
+
+        let recipe = match get_factory_recipe(bar) {
+            Some(recipe) => recipe,
+            None => continue,
+        };
+
+        if check_recipe_available(room, factory, &recipe) == true {
+            return Some(FactoryJob {
+                product: bar,
+                started: game::time(),
+            });
+        }
+    }
+
+    let mut best: Option<(ResourceType, f64)> = None;
+
+    for recipe in get_factory_recipes() {
+        if check_bar(recipe.product) == true {
+            continue;
+        }
+
+        if check_recipe_available(room, factory, &recipe) == false {
+            continue;
+        }
+
+        let profit = match calc_recipe_profit(&recipe) {
+            Some(profit) => profit,
+            None => continue,
+        };
+
+        debug!(
+            "factory profit {}: {:?}, {}",
+            room.name(),
+            recipe.product,
+            profit
+        );
+
+        let is_better = match best {
+            Some((_product, best_profit)) => profit > best_profit,
+            None => profit >= FACTORY_MIN_PROFIT,
+        };
+        if is_better == true {
+            best = Some((recipe.product, profit));
+        }
+    }
+
+    let (product, _profit) = best?;
+
+    return Some(FactoryJob {
+        product: product,
+        started: game::time(),
+    });
+}
+
+// 目標量に届いたか、材料がなくなれば終わり.
+fn check_job_finished(room: &Room, factory: &StructureFactory, job: &FactoryJob) -> bool {
+    if game::time() - job.started > FACTORY_JOB_TIMEOUT {
+        return true;
+    }
+
+    for (bar, target) in get_bar_targets() {
+        if bar == job.product && get_room_stock(room, bar) + factory.store_of(bar) >= target {
+            return true;
+        }
+    }
+
+    let recipe = match get_factory_recipe(job.product) {
+        Some(recipe) => recipe,
+        None => return true,
+    };
+
+    return check_recipe_available(room, factory, &recipe) == false;
+}
+
+// factoryで使う材料と、factoryに置いておく量.
+fn get_factory_inputs(job: &FactoryJob) -> Vec<(ResourceType, u32)> {
+    return match get_factory_recipe(job.product) {
+        Some(recipe) => recipe
+            .components
+            .iter()
+            .map(|(component, amount)| (*component, amount * FACTORY_INPUT_BATCHES))
+            .collect(),
+        None => Vec::new(),
+    };
+}
+
+// lab haulerの仕事. 要らない物の回収 -> 材料の補充の順.
+pub fn get_factory_haul_task(room: &Room) -> Option<LabHaulTask> {
+    let factory = get_room_factory(room)?;
+    let job = get_factory_job(&room.name());
+
+    let inputs = match job.as_ref() {
+        Some(job) => get_factory_inputs(job),
+        None => Vec::new(),
+    };
+
+    for resource in factory.store_types() {
+        if inputs
+            .iter()
+            .any(|(component, _amount)| *component == resource)
+        {
+            continue;
+        }
+
+        // 仕事中は少し溜まってから回収する.
+        let amount = factory.store_of(resource);
+        if job.is_some() && amount < FACTORY_OUTPUT_COLLECT_AMOUNT {
+            continue;
+        }
+
+        return Some(LabHaulTask {
+            from: Structure::Factory(factory.clone()),
+            resource: resource,
+            amount: amount,
+        });
+    }
+
+    for (component, target) in inputs {
+        let amount = factory.store_of(component);
+        if amount >= target {
+            continue;
+        }
+
+        if get_room_stock(room, component) <= get_reserve_amount(component) {
+            continue;
+        }
+
+        if let Some(task) = make_fill_task(room, component, target - amount) {
+            return Some(task);
+        }
+    }
+
+    return None;
+}
+
+// 材料ならfactoryへ、売り物はterminalへ. それ以外はNone.
+pub fn get_factory_deliver_target(room: &Room, resource: ResourceType) -> Option<Structure> {
+    let factory = get_room_factory(room)?;
+
+    if let Some(job) = get_factory_job(&room.name()) {
+        for (component, target) in get_factory_inputs(&job) {
+            if component == resource
+                && factory.store_of(component) < target
+                && factory.store_free_capacity(Some(resource)) > 0
+            {
+                return Some(Structure::Factory(factory));
+            }
+        }
+    }
+
+    if get_factory_recipe(resource).is_some() && check_bar(resource) == false {
+        if let Some(terminal) = room.terminal() {
+            if terminal.store_free_capacity(None) > 0 {
+                return Some(Structure::Terminal(terminal));
+            }
+        }
+    }
+
+    return None;
+}
+
+fn run_room_factory(room: &Room) {
+    let factory = match get_room_factory(room) {
+        Some(factory) => factory,
+        None => return,
+    };
+
+    let mut job = get_factory_job(&room.name());
+
+    let is_finished = match job.as_ref() {
+        Some(job) => check_job_finished(room, &factory, job),
+        None => true,
+    };
+
+    if is_finished == true && game::time() % FACTORY_PLAN_INTERVAL == 0 {
+        job = plan_factory_job(room, &factory);
+
+        let mut jobs = FACTORY_JOB_CACHE.write().unwrap();
+        match job.as_ref() {
+            Some(job) => {
+                info!("factory job planned {}: {:?}", room.name(), job.product);
+                jobs.insert(room.name(), job.clone());
+            }
+            None => {
+                jobs.remove(&room.name());
+            }
+        }
+    }
+
+    let job = match job {
+        Some(job) => job,
+        None => return,
+    };
+
+    if factory.cooldown() > 0 {
+        return;
+    }
+
+    let recipe = match get_factory_recipe(job.product) {
+        Some(recipe) => recipe,
+        None => return,
+    };
+
+    // 材料がfactoryに揃うまで待つ.
+    let is_ready = recipe
+        .components
+        .iter()
+        .all(|(component, amount)| factory.store_of(*component) >= *amount);
+    if is_ready == false {
+        return;
+    }
+
+    let r = factory.produce(job.product);
+    if r != ReturnCode::Ok {
+        debug!("couldn't produce {:?}: {:?}", job.product, r);
+    }
+}
+
+pub fn run_factory() {
+    for room in game::rooms::values() {
+        let controller = match room.controller() {
+            Some(controller) => controller,
+            None => continue,
+        };
+
+        if controller.my() == false {
+            continue;
+        }
+
+        run_room_factory(&room);
+    }
+}
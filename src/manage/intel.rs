@@ -0,0 +1,420 @@
+use crate::constants::*;
+use crate::route::*;
+use log::*;
+use screeps::constants::find::*;
+use screeps::{
+    game, prelude::*, raw_memory, Position, ReturnCode, Room, RoomName, Structure, StructureType,
+    Terrain,
+};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+use lazy_static::lazy_static;
+use std::sync::RwLock;
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct IntelObject {
+    pub id: String,
+    pub x: u32,
+    pub y: u32,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct IntelStructure {
+    pub structure_type: String,
+    pub x: u32,
+    pub y: u32,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct IntelMineral {
+    pub id: String,
+    pub x: u32,
+    pub y: u32,
+    pub mineral_type: String,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct IntelController {
+    pub x: u32,
+    pub y: u32,
+    pub owner: Option<String>,
+    pub level: u32,
+    pub reservation: Option<String>,
+    pub reservation_ticks: u32,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct IntelPowerBank {
+    pub id: String,
+    pub x: u32,
+    pub y: u32,
+    pub power: u32,
+    pub hits: u32,
+    pub decay_at: u32,
+    // 周りの歩けるマス. 同時に殴れる数になる.
+    pub free_tiles: u32,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct IntelDeposit {
+    pub id: String,
+    pub x: u32,
+    pub y: u32,
+    pub deposit_type: String,
+    pub last_cooldown: u32,
+    pub decay_at: u32,
+    pub free_tiles: u32,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct RoomIntel {
+    pub sources: Vec<IntelObject>,
+    pub mineral: Option<IntelMineral>,
+    pub controller: Option<IntelController>,
+    // 他人のtower/spawnなど.
+    pub hostile_structures: Vec<IntelStructure>,
+    pub num_keeper_lairs: u32,
+    pub exits: Vec<String>,
+    pub last_seen: u32,
+    #[serde(default)]
+    pub power_bank: Option<IntelPowerBank>,
+    #[serde(default)]
+    pub deposits: Vec<IntelDeposit>,
+}
+
+#[derive(Default)]
+struct IntelData {
+    rooms: HashMap<String, RoomIntel>,
+    is_loaded: bool,
+    // segmentを最初に要求したtick.
+    requested: Option<u32>,
+}
+
+lazy_static! {
+    static ref INTEL_CACHE: RwLock<IntelData> = RwLock::new(IntelData::default());
+}
+
+pub fn get_room_intel(room_name: &str) -> Option<RoomIntel> {
+    let intel = INTEL_CACHE.read().unwrap();
+    return intel.rooms.get(room_name).cloned();
+}
+
+pub fn get_all_room_intel() -> HashMap<String, RoomIntel> {
+    let intel = INTEL_CACHE.read().unwrap();
+    return intel.rooms.clone();
+}
+
+// 他人に取られている部屋.
+pub fn check_hostile_owned(room_intel: &RoomIntel, my_name: &Option<String>) -> bool {
+    match &room_intel.controller {
+        Some(controller) => {
+            return controller.owner.is_some() && controller.owner != *my_name;
+        }
+        None => {}
+    }
+
+    return false;
+}
+
+pub fn check_source_keeper_room(room_intel: &RoomIntel) -> bool {
+    return room_intel.num_keeper_lairs > 0;
+}
+
+fn count_free_tiles(room: &Room, pos: &Position) -> u32 {
+    let terrain = room.get_terrain();
+    let mut count = 0;
+
+    for dx in -1..=1 {
+        for dy in -1..=1 {
+            if dx == 0 && dy == 0 {
+                continue;
+            }
+
+            let x = pos.x() as i32 + dx;
+            let y = pos.y() as i32 + dy;
+            if x <= 0 || y <= 0 || x >= 49 || y >= 49 {
+                continue;
+            }
+
+            if terrain.get(x as u32, y as u32) != Terrain::Wall {
+                count += 1;
+            }
+        }
+    }
+
+    return count;
+}
+
+pub fn make_room_intel(room: &Room) -> RoomIntel {
+    let mut sources = Vec::new();
+    for source in room.find(SOURCES) {
+        sources.push(IntelObject {
+            id: source.id().to_string(),
+            x: source.pos().x(),
+            y: source.pos().y(),
+        });
+    }
+
+    let mineral = room.find(MINERALS).first().map(|mineral| IntelMineral {
+        id: mineral.id().to_string(),
+        x: mineral.pos().x(),
+        y: mineral.pos().y(),
+        mineral_type: format!("{:?}", mineral.mineral_type()),
+    });
+
+    let controller = room.controller().map(|controller| {
+        let reservation = controller.reservation();
+
+        IntelController {
+            x: controller.pos().x(),
+            y: controller.pos().y(),
+            owner: controller.owner_name(),
+            level: controller.level(),
+            reservation: reservation.as_ref().map(|r| r.username.clone()),
+            reservation_ticks: reservation.as_ref().map(|r| r.ticks_to_end).unwrap_or(0),
+        }
+    });
+
+    let mut deposits = Vec::new();
+    for deposit in room.find(DEPOSITS) {
+        deposits.push(IntelDeposit {
+            id: deposit.id().to_string(),
+            x: deposit.pos().x(),
+            y: deposit.pos().y(),
+            deposit_type: format!("{:?}", deposit.deposit_type()),
+            last_cooldown: deposit.last_cooldown(),
+            decay_at: game::time() + deposit.ticks_to_decay(),
+            free_tiles: count_free_tiles(room, &deposit.pos()),
+        });
+    }
+
+    let mut hostile_structures = Vec::new();
+    let mut num_keeper_lairs = 0;
+    let mut power_bank = None;
+
+    for structure in room.find(STRUCTURES) {
+        if structure.structure_type() == StructureType::KeeperLair {
+            num_keeper_lairs += 1;
+            continue;
+        }
+
+        if let Structure::PowerBank(bank) = &structure {
+            power_bank = Some(IntelPowerBank {
+                id: bank.id().to_string(),
+                x: bank.pos().x(),
+                y: bank.pos().y(),
+                power: bank.power(),
+                hits: bank.hits(),
+                decay_at: game::time() + bank.ticks_to_decay(),
+                free_tiles: count_free_tiles(room, &bank.pos()),
+            });
+            continue;
+        }
+
+        if let Some(owned) = structure.as_owned() {
+            if owned.my() == false && owned.owner_name().is_some() {
+                hostile_structures.push(IntelStructure {
+                    structure_type: format!("{:?}", structure.structure_type()),
+                    x: structure.pos().x(),
+                    y: structure.pos().y(),
+                });
+            }
+        }
+    }
+
+    let exits = game::map::describe_exits(room.name())
+        .values()
+        .map(|room_name| room_name.to_string())
+        .collect();
+
+    return RoomIntel {
+        sources: sources,
+        mineral: mineral,
+        controller: controller,
+        hostile_structures: hostile_structures,
+        num_keeper_lairs: num_keeper_lairs,
+        exits: exits,
+        last_seen: game::time(),
+        power_bank: power_bank,
+        deposits: deposits,
+    };
+}
+
+// segmentから読み込む. segmentは要求した次のtickから読める.
+fn load_intel_segment(intel: &mut IntelData) {
+    let segment = match raw_memory::get_segment(INTEL_SEGMENT_ID) {
+        Some(segment) => segment,
+        None => {
+            // 要求した次のtickからは有効. それでも読めなければまだ一度も書いていないので空で始める.
+            let is_active = intel
+                .requested
+                .map(|requested| requested < game::time())
+                .unwrap_or(false);
+
+            if is_active == false {
+                debug!("intel segment is not active yet");
+                return;
+            }
+            String::new()
+        }
+    };
+
+    let saved_rooms: HashMap<String, RoomIntel> =
+        serde_json::from_str(segment.as_str()).unwrap_or(HashMap::new());
+
+    info!("intel loaded: {} rooms", saved_rooms.len());
+
+    for (room_name, room_intel) in saved_rooms {
+        intel.rooms.entry(room_name).or_insert(room_intel);
+    }
+    intel.is_loaded = true;
+}
+
+// 入りきらなければ古いものから捨てる.
+fn save_intel_segment(intel: &mut IntelData) {
+    loop {
+        let json_str = serde_json::to_string(&intel.rooms).unwrap();
+
+        if json_str.len() <= INTEL_SEGMENT_MAX_SIZE {
+            raw_memory::set_segment(INTEL_SEGMENT_ID, json_str.as_str());
+            return;
+        }
+
+        let oldest = intel
+            .rooms
+            .iter()
+            .min_by_key(|(_name, room_intel)| room_intel.last_seen)
+            .map(|(name, _room_intel)| name.clone());
+
+        match oldest {
+            Some(oldest) => {
+                warn!("intel segment is full, drop {}", oldest);
+                intel.rooms.remove(&oldest);
+            }
+            None => return,
+        }
+    }
+}
+
+// "W10N5" -> (-11, -6). 東と南が正.
+fn get_room_coords(room_name: &str) -> Option<(i32, i32)> {
+    let mut chars = room_name.chars();
+    let horizontal = chars.next()?;
+    let rest: String = chars.collect();
+    let index = rest.find(|c: char| c.is_ascii_alphabetic())?;
+    let vertical = rest[index..].chars().next()?;
+
+    let x: i32 = rest[..index].parse().ok()?;
+    let y: i32 = rest[index + 1..].parse().ok()?;
+
+    let x = if horizontal == 'W' { -x - 1 } else { x };
+    let y = if vertical == 'N' { -y - 1 } else { y };
+
+    return Some((x, y));
+}
+
+fn make_room_name(x: i32, y: i32) -> String {
+    let horizontal = if x < 0 {
+        format!("W{}", -x - 1)
+    } else {
+        format!("E{}", x)
+    };
+    let vertical = if y < 0 {
+        format!("N{}", -y - 1)
+    } else {
+        format!("S{}", y)
+    };
+
+    return horizontal + vertical.as_str();
+}
+
+// observerの届く範囲のhighway.
+fn get_observe_targets(room_name: &str) -> Vec<RoomName> {
+    let mut targets = Vec::new();
+
+    let (x, y) = match get_room_coords(room_name) {
+        Some(coords) => coords,
+        None => return targets,
+    };
+
+    let range = INTEL_OBSERVE_RANGE as i32;
+    for dx in -range..=range {
+        for dy in -range..=range {
+            let target_name = make_room_name(x + dx, y + dy);
+            if check_highway_room(target_name.as_str()) == false {
+                continue;
+            }
+
+            if let Ok(target) = target_name.parse() {
+                targets.push(target);
+            }
+        }
+    }
+
+    return targets;
+}
+
+// observerごとに1部屋ずつ順番に見る. 見えた部屋は次のtickにintelに入る.
+pub fn run_observer() {
+    for room in game::rooms::values() {
+        let observer = room
+            .find(MY_STRUCTURES)
+            .into_iter()
+            .filter_map(|structure| match structure {
+                Structure::Observer(observer) => Some(observer),
+                _ => None,
+            })
+            .next();
+
+        let observer = match observer {
+            Some(observer) => observer,
+            None => continue,
+        };
+
+        let targets = get_observe_targets(room.name().to_string().as_str());
+        if targets.len() <= 0 {
+            continue;
+        }
+
+        let target = targets[(game::time() as usize) % targets.len()];
+        let r = observer.observe_room(target);
+        if r != ReturnCode::Ok {
+            debug!("couldn't observe {}: {:?}", target, r);
+        }
+    }
+}
+
+pub fn run_intel() {
+    raw_memory::set_active_segments(&[INTEL_SEGMENT_ID]);
+
+    let mut intel = INTEL_CACHE.write().unwrap();
+
+    if intel.requested.is_none() {
+        intel.requested = Some(game::time());
+    }
+
+    if intel.is_loaded == false {
+        load_intel_segment(&mut intel);
+    }
+
+    // 部屋ごとに間隔をあけて更新する.
+    for room in game::rooms::values() {
+        let room_key = room.name().to_string();
+
+        let is_fresh = intel
+            .rooms
+            .get(&room_key)
+            .map(|room_intel| room_intel.last_seen + INTEL_UPDATE_INTERVAL > game::time())
+            .unwrap_or(false);
+        if is_fresh == true {
+            continue;
+        }
+
+        intel.rooms.insert(room_key, make_room_intel(&room));
+    }
+
+    if intel.is_loaded == true && game::time() % INTEL_SAVE_INTERVAL == 0 {
+        save_intel_segment(&mut intel);
+    }
+}
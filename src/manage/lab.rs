@@ -0,0 +1,528 @@
+use crate::constants::*;
+use crate::manage::boost::*;
+use log::*;
+use screeps::constants::find::*;
+use screeps::{
+    game, objects::StructureLab, prelude::*, ResourceType, ReturnCode, Room, RoomName, Structure,
+};
+use std::collections::HashMap;
+
+use lazy_static::lazy_static;
+use std::sync::RwLock;
+
+#[derive(Clone, Debug)]
+pub struct LabReaction {
+    pub product: ResourceType,
+    pub reagents: (ResourceType, ResourceType),
+    pub amount: u32,
+    // 始めた時のproductの在庫. amountだけ増えたら終わり.
+    pub start_stock: u32,
+    pub started: u32,
+}
+
+pub struct LabSetup {
+    pub inputs: (StructureLab, StructureLab),
+    pub outputs: Vec<StructureLab>,
+}
+
+// 拾う/運ぶ対象.
+#[derive(Clone, Debug)]
+pub struct LabHaulTask {
+    pub from: Structure,
+    pub resource: ResourceType,
+    pub amount: u32,
+}
+
+lazy_static! {
+    static ref LAB_REACTION_CACHE: RwLock<HashMap<RoomName, LabReaction>> =
+        RwLock::new(HashMap::new());
+}
+
+// 作りたい化合物と在庫の目標. 上から順に作る.
+fn get_stock_targets() -> Vec<(ResourceType, u32)> {
+    return vec![
+        (
+            ResourceType::CatalyzedGhodiumAlkalide,
+            LAB_STOCK_TARGET_AMOUNT,
+        ),
+        (ResourceType::CatalyzedUtriumAcid, LAB_STOCK_TARGET_AMOUNT),
+        (
+            ResourceType::CatalyzedKeaniumAlkalide,
+            LAB_STOCK_TARGET_AMOUNT,
+        ),
+        (
+            ResourceType::CatalyzedLemergiumAlkalide,
+            LAB_STOCK_TARGET_AMOUNT,
+        ),
+        (ResourceType::CatalyzedZynthiumAcid, LAB_STOCK_TARGET_AMOUNT),
+        (ResourceType::CatalyzedGhodiumAcid, LAB_STOCK_TARGET_AMOUNT),
+    ];
+}
+
+// 反応式. 元素(とG以外の基本鉱物)はNone.
+pub fn get_reaction_reagents(product: ResourceType) -> Option<(ResourceType, ResourceType)> {
+    use ResourceType::*;
+
+    let reagents = match product {
+        Hydroxide => (Hydrogen, Oxygen),
+        ZynthiumKeanite => (Zynthium, Keanium),
+        UtriumLemergite => (Utrium, Lemergium),
+        Ghodium => (ZynthiumKeanite, UtriumLemergite),
+
+        UtriumHydride => (Utrium, Hydrogen),
+        UtriumOxide => (Utrium, Oxygen),
+        KeaniumHydride => (Keanium, Hydrogen),
+        KeaniumOxide => (Keanium, Oxygen),
+        LemergiumHydride => (Lemergium, Hydrogen),
+        LemergiumOxide => (Lemergium, Oxygen),
+        ZynthiumHydride => (Zynthium, Hydrogen),
+        ZynthiumOxide => (Zynthium, Oxygen),
+        GhodiumHydride => (Ghodium, Hydrogen),
+        GhodiumOxide => (Ghodium, Oxygen),
+
+        UtriumAcid => (UtriumHydride, Hydroxide),
+        UtriumAlkalide => (UtriumOxide, Hydroxide),
+        KeaniumAcid => (KeaniumHydride, Hydroxide),
+        KeaniumAlkalide => (KeaniumOxide, Hydroxide),
+        LemergiumAcid => (LemergiumHydride, Hydroxide),
+        LemergiumAlkalide => (LemergiumOxide, Hydroxide),
+        ZynthiumAcid => (ZynthiumHydride, Hydroxide),
+        ZynthiumAlkalide => (ZynthiumOxide, Hydroxide),
+        GhodiumAcid => (GhodiumHydride, Hydroxide),
+        GhodiumAlkalide => (GhodiumOxide, Hydroxide),
+
+        CatalyzedUtriumAcid => (UtriumAcid, Catalyst),
+        CatalyzedUtriumAlkalide => (UtriumAlkalide, Catalyst),
+        CatalyzedKeaniumAcid => (KeaniumAcid, Catalyst),
+        CatalyzedKeaniumAlkalide => (KeaniumAlkalide, Catalyst),
+        CatalyzedLemergiumAcid => (LemergiumAcid, Catalyst),
+        CatalyzedLemergiumAlkalide => (LemergiumAlkalide, Catalyst),
+        CatalyzedZynthiumAcid => (ZynthiumAcid, Catalyst),
+        CatalyzedZynthiumAlkalide => (ZynthiumAlkalide, Catalyst),
+        CatalyzedGhodiumAcid => (GhodiumAcid, Catalyst),
+        CatalyzedGhodiumAlkalide => (GhodiumAlkalide, Catalyst),
+
+        _ => return None,
+    };
+
+    return Some(reagents);
+}
+
+pub fn get_lab_reaction(room_name: &RoomName) -> Option<LabReaction> {
+    let reactions = LAB_REACTION_CACHE.read().unwrap();
+    return reactions.get(room_name).cloned();
+}
+
+// labに入っているminerals. energyは数えない.
+pub fn get_lab_mineral(lab: &StructureLab) -> Option<ResourceType> {
+    return lab
+        .store_types()
+        .into_iter()
+        .find(|resource| *resource != ResourceType::Energy);
+}
+
+// storageとterminalの在庫.
+pub fn get_room_stock(room: &Room, resource: ResourceType) -> u32 {
+    let mut amount = 0;
+
+    if let Some(storage) = room.storage() {
+        amount += storage.store_of(resource);
+    }
+    if let Some(terminal) = room.terminal() {
+        amount += terminal.store_of(resource);
+    }
+
+    return amount;
+}
+
+fn get_room_labs(room: &Room) -> Vec<StructureLab> {
+    let mut labs: Vec<StructureLab> = room
+        .find(MY_STRUCTURES)
+        .into_iter()
+        .filter_map(|structure| match structure {
+            Structure::Lab(lab) => Some(lab),
+            _ => None,
+        })
+        .collect();
+
+    // tickごとに結果が変わらないようにidで並べておく.
+    labs.sort_by_key(|lab| lab.id().to_string());
+
+    return labs;
+}
+
+// 他のlabの多くから2マス以内にある2つをinputにする.
+pub fn get_lab_setup(room: &Room) -> Option<LabSetup> {
+    let labs = get_room_labs(room);
+    if labs.len() < 3 {
+        return None;
+    }
+
+    let mut best: Option<(usize, usize, usize)> = None;
+
+    for i in 0..labs.len() {
+        for j in (i + 1)..labs.len() {
+            let num_outputs = labs
+                .iter()
+                .enumerate()
+                .filter(|(k, lab)| {
+                    *k != i
+                        && *k != j
+                        && lab.pos().get_range_to(&labs[i].pos()) <= 2
+                        && lab.pos().get_range_to(&labs[j].pos()) <= 2
+                })
+                .count();
+
+            let is_better = match best {
+                Some((_i, _j, best_num)) => num_outputs > best_num,
+                None => num_outputs > 0,
+            };
+            if is_better == true {
+                best = Some((i, j, num_outputs));
+            }
+        }
+    }
+
+    let (i, j, _num_outputs) = best?;
+
+    let outputs = labs
+        .iter()
+        .enumerate()
+        .filter(|(k, lab)| {
+            *k != i
+                && *k != j
+                && lab.pos().get_range_to(&labs[i].pos()) <= 2
+                && lab.pos().get_range_to(&labs[j].pos()) <= 2
+        })
+        .map(|(_k, lab)| lab.clone())
+        .collect();
+
+    return Some(LabSetup {
+        inputs: (labs[i].clone(), labs[j].clone()),
+        outputs: outputs,
+    });
+}
+
+// storage/terminalとlabの合計.
+fn get_product_stock(room: &Room, product: ResourceType) -> u32 {
+    let in_labs: u32 = get_room_labs(room)
+        .iter()
+        .map(|lab| lab.store_of(product))
+        .sum();
+
+    return get_room_stock(room, product) + in_labs;
+}
+
+// あと何個作るか.
+fn get_remaining_amount(room: &Room, reaction: &LabReaction) -> u32 {
+    let made = get_product_stock(room, reaction.product).saturating_sub(reaction.start_stock);
+    return reaction.amount.saturating_sub(made);
+}
+
+// productを作るために、今すぐ始められる一番下の反応を探す.
+fn find_next_reaction(
+    room: &Room,
+    product: ResourceType,
+    amount: u32,
+    depth: u32,
+) -> Option<LabReaction> {
+    if depth > LAB_MAX_CHAIN_DEPTH {
+        return None;
+    }
+
+    let reagents = get_reaction_reagents(product)?;
+
+    for reagent in [reagents.0, reagents.1].iter() {
+        let stock = get_room_stock(room, *reagent);
+        if stock >= amount {
+            continue;
+        }
+
+        // 足りない材料を先に作る. 作れなければこの目標は諦める.
+        let deficit = std::cmp::max(amount - stock, LAB_MIN_REACTION_AMOUNT);
+        return find_next_reaction(room, *reagent, deficit, depth + 1);
+    }
+
+    return Some(LabReaction {
+        product: product,
+        reagents: reagents,
+        amount: amount,
+        start_stock: get_product_stock(room, product),
+        started: game::time(),
+    });
+}
+
+fn plan_reaction(room: &Room) -> Option<LabReaction> {
+    for (product, target) in get_stock_targets() {
+        let stock = get_room_stock(room, product);
+        if stock >= target {
+            continue;
+        }
+
+        let amount = std::cmp::min(target - stock, LAB_BATCH_AMOUNT);
+        match find_next_reaction(
+            room,
+            product,
+            std::cmp::max(amount, LAB_MIN_REACTION_AMOUNT),
+            0,
+        ) {
+            Some(reaction) => {
+                info!(
+                    "lab reaction planned {}: {:?} for {:?}, amount:{}",
+                    room.name(),
+                    reaction.product,
+                    product,
+                    reaction.amount
+                );
+                return Some(reaction);
+            }
+            None => {
+                debug!("can't make {:?} in {}", product, room.name());
+            }
+        }
+    }
+
+    return None;
+}
+
+// 予定の量を作り終えたか、材料がlabにもstorageにも残っていなければ終わり.
+fn check_reaction_finished(room: &Room, setup: &LabSetup, reaction: &LabReaction) -> bool {
+    if game::time() - reaction.started > LAB_REACTION_TIMEOUT {
+        return true;
+    }
+
+    if get_remaining_amount(room, reaction) <= 0 {
+        return true;
+    }
+
+    let (reagent1, reagent2) = reaction.reagents;
+    let in_lab1 = setup.inputs.0.store_of(reagent1) + setup.inputs.1.store_of(reagent1);
+    let in_lab2 = setup.inputs.0.store_of(reagent2) + setup.inputs.1.store_of(reagent2);
+
+    let is_empty1 = in_lab1 < LAB_REACTION_AMOUNT && get_room_stock(room, reagent1) <= 0;
+    let is_empty2 = in_lab2 < LAB_REACTION_AMOUNT && get_room_stock(room, reagent2) <= 0;
+
+    return is_empty1 || is_empty2;
+}
+
+// input labには決まった材料だけ. 違う物が入っていればlab haulerが回収する.
+pub fn get_input_reagent(
+    setup: &LabSetup,
+    reaction: &LabReaction,
+    lab: &StructureLab,
+) -> Option<ResourceType> {
+    if lab.id() == setup.inputs.0.id() {
+        return Some(reaction.reagents.0);
+    }
+    if lab.id() == setup.inputs.1.id() {
+        return Some(reaction.reagents.1);
+    }
+    return None;
+}
+
+// storage -> terminalの順にresourceのある所を探す.
+pub fn find_stored(room: &Room, resource: ResourceType) -> Option<(Structure, u32)> {
+    if let Some(storage) = room.storage() {
+        let stored = storage.store_of(resource);
+        if stored > 0 {
+            return Some((Structure::Storage(storage), stored));
+        }
+    }
+
+    if let Some(terminal) = room.terminal() {
+        let stored = terminal.store_of(resource);
+        if stored > 0 {
+            return Some((Structure::Terminal(terminal), stored));
+        }
+    }
+
+    return None;
+}
+
+pub fn make_fill_task(room: &Room, resource: ResourceType, needed: u32) -> Option<LabHaulTask> {
+    let (store, stored) = find_stored(room, resource)?;
+
+    return Some(LabHaulTask {
+        from: store,
+        resource: resource,
+        amount: std::cmp::min(needed, stored),
+    });
+}
+
+// lab haulerの仕事. 違う物の回収 -> 完成品の回収 -> boost labの補充 -> 材料の補充の順.
+pub fn get_lab_haul_task(room: &Room) -> Option<LabHaulTask> {
+    let setup = get_lab_setup(room)?;
+    let reaction = get_lab_reaction(&room.name());
+
+    let mut all_labs = vec![setup.inputs.0.clone(), setup.inputs.1.clone()];
+    all_labs.extend(setup.outputs.iter().cloned());
+
+    for lab in all_labs.iter() {
+        let mineral = match get_lab_mineral(lab) {
+            Some(mineral) => mineral,
+            None => continue,
+        };
+
+        let expected = match get_boost_compound_for_lab(&room.name(), lab) {
+            Some(compound) => Some(compound),
+            None => match reaction.as_ref() {
+                Some(reaction) => match get_input_reagent(&setup, reaction, lab) {
+                    Some(reagent) => Some(reagent),
+                    None => Some(reaction.product),
+                },
+                None => None,
+            },
+        };
+
+        if expected != Some(mineral) {
+            return Some(LabHaulTask {
+                from: Structure::Lab(lab.clone()),
+                resource: mineral,
+                amount: lab.store_of(mineral),
+            });
+        }
+    }
+
+    if let Some(reaction) = reaction.as_ref() {
+        for lab in setup.outputs.iter() {
+            if get_boost_compound_for_lab(&room.name(), lab).is_some() {
+                continue;
+            }
+
+            let amount = lab.store_of(reaction.product);
+            if amount >= LAB_OUTPUT_COLLECT_AMOUNT {
+                return Some(LabHaulTask {
+                    from: Structure::Lab(lab.clone()),
+                    resource: reaction.product,
+                    amount: amount,
+                });
+            }
+        }
+    }
+
+    for lab in setup.outputs.iter() {
+        let compound = match get_boost_compound_for_lab(&room.name(), lab) {
+            Some(compound) => compound,
+            None => continue,
+        };
+
+        let amount = lab.store_of(compound);
+        if amount < LAB_BOOST_FILL_AMOUNT {
+            if let Some(task) = make_fill_task(room, compound, LAB_BOOST_FILL_AMOUNT - amount) {
+                return Some(task);
+            }
+        }
+
+        let energy = lab.store_of(ResourceType::Energy);
+        if energy < LAB_BOOST_ENERGY_FILL_AMOUNT {
+            if let Some(task) = make_fill_task(
+                room,
+                ResourceType::Energy,
+                LAB_BOOST_ENERGY_FILL_AMOUNT - energy,
+            ) {
+                return Some(task);
+            }
+        }
+    }
+
+    let reaction = reaction?;
+
+    // 予定の量の分だけ入れる. 余った材料は他の反応に回す.
+    let remaining = get_remaining_amount(room, &reaction);
+    let fill_amount = std::cmp::min(LAB_INPUT_FILL_AMOUNT, remaining);
+
+    for (lab, reagent) in [
+        (&setup.inputs.0, reaction.reagents.0),
+        (&setup.inputs.1, reaction.reagents.1),
+    ]
+    .iter()
+    {
+        let amount = lab.store_of(*reagent);
+        if amount >= fill_amount {
+            continue;
+        }
+
+        if let Some(task) = make_fill_task(room, *reagent, fill_amount - amount) {
+            return Some(task);
+        }
+    }
+
+    return None;
+}
+
+fn run_room_lab(room: &Room) {
+    let setup = match get_lab_setup(room) {
+        Some(setup) => setup,
+        None => return,
+    };
+
+    let mut reaction = get_lab_reaction(&room.name());
+
+    let is_finished = match reaction.as_ref() {
+        Some(reaction) => check_reaction_finished(room, &setup, reaction),
+        None => true,
+    };
+
+    if is_finished == true && game::time() % LAB_PLAN_INTERVAL == 0 {
+        reaction = plan_reaction(room);
+
+        let mut reactions = LAB_REACTION_CACHE.write().unwrap();
+        match reaction.as_ref() {
+            Some(reaction) => {
+                reactions.insert(room.name(), reaction.clone());
+            }
+            None => {
+                reactions.remove(&room.name());
+            }
+        }
+    }
+
+    let reaction = match reaction {
+        Some(reaction) => reaction,
+        None => return,
+    };
+
+    let (input1, input2) = &setup.inputs;
+    if input1.store_of(reaction.reagents.0) < LAB_REACTION_AMOUNT
+        || input2.store_of(reaction.reagents.1) < LAB_REACTION_AMOUNT
+    {
+        return;
+    }
+
+    for output in setup.outputs.iter() {
+        if output.cooldown() > 0 {
+            continue;
+        }
+
+        // boost用に押さえているlabは使わない.
+        if get_boost_compound_for_lab(&room.name(), output).is_some() {
+            continue;
+        }
+
+        // 他の物が入っているlabでは反応できない.
+        match get_lab_mineral(output) {
+            Some(mineral) if mineral != reaction.product => continue,
+            _ => {}
+        }
+
+        let r = output.run_reaction(input1, input2);
+        if r != ReturnCode::Ok {
+            debug!("couldn't run reaction {:?}: {:?}", reaction.product, r);
+        }
+    }
+}
+
+pub fn run_lab() {
+    for room in game::rooms::values() {
+        let controller = match room.controller() {
+            Some(controller) => controller,
+            None => continue,
+        };
+
+        if controller.my() == false {
+            continue;
+        }
+
+        run_room_lab(&room);
+    }
+}
@@ -1,68 +1,151 @@
-use crate::util::*;
+use crate::constants::*;
 use log::*;
-
+use screeps::constants::find::*;
 use screeps::Structure;
-use screeps::{
-    find, game, local::ObjectId, objects::StructureLink, pathfinder::SearchResults, prelude::*,
-    Attackable, Creep, Part, ResourceType, ReturnCode, RoomObjectProperties, StructureType,
-};
+use screeps::{objects::StructureLink, prelude::*, Position, ResourceType, ReturnCode, Room};
+use std::collections::HashMap;
 
-pub fn run_link() {
-    let mut min_link: Option<ObjectId<StructureLink>> = None;
-    let mut max_link: Option<ObjectId<StructureLink>> = None;
-
-    for game_structure in screeps::game::structures::values() {
-        if check_my_structure(&game_structure) == true {
-            match game_structure {
-                Structure::Link(my_link) => {
-                    debug!("check links {}", my_link.id());
-
-                    if min_link == None {
-                        min_link = Some(my_link.id());
-                    } else if my_link.store_of(ResourceType::Energy)
-                        < game::get_object_typed(min_link.unwrap())
-                            .unwrap()
-                            .unwrap()
-                            .store_of(ResourceType::Energy)
-                    {
-                        min_link = Some(my_link.id());
-                    }
-
-                    if max_link == None {
-                        max_link = Some(my_link.id());
-                    } else if my_link.store_of(ResourceType::Energy)
-                        > game::get_object_typed(max_link.unwrap())
-                            .unwrap()
-                            .unwrap()
-                            .store_of(ResourceType::Energy)
-                    {
-                        max_link = Some(my_link.id());
-                    }
-                }
-
-                _ => {}
-            }
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
+pub enum LinkRole {
+    SOURCE,
+    CONTROLLER,
+    STORAGE,
+    NONE,
+}
+
+// 周りに何があるかでlinkの役割を決める.
+pub fn get_link_role(link_pos: &Position, room: &Room) -> LinkRole {
+    if let Some(storage) = room.storage() {
+        if link_pos.get_range_to(&storage.pos()) <= LINK_STORAGE_RANGE {
+            return LinkRole::STORAGE;
+        }
+    }
+
+    if let Some(controller) = room.controller() {
+        if link_pos.get_range_to(&controller.pos()) <= LINK_CONTROLLER_RANGE {
+            return LinkRole::CONTROLLER;
+        }
+    }
+
+    for source in room.find(SOURCES) {
+        if link_pos.get_range_to(&source.pos()) <= LINK_SOURCE_RANGE {
+            return LinkRole::SOURCE;
         }
     }
 
-    info!("Link: Max:{:?}, Min:{:?}", max_link, min_link);
-    if min_link == None || max_link == None || min_link == max_link {
+    return LinkRole::NONE;
+}
+
+fn get_room_links(room: &Room) -> Vec<(StructureLink, LinkRole)> {
+    let mut links = Vec::new();
+
+    for structure in room.find(MY_STRUCTURES) {
+        if let Structure::Link(my_link) = structure {
+            let role = get_link_role(&my_link.pos(), room);
+            links.push((my_link, role));
+        }
+    }
+
+    return links;
+}
+
+fn check_link_needs_energy(link: &StructureLink) -> bool {
+    return (link.store_of(ResourceType::Energy) as f64)
+        < (link.store_capacity(Some(ResourceType::Energy)) as f64 * LINK_CONTROLLER_REFILL_RATE);
+}
+
+// controller linkが空いていれば、storage linkに詰めてもらう.
+pub fn check_controller_link_needs_energy(room: &Room) -> bool {
+    return get_room_links(room)
+        .iter()
+        .any(|(link, role)| *role == LinkRole::CONTROLLER && check_link_needs_energy(link));
+}
+
+// controller -> storageの順に受け取り先を並べる.
+fn get_receivers(links: &Vec<(StructureLink, LinkRole)>) -> Vec<(StructureLink, LinkRole)> {
+    let mut receivers: Vec<(StructureLink, LinkRole)> = links
+        .iter()
+        .filter(|(link, role)| match role {
+            LinkRole::CONTROLLER => check_link_needs_energy(link),
+            LinkRole::STORAGE => link.store_free_capacity(Some(ResourceType::Energy)) > 0,
+            _ => false,
+        })
+        .cloned()
+        .collect();
+
+    receivers.sort_by_key(|(_link, role)| *role != LinkRole::CONTROLLER);
+
+    return receivers;
+}
+
+fn run_room_link(room: &Room) {
+    let links = get_room_links(room);
+    if links.len() < 2 {
         return;
     }
 
-    let max_link_structure = game::get_object_typed(max_link.unwrap()).unwrap().unwrap();
-    let min_link_structure = game::get_object_typed(min_link.unwrap()).unwrap().unwrap();
+    let receivers = get_receivers(&links);
+    let mut pending: HashMap<String, u32> = HashMap::new();
 
-    let diff = max_link_structure.store_of(ResourceType::Energy)
-        - min_link_structure.store_of(ResourceType::Energy);
+    // source linkから先に送る. storage linkはcontroller linkにだけ送る.
+    let mut senders: Vec<&(StructureLink, LinkRole)> = links
+        .iter()
+        .filter(|(_link, role)| *role == LinkRole::SOURCE || *role == LinkRole::STORAGE)
+        .collect();
+    senders.sort_by_key(|(_link, role)| *role != LinkRole::SOURCE);
 
-    if diff >= 300 {
-        if max_link_structure.cooldown() <= 0 {
-            let r = max_link_structure.transfer_energy(&min_link_structure, Some(diff / 2));
+    for (sender, sender_role) in senders {
+        let energy = sender.store_of(ResourceType::Energy);
 
-            if r != ReturnCode::Ok {
-                warn!("couldn't transfer to another link:{:?}", r);
+        if sender.cooldown() > 0 || energy < LINK_MIN_TRANSFER {
+            debug!(
+                "link is not ready {}: cooldown:{}",
+                sender.id(),
+                sender.cooldown()
+            );
+            continue;
+        }
+
+        for (receiver, receiver_role) in receivers.iter() {
+            if *sender_role == LinkRole::STORAGE && *receiver_role != LinkRole::CONTROLLER {
+                continue;
             }
+
+            let receiver_id = receiver.id().to_string();
+            let reserved = *pending.get(&receiver_id).unwrap_or(&0);
+            let free = receiver.store_free_capacity(Some(ResourceType::Energy)) as u32;
+
+            if free <= reserved || free - reserved < LINK_MIN_TRANSFER {
+                continue;
+            }
+
+            let amount = std::cmp::min(energy, free - reserved);
+            let r = sender.transfer_energy(receiver, Some(amount));
+
+            info!(
+                "Link: {:?} -> {:?}, amount:{}, {:?}",
+                sender_role, receiver_role, amount, r
+            );
+
+            if r == ReturnCode::Ok {
+                pending.insert(receiver_id, reserved + amount);
+                break;
+            }
+        }
+    }
+}
+
+pub fn run_link() {
+    for room in screeps::game::rooms::values() {
+        let controller = match room.controller() {
+            Some(controller) => controller,
+            None => continue,
+        };
+
+        if controller.my() == false {
+            continue;
         }
+
+        run_room_link(&room);
     }
 }
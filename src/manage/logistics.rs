@@ -0,0 +1,718 @@
+use crate::constants::*;
+use crate::manage::link::*;
+use log::*;
+use screeps::constants::find::*;
+use screeps::{
+    game, local::RawObjectId, prelude::*, Creep, Position, ResourceType, ReturnCode, Room,
+    RoomName, Structure, StructureType,
+};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+use lazy_static::lazy_static;
+use std::sync::RwLock;
+
+// 採掘container/link/落とし物 -> storage -> terminal -> 消費側.
+pub const LOGISTICS_RANK_SOURCE: u32 = 0;
+pub const LOGISTICS_RANK_STORAGE: u32 = 1;
+pub const LOGISTICS_RANK_TERMINAL: u32 = 2;
+pub const LOGISTICS_RANK_CONSUMER: u32 = 3;
+
+// 部屋の中だけで使うので座標だけ持つ.
+#[derive(Clone, Debug)]
+pub struct LogisticsEntry {
+    pub id: String,
+    pub x: u32,
+    pub y: u32,
+    pub amount: u32,
+    pub priority: u32,
+    // energyの流れる順番. rankの低い所から高い所へだけ運ぶ.
+    pub rank: u32,
+}
+
+#[derive(Default)]
+struct RoomLogistics {
+    requests: Vec<LogisticsEntry>,
+    offers: Vec<LogisticsEntry>,
+}
+
+// 割り当てを決めるのに使うcreepの状態.
+#[derive(Clone, Debug)]
+pub struct LogisticsCarrier {
+    pub x: u32,
+    pub y: u32,
+    pub carrying: u32,
+    pub capacity: u32,
+    pub withdrawn_from: Option<String>,
+    pub withdrawn_rank: Option<u32>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct LogisticsAssignment {
+    pub offer: Option<String>,
+    pub offer_rank: u32,
+    pub request: String,
+    pub amount: u32,
+}
+
+type LogisticsData = HashMap<RoomName, RoomLogistics>;
+
+lazy_static! {
+    static ref LOGISTICS_CACHE: RwLock<LogisticsData> = RwLock::new(HashMap::new());
+}
+
+pub fn register_request(room_name: RoomName, entry: LogisticsEntry) {
+    let mut logistics = LOGISTICS_CACHE.write().unwrap();
+    logistics
+        .entry(room_name)
+        .or_insert(RoomLogistics::default())
+        .requests
+        .push(entry);
+}
+
+pub fn register_offer(room_name: RoomName, entry: LogisticsEntry) {
+    let mut logistics = LOGISTICS_CACHE.write().unwrap();
+    logistics
+        .entry(room_name)
+        .or_insert(RoomLogistics::default())
+        .offers
+        .push(entry);
+}
+
+pub fn get_logistics_rank(structure_type: StructureType) -> u32 {
+    match structure_type {
+        StructureType::Storage => LOGISTICS_RANK_STORAGE,
+        StructureType::Terminal => LOGISTICS_RANK_TERMINAL,
+        StructureType::Spawn
+        | StructureType::Extension
+        | StructureType::Tower
+        | StructureType::PowerSpawn => LOGISTICS_RANK_CONSUMER,
+        _ => LOGISTICS_RANK_SOURCE,
+    }
+}
+
+fn make_entry(structure: &Structure, amount: u32, priority: u32) -> LogisticsEntry {
+    return LogisticsEntry {
+        id: structure.id().to_string(),
+        x: structure.pos().x(),
+        y: structure.pos().y(),
+        amount: amount,
+        priority: priority,
+        rank: get_logistics_rank(structure.structure_type()),
+    };
+}
+
+fn register_room(room: &Room) {
+    let room_name = room.name();
+
+    let controller_pos = room.controller().map(|c| c.pos());
+    let is_controller_link_hungry = check_controller_link_needs_energy(room);
+
+    for structure in room.find(STRUCTURES) {
+        let has_store = match structure.as_has_store() {
+            Some(has_store) => has_store,
+            None => continue,
+        };
+
+        let energy = has_store.store_of(ResourceType::Energy);
+        let free =
+            std::cmp::max(has_store.store_free_capacity(Some(ResourceType::Energy)), 0) as u32;
+
+        match structure.structure_type() {
+            StructureType::Spawn => {
+                if free > 0 {
+                    register_request(room_name, make_entry(&structure, free, 100));
+                }
+            }
+
+            StructureType::Extension => {
+                if free > 0 {
+                    register_request(room_name, make_entry(&structure, free, 90));
+                }
+            }
+
+            StructureType::Tower => {
+                if free > has_store.store_capacity(Some(ResourceType::Energy)) / 2 {
+                    register_request(room_name, make_entry(&structure, free, 80));
+                } else if free > 0 {
+                    register_request(room_name, make_entry(&structure, free, 40));
+                }
+            }
+
+            StructureType::PowerSpawn => {
+                // 半分以上空いたらまとめて詰める.
+                if free > has_store.store_capacity(Some(ResourceType::Energy)) / 2 {
+                    register_request(room_name, make_entry(&structure, free, 15));
+                }
+            }
+
+            StructureType::Terminal => {
+                if energy < TERMINAL_KEEP_ENERGY {
+                    register_request(
+                        room_name,
+                        make_entry(&structure, TERMINAL_KEEP_ENERGY - energy, 30),
+                    );
+                } else if energy > TERMINAL_KEEP_ENERGY {
+                    register_offer(
+                        room_name,
+                        make_entry(&structure, energy - TERMINAL_KEEP_ENERGY, 5),
+                    );
+                }
+            }
+
+            StructureType::Storage => {
+                if free > 0 {
+                    register_request(room_name, make_entry(&structure, free, 10));
+                }
+                if energy > 0 {
+                    register_offer(room_name, make_entry(&structure, energy, 10));
+                }
+            }
+
+            StructureType::Container => {
+                // controllerの横のcontainerはupgrader用の受け取り先.
+                let is_controller_container = controller_pos
+                    .map(|pos| structure.pos().get_range_to(&pos) <= 3)
+                    .unwrap_or(false);
+
+                if is_controller_container {
+                    if free > 0 {
+                        let mut entry = make_entry(&structure, free, 20);
+                        entry.rank = LOGISTICS_RANK_CONSUMER;
+                        register_request(room_name, entry);
+                    }
+                } else if energy > 0 {
+                    register_offer(room_name, make_entry(&structure, energy, 80));
+                }
+            }
+
+            StructureType::Link => {
+                // storage linkはcontroller linkが空いていれば詰める側、それ以外は受け取り側.
+                if get_link_role(&structure.pos(), room) != LinkRole::STORAGE {
+                    continue;
+                }
+
+                if is_controller_link_hungry {
+                    if free > 0 {
+                        let mut entry = make_entry(&structure, free, 50);
+                        entry.rank = LOGISTICS_RANK_CONSUMER;
+                        register_request(room_name, entry);
+                    }
+                } else if energy > 0 {
+                    register_offer(room_name, make_entry(&structure, energy, 70));
+                }
+            }
+
+            _ => {}
+        }
+    }
+
+    for resource in room.find(DROPPED_RESOURCES) {
+        if resource.resource_type() == ResourceType::Energy {
+            register_offer(
+                room_name,
+                LogisticsEntry {
+                    id: resource.id().to_string(),
+                    x: resource.pos().x(),
+                    y: resource.pos().y(),
+                    amount: resource.amount(),
+                    priority: 100,
+                    rank: LOGISTICS_RANK_SOURCE,
+                },
+            );
+        }
+    }
+
+    for tombstone in room.find(TOMBSTONES) {
+        let energy = tombstone.store_of(ResourceType::Energy);
+        if energy > 0 {
+            register_offer(
+                room_name,
+                LogisticsEntry {
+                    id: tombstone.id().to_string(),
+                    x: tombstone.pos().x(),
+                    y: tombstone.pos().y(),
+                    amount: energy,
+                    priority: 90,
+                    rank: LOGISTICS_RANK_SOURCE,
+                },
+            );
+        }
+    }
+}
+
+pub fn load_assignment(creep: &Creep) -> Option<LogisticsAssignment> {
+    let json_str = creep.memory().string("logistics").unwrap_or(None)?;
+    return serde_json::from_str(json_str.as_str()).ok();
+}
+
+fn save_assignment(creep: &Creep, assignment: &LogisticsAssignment) {
+    let json_str = serde_json::to_string(assignment).unwrap();
+    creep.memory().set("logistics", json_str);
+}
+
+pub fn clear_assignment(creep: &Creep) {
+    creep.memory().del("logistics");
+}
+
+// 引き出し元を覚えておき、そこ(とそれより上流)へは戻さない.
+pub fn set_withdrawn_from(creep: &Creep, id: &str, rank: u32) {
+    creep.memory().set("withdrawn_from", id);
+    creep.memory().set("withdrawn_rank", rank as i32);
+}
+
+pub fn clear_withdrawn_from(creep: &Creep) {
+    creep.memory().del("withdrawn_from");
+    creep.memory().del("withdrawn_rank");
+}
+
+// 割り当て済みの量を差し引いた残り.
+fn calc_reserved(room: &Room) -> (HashMap<String, u32>, HashMap<String, u32>) {
+    let mut reserved_requests = HashMap::<String, u32>::new();
+    let mut reserved_offers = HashMap::<String, u32>::new();
+
+    for my_creep in room.find(MY_CREEPS) {
+        if let Some(assignment) = load_assignment(&my_creep) {
+            *reserved_requests
+                .entry(assignment.request.clone())
+                .or_insert(0) += assignment.amount;
+
+            if let Some(offer) = assignment.offer {
+                *reserved_offers.entry(offer).or_insert(0) += assignment.amount;
+            }
+        }
+    }
+
+    return (reserved_requests, reserved_offers);
+}
+
+fn calc_range(from: (u32, u32), entry: &LogisticsEntry) -> u32 {
+    let dx = (from.0 as i32 - entry.x as i32).abs() as u32;
+    let dy = (from.1 as i32 - entry.y as i32).abs() as u32;
+    return std::cmp::max(dx, dy);
+}
+
+// 条件に合うentryを優先度の高い順、同じなら近い順に並べる.
+fn rank_entries<'a>(
+    entries: &'a Vec<LogisticsEntry>,
+    reserved: &HashMap<String, u32>,
+    from: (u32, u32),
+    exclude_id: Option<&String>,
+    is_rank_allowed: &dyn Fn(u32) -> bool,
+) -> Vec<&'a LogisticsEntry> {
+    let mut ranked: Vec<&LogisticsEntry> = entries
+        .iter()
+        .filter(|entry| Some(&entry.id) != exclude_id)
+        .filter(|entry| is_rank_allowed(entry.rank))
+        .filter(|entry| *reserved.get(&entry.id).unwrap_or(&0) < entry.amount)
+        .collect();
+
+    ranked.sort_by_key(|entry| (std::cmp::Reverse(entry.priority), calc_range(from, entry)));
+
+    return ranked;
+}
+
+// energyを持っていればrequestだけ、空ならofferから引き出してrequestへ.
+// 引き出した先(とそれより上流)へ戻すことはない.
+pub fn select_assignment(
+    requests: &Vec<LogisticsEntry>,
+    offers: &Vec<LogisticsEntry>,
+    reserved_requests: &HashMap<String, u32>,
+    reserved_offers: &HashMap<String, u32>,
+    carrier: &LogisticsCarrier,
+) -> Option<LogisticsAssignment> {
+    let from = (carrier.x, carrier.y);
+
+    if carrier.carrying > 0 {
+        let withdrawn_rank = carrier.withdrawn_rank;
+        let request = *rank_entries(
+            requests,
+            reserved_requests,
+            from,
+            carrier.withdrawn_from.as_ref(),
+            &|rank| withdrawn_rank.map(|w| rank > w).unwrap_or(true),
+        )
+        .first()?;
+
+        return Some(LogisticsAssignment {
+            offer: None,
+            offer_rank: withdrawn_rank.unwrap_or(LOGISTICS_RANK_SOURCE),
+            request: request.id.clone(),
+            amount: carrier.carrying,
+        });
+    }
+
+    // 上流にofferがないrequestは飛ばして次へ.
+    for request in rank_entries(requests, reserved_requests, from, None, &|_rank| true) {
+        let request_rank = request.rank;
+        let offer = match rank_entries(
+            offers,
+            reserved_offers,
+            (request.x, request.y),
+            Some(&request.id),
+            &|rank| rank < request_rank,
+        )
+        .first()
+        {
+            Some(offer) => *offer,
+            None => continue,
+        };
+
+        return Some(LogisticsAssignment {
+            offer: Some(offer.id.clone()),
+            offer_rank: offer.rank,
+            request: request.id.clone(),
+            amount: std::cmp::min(
+                carrier.capacity,
+                std::cmp::min(offer.amount, request.amount),
+            ),
+        });
+    }
+
+    return None;
+}
+
+// creepに(offer, request)の組を割り当てる.
+pub fn assign_logistics(creep: &Creep) -> Option<LogisticsAssignment> {
+    if let Some(assignment) = load_assignment(creep) {
+        return Some(assignment);
+    }
+
+    let room = creep.room().expect("room is not visible to you");
+    let (reserved_requests, reserved_offers) = calc_reserved(&room);
+
+    let logistics = LOGISTICS_CACHE.read().unwrap();
+    let room_logistics = logistics.get(&room.name())?;
+
+    let carrier = LogisticsCarrier {
+        x: creep.pos().x(),
+        y: creep.pos().y(),
+        carrying: creep.store_of(ResourceType::Energy),
+        capacity: std::cmp::max(creep.store_free_capacity(Some(ResourceType::Energy)), 0) as u32,
+        withdrawn_from: creep.memory().string("withdrawn_from").unwrap_or(None),
+        withdrawn_rank: creep
+            .memory()
+            .i32("withdrawn_rank")
+            .unwrap_or(None)
+            .map(|rank| rank as u32),
+    };
+
+    let assignment = select_assignment(
+        &room_logistics.requests,
+        &room_logistics.offers,
+        &reserved_requests,
+        &reserved_offers,
+        &carrier,
+    )?;
+
+    debug!("logistics assign {}: {:?}", creep.name(), assignment);
+    save_assignment(creep, &assignment);
+
+    return Some(assignment);
+}
+
+fn move_to(creep: &Creep, target_pos: &Position) {
+    let res = crate::util::find_path(&creep, target_pos, 1);
+
+    if res.load_local_path().len() > 0 {
+        let res = creep.move_by_path_search_result(&res);
+        if res != ReturnCode::Ok {
+            info!("couldn't move to logistics target: {:?}", res);
+        }
+    }
+}
+
+fn run_withdraw(creep: &Creep, offer_id: &str, offer_rank: u32) -> ReturnCode {
+    let raw_id: RawObjectId = match offer_id.parse() {
+        Ok(id) => id,
+        Err(_err) => return ReturnCode::NotFound,
+    };
+
+    if let Ok(Some(resource)) = game::get_object_typed::<screeps::Resource>(raw_id.into()) {
+        let r = creep.pickup(&resource);
+        if r == ReturnCode::NotInRange {
+            move_to(creep, &resource.pos());
+        }
+        return r;
+    }
+
+    if let Ok(Some(tombstone)) = game::get_object_typed::<screeps::Tombstone>(raw_id.into()) {
+        let r = creep.withdraw_all(&tombstone, ResourceType::Energy);
+        if r == ReturnCode::NotInRange {
+            move_to(creep, &tombstone.pos());
+        }
+        return r;
+    }
+
+    if let Ok(Some(structure)) = game::get_object_typed::<Structure>(raw_id.into()) {
+        if let Some(withdrawable) = structure.as_withdrawable() {
+            let r = creep.withdraw_amount(
+                withdrawable,
+                ResourceType::Energy,
+                std::cmp::min(
+                    structure
+                        .as_has_store()
+                        .map(|s| s.store_of(ResourceType::Energy))
+                        .unwrap_or(0),
+                    std::cmp::max(creep.store_free_capacity(Some(ResourceType::Energy)), 0) as u32,
+                ),
+            );
+
+            if r == ReturnCode::Ok {
+                set_withdrawn_from(creep, offer_id, offer_rank);
+            } else if r == ReturnCode::NotInRange {
+                move_to(creep, &structure.pos());
+            }
+            return r;
+        }
+    }
+
+    return ReturnCode::NotFound;
+}
+
+fn run_deliver(creep: &Creep, request_id: &str) -> ReturnCode {
+    let raw_id: RawObjectId = match request_id.parse() {
+        Ok(id) => id,
+        Err(_err) => return ReturnCode::NotFound,
+    };
+
+    if let Ok(Some(structure)) = game::get_object_typed::<Structure>(raw_id.into()) {
+        if let Some(transferable) = structure.as_transferable() {
+            let r = creep.transfer_all(transferable, ResourceType::Energy);
+
+            if r == ReturnCode::NotInRange {
+                move_to(creep, &structure.pos());
+            }
+            return r;
+        }
+    }
+
+    return ReturnCode::NotFound;
+}
+
+// 割り当てに従って運ぶ. このtickの行動を消費した場合はtrueを返す.
+pub fn run_logistics_creep(creep: &Creep) -> bool {
+    let mut assignment = match assign_logistics(creep) {
+        Some(assignment) => assignment,
+        None => return false,
+    };
+
+    if let Some(offer_id) = assignment.offer.clone() {
+        if creep.store_of(ResourceType::Energy) <= 0 {
+            let r = run_withdraw(creep, offer_id.as_str(), assignment.offer_rank);
+
+            if r == ReturnCode::Ok || r == ReturnCode::NotInRange {
+                return true;
+            }
+
+            info!("logistics withdraw failed {}: {:?}", creep.name(), r);
+            clear_assignment(creep);
+            return false;
+        }
+
+        // 引き出し完了.
+        assignment.offer = None;
+        save_assignment(creep, &assignment);
+    }
+
+    let r = run_deliver(creep, assignment.request.as_str());
+
+    if r == ReturnCode::NotInRange {
+        return true;
+    }
+
+    clear_assignment(creep);
+
+    if r == ReturnCode::Ok {
+        info!("logistics delivered {}!!", creep.name());
+        return true;
+    }
+
+    info!("logistics deliver failed {}: {:?}", creep.name(), r);
+    return false;
+}
+
+pub fn run_logistics() {
+    {
+        let mut logistics = LOGISTICS_CACHE.write().unwrap();
+        logistics.clear();
+    }
+
+    for room in screeps::game::rooms::values() {
+        if let Some(controller) = room.controller() {
+            if controller.my() == true {
+                register_room(&room);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn make_test_entry(id: &str, x: u32, amount: u32, priority: u32, rank: u32) -> LogisticsEntry {
+        return LogisticsEntry {
+            id: id.to_string(),
+            x: x,
+            y: 25,
+            amount: amount,
+            priority: priority,
+            rank: rank,
+        };
+    }
+
+    // storageとterminalは両方に出てくる.
+    fn make_test_room() -> (Vec<LogisticsEntry>, Vec<LogisticsEntry>) {
+        let requests = vec![
+            make_test_entry("spawn", 10, 300, 100, LOGISTICS_RANK_CONSUMER),
+            make_test_entry("storage", 20, 10000, 10, LOGISTICS_RANK_STORAGE),
+            make_test_entry("terminal", 30, 500, 30, LOGISTICS_RANK_TERMINAL),
+        ];
+        let offers = vec![
+            make_test_entry("container", 40, 2000, 80, LOGISTICS_RANK_SOURCE),
+            make_test_entry("storage", 20, 50000, 10, LOGISTICS_RANK_STORAGE),
+            make_test_entry("terminal", 30, 3000, 5, LOGISTICS_RANK_TERMINAL),
+        ];
+        return (requests, offers);
+    }
+
+    fn make_test_carrier(carrying: u32, withdrawn_from: Option<&str>) -> LogisticsCarrier {
+        let withdrawn_rank = withdrawn_from.map(|id| match id {
+            "storage" => LOGISTICS_RANK_STORAGE,
+            "terminal" => LOGISTICS_RANK_TERMINAL,
+            _ => LOGISTICS_RANK_SOURCE,
+        });
+
+        return LogisticsCarrier {
+            x: 25,
+            y: 25,
+            carrying: carrying,
+            capacity: 1000,
+            withdrawn_from: withdrawn_from.map(|id| id.to_string()),
+            withdrawn_rank: withdrawn_rank,
+        };
+    }
+
+    #[test]
+    fn never_deliver_back_to_offer() {
+        let (requests, offers) = make_test_room();
+        let no_reserved = HashMap::new();
+
+        // 消費側が埋まっていても、引き出した所へは戻さない.
+        for num_reserved in 0..requests.len() {
+            let mut reserved_requests = HashMap::new();
+            for request in requests.iter().take(num_reserved) {
+                reserved_requests.insert(request.id.clone(), request.amount);
+            }
+
+            let carrier = make_test_carrier(0, None);
+            let assignment = select_assignment(
+                &requests,
+                &offers,
+                &reserved_requests,
+                &no_reserved,
+                &carrier,
+            )
+            .unwrap();
+
+            assert_ne!(assignment.offer, Some(assignment.request.clone()));
+            assert!(
+                assignment.offer_rank
+                    < requests
+                        .iter()
+                        .find(|r| r.id == assignment.request)
+                        .unwrap()
+                        .rank
+            );
+        }
+    }
+
+    #[test]
+    fn skip_request_without_upstream_offer() {
+        // storageが一番欲しがっているが、storageより上流のofferはない.
+        let requests = vec![
+            make_test_entry("storage", 20, 10000, 100, LOGISTICS_RANK_STORAGE),
+            make_test_entry("spawn", 10, 300, 50, LOGISTICS_RANK_CONSUMER),
+        ];
+        let offers = vec![
+            make_test_entry("storage", 20, 50000, 10, LOGISTICS_RANK_STORAGE),
+            make_test_entry("terminal", 30, 3000, 5, LOGISTICS_RANK_TERMINAL),
+        ];
+        let no_reserved = HashMap::new();
+
+        let carrier = make_test_carrier(0, None);
+        let assignment =
+            select_assignment(&requests, &offers, &no_reserved, &no_reserved, &carrier).unwrap();
+
+        assert_eq!(assignment.request, String::from("spawn"));
+        assert_eq!(assignment.offer, Some(String::from("storage")));
+    }
+
+    #[test]
+    fn never_deliver_back_to_withdrawn() {
+        let (requests, offers) = make_test_room();
+        let no_reserved = HashMap::new();
+
+        // terminalより下流はspawnだけなのでwithdrawn_from_terminal_has_nowhere_upstreamで見る.
+        for withdrawn_from in ["container", "storage"].iter() {
+            // spawnが埋まっていれば、storage/terminalが次の候補になる.
+            let mut reserved_requests = HashMap::new();
+            reserved_requests.insert(String::from("spawn"), 300);
+
+            let carrier = make_test_carrier(500, Some(withdrawn_from));
+            let assignment = select_assignment(
+                &requests,
+                &offers,
+                &reserved_requests,
+                &no_reserved,
+                &carrier,
+            )
+            .unwrap();
+
+            assert_ne!(assignment.request, withdrawn_from.to_string());
+            assert_eq!(assignment.offer, None);
+        }
+    }
+
+    #[test]
+    fn withdrawn_from_storage_goes_downstream() {
+        let (requests, offers) = make_test_room();
+        let no_reserved = HashMap::new();
+
+        let mut reserved_requests = HashMap::new();
+        reserved_requests.insert(String::from("spawn"), 300);
+
+        let carrier = make_test_carrier(500, Some("storage"));
+        let assignment = select_assignment(
+            &requests,
+            &offers,
+            &reserved_requests,
+            &no_reserved,
+            &carrier,
+        )
+        .unwrap();
+
+        assert_eq!(assignment.request, String::from("terminal"));
+    }
+
+    #[test]
+    fn withdrawn_from_terminal_has_nowhere_upstream() {
+        let (requests, offers) = make_test_room();
+        let no_reserved = HashMap::new();
+
+        let mut reserved_requests = HashMap::new();
+        reserved_requests.insert(String::from("spawn"), 300);
+
+        let carrier = make_test_carrier(500, Some("terminal"));
+        let assignment = select_assignment(
+            &requests,
+            &offers,
+            &reserved_requests,
+            &no_reserved,
+            &carrier,
+        );
+
+        assert!(assignment.is_none());
+    }
+}
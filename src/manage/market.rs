@@ -7,6 +7,26 @@ use screeps::{
     RoomObjectProperties, StructureType,
 };
 
+// 直近の平均価格. 取引がなければNone.
+pub fn get_average_price(resource: ResourceType) -> Option<f64> {
+    let market_history =
+        game::market::get_history(Some(screeps::MarketResourceType::Resource(resource)));
+
+    let mut price: f64 = 0 as f64;
+    let mut num_data: u128 = 0;
+
+    for history in market_history {
+        price += history.avg_price;
+        num_data += 1;
+    }
+
+    if num_data <= 0 {
+        return None;
+    }
+
+    return Some(price / num_data as f64);
+}
+
 pub fn run_market() {
     info!("running market");
 
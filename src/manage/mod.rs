@@ -1,2 +1,14 @@
+pub mod boost;
+pub mod deposit;
+pub mod economy;
+pub mod expansion;
+pub mod factory;
+pub mod intel;
+pub mod lab;
 pub mod link;
+pub mod logistics;
 pub mod market;
+pub mod power;
+pub mod remote;
+pub mod repair;
+pub mod source;
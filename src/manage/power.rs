@@ -0,0 +1,408 @@
+use crate::attack::squad::*;
+use crate::constants::*;
+use crate::manage::intel::*;
+use crate::manage::lab::*;
+use log::*;
+use screeps::constants::find::*;
+use screeps::constants::*;
+use screeps::{
+    game, objects::StructurePowerSpawn, prelude::*, Part, ResourceType, ReturnCode, Room, RoomName,
+    Structure,
+};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+#[derive(PartialEq, Eq, Clone, Copy, Debug, Serialize, Deserialize)]
+pub enum PowerOpState {
+    ATTACKING,
+    HAULING,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct PowerOp {
+    pub home: String,
+    pub room: String,
+    pub x: u32,
+    pub y: u32,
+    pub power: u32,
+    pub hits: u32,
+    pub decay_at: u32,
+    pub squads: Vec<String>,
+    pub state: PowerOpState,
+    pub created: u32,
+}
+
+// power bankのidごと.
+type PowerOpMap = HashMap<String, PowerOp>;
+
+pub fn load_power_ops() -> PowerOpMap {
+    let json_str = screeps::memory::root()
+        .string("power_ops")
+        .unwrap_or(None)
+        .unwrap_or(String::from("{}"));
+
+    return serde_json::from_str(json_str.as_str()).unwrap_or(HashMap::new());
+}
+
+fn save_power_ops(op_map: &PowerOpMap) {
+    let json_str = serde_json::to_string(op_map).unwrap();
+    screeps::memory::root().set("power_ops", json_str);
+}
+
+pub fn get_power_op(bank_id: &str) -> Option<PowerOp> {
+    return load_power_ops().get(bank_id).cloned();
+}
+
+// 残りHPが少なくなったら、壊れる前にhaulerを向かわせる.
+pub fn check_power_hauler_needed(op: &PowerOp) -> bool {
+    return op.state == PowerOpState::HAULING || op.hits <= POWER_HAULER_DISPATCH_HITS;
+}
+
+fn count_parts(body: &Vec<Part>, part: Part) -> u32 {
+    return body.iter().filter(|p| **p == part).count() as u32;
+}
+
+// power bankに一番近く、余裕のある部屋.
+fn find_power_home(room_name: RoomName) -> Option<(Room, u32)> {
+    let mut best: Option<(Room, u32)> = None;
+
+    for room in game::rooms::values() {
+        let controller = match room.controller() {
+            Some(controller) => controller,
+            None => continue,
+        };
+
+        if controller.my() == false || controller.level() < POWER_MIN_RCL {
+            continue;
+        }
+
+        let storage_energy = room
+            .storage()
+            .map(|s| s.store_of(ResourceType::Energy))
+            .unwrap_or(0);
+        if storage_energy < POWER_MIN_STORAGE_ENERGY {
+            continue;
+        }
+
+        let distance = game::map::get_room_linear_distance(room.name(), room_name, false);
+        if distance > POWER_MAX_DISTANCE {
+            continue;
+        }
+
+        let is_nearer = match &best {
+            Some((_room, best_distance)) => distance < *best_distance,
+            None => true,
+        };
+        if is_nearer == true {
+            best = Some((room, distance));
+        }
+    }
+
+    return best;
+}
+
+// 消える前に壊せるだけのペア数. 壊せなければNone.
+fn calc_needed_pairs(bank: &IntelPowerBank, home: &Room, distance: u32) -> Option<u32> {
+    let energy_capacity = home.energy_capacity_available();
+    let breaker_body = make_squad_body(SquadRole::BREAKER, energy_capacity);
+    let healer_body = make_squad_body(SquadRole::HEALER, energy_capacity);
+
+    let damage = count_parts(&breaker_body, Part::Attack) * ATTACK_POWER;
+    let heal = count_parts(&healer_body, Part::Heal) * HEAL_POWER;
+
+    // 殴った分の半分が返ってくる. 回復が追いつかなければ無理.
+    if damage <= 0 || heal < damage * POWER_BANK_HIT_BACK_PERCENT / 100 {
+        return None;
+    }
+
+    let spawn_time = (breaker_body.len() + healer_body.len()) as u32 * CREEP_SPAWN_TIME;
+    let travel_time = distance * POWER_TICKS_PER_ROOM;
+    let ticks_left = bank.decay_at.saturating_sub(game::time());
+
+    // 入れ替えは出さないので、1ペアが殴れるのは寿命の残りまで.
+    let available = std::cmp::min(
+        ticks_left.saturating_sub(spawn_time + travel_time),
+        CREEP_LIFE_TIME.saturating_sub(travel_time),
+    );
+    if available <= 0 {
+        return None;
+    }
+
+    let pairs = (bank.hits + damage * available - 1) / (damage * available);
+    if pairs > std::cmp::min(bank.free_tiles, POWER_MAX_PAIRS) {
+        return None;
+    }
+
+    return Some(std::cmp::max(pairs, 1));
+}
+
+fn plan_power_ops(op_map: &mut PowerOpMap) {
+    if op_map.len() >= POWER_MAX_OPS as usize {
+        return;
+    }
+
+    for (room_key, room_intel) in get_all_room_intel() {
+        let bank = match room_intel.power_bank {
+            Some(bank) => bank,
+            None => continue,
+        };
+
+        if op_map.contains_key(&bank.id) {
+            continue;
+        }
+
+        if bank.power < POWER_MIN_AMOUNT
+            || game::time() - room_intel.last_seen > POWER_INTEL_MAX_AGE
+        {
+            continue;
+        }
+
+        let room_name: RoomName = match room_key.parse() {
+            Ok(room_name) => room_name,
+            Err(_err) => continue,
+        };
+
+        let (home, distance) = match find_power_home(room_name) {
+            Some(home) => home,
+            None => continue,
+        };
+
+        let pairs = match calc_needed_pairs(&bank, &home, distance) {
+            Some(pairs) => pairs,
+            None => {
+                debug!("power bank is not worth it: {}, {:?}", room_key, bank);
+                continue;
+            }
+        };
+
+        info!(
+            "power op planned: {} from {}, power:{}, pairs:{}",
+            room_key,
+            home.name(),
+            bank.power,
+            pairs
+        );
+
+        let mut squads = Vec::new();
+        for i in 0..pairs {
+            let squad_id = format!("power_{}_{}", bank.id, i);
+            add_squad(
+                squad_id.as_str(),
+                Squad {
+                    home: home.name().to_string(),
+                    target_room: room_key.clone(),
+                    formation: Formation::POWER,
+                    state: SquadState::FORMING,
+                    members: Vec::new(),
+                    target_id: Some(bank.id.clone()),
+                },
+            );
+            squads.push(squad_id);
+        }
+
+        op_map.insert(
+            bank.id.clone(),
+            PowerOp {
+                home: home.name().to_string(),
+                room: room_key.clone(),
+                x: bank.x,
+                y: bank.y,
+                power: bank.power,
+                hits: bank.hits,
+                decay_at: bank.decay_at,
+                squads: squads,
+                state: PowerOpState::ATTACKING,
+                created: game::time(),
+            },
+        );
+
+        if op_map.len() >= POWER_MAX_OPS as usize {
+            return;
+        }
+    }
+}
+
+// 落ちているpowerと、廃墟に残っているpower.
+pub fn count_remaining_power(room: &Room) -> u32 {
+    let mut amount = 0;
+
+    for resource in room.find(DROPPED_RESOURCES) {
+        if resource.resource_type() == ResourceType::Power {
+            amount += resource.amount();
+        }
+    }
+
+    for ruin in room.find(RUINS) {
+        amount += ruin.store_of(ResourceType::Power);
+    }
+
+    return amount;
+}
+
+// 続けるならtrue.
+fn update_power_op(bank_id: &str, op: &mut PowerOp) -> bool {
+    if game::time() > op.decay_at + POWER_HAUL_TIMEOUT {
+        info!("power op timed out: {}", op.room);
+        return false;
+    }
+
+    let squad_map = load_squads();
+    if op.state == PowerOpState::ATTACKING
+        && op
+            .squads
+            .iter()
+            .all(|id| squad_map.contains_key(id) == false)
+    {
+        info!("power op lost its squads: {}", op.room);
+        return false;
+    }
+
+    let room = match op
+        .room
+        .parse::<RoomName>()
+        .ok()
+        .and_then(|name| game::rooms::get(name))
+    {
+        Some(room) => room,
+        None => return true,
+    };
+
+    let bank = room
+        .find(STRUCTURES)
+        .into_iter()
+        .find(|s| s.id().to_string() == bank_id);
+
+    match bank {
+        Some(Structure::PowerBank(bank)) => {
+            op.hits = bank.hits();
+            op.power = bank.power();
+        }
+        _ => {
+            if op.state == PowerOpState::ATTACKING {
+                info!("power bank destroyed: {}", op.room);
+                op.state = PowerOpState::HAULING;
+                op.hits = 0;
+                return true;
+            }
+
+            if count_remaining_power(&room) <= 0 {
+                info!("power op finished: {}", op.room);
+                return false;
+            }
+        }
+    }
+
+    return true;
+}
+
+fn run_power_ops() {
+    let mut op_map = load_power_ops();
+
+    if game::time() % POWER_PLAN_INTERVAL == 0 {
+        plan_power_ops(&mut op_map);
+    }
+
+    let mut finished = Vec::new();
+    for (bank_id, op) in op_map.iter_mut() {
+        if update_power_op(bank_id.as_str(), op) == false {
+            finished.push(bank_id.clone());
+        }
+
+        // 壊し終わったらsquadは帰す.
+        if op.state == PowerOpState::HAULING || finished.contains(bank_id) {
+            for squad_id in op.squads.iter() {
+                retreat_squad(squad_id.as_str());
+            }
+        }
+    }
+
+    for bank_id in finished {
+        op_map.remove(&bank_id);
+    }
+
+    save_power_ops(&op_map);
+}
+
+pub fn get_room_power_spawn(room: &Room) -> Option<StructurePowerSpawn> {
+    return room
+        .find(MY_STRUCTURES)
+        .into_iter()
+        .filter_map(|structure| match structure {
+            Structure::PowerSpawn(power_spawn) => Some(power_spawn),
+            _ => None,
+        })
+        .next();
+}
+
+// lab haulerの仕事. storage/terminalからpower spawnにpowerを運ぶ.
+pub fn get_power_spawn_haul_task(room: &Room) -> Option<LabHaulTask> {
+    let power_spawn = get_room_power_spawn(room)?;
+
+    let power = power_spawn.store_of(ResourceType::Power);
+    if power >= POWER_SPAWN_POWER_FILL_AMOUNT {
+        return None;
+    }
+
+    return make_fill_task(
+        room,
+        ResourceType::Power,
+        POWER_SPAWN_POWER_FILL_AMOUNT - power,
+    );
+}
+
+pub fn get_power_deliver_target(room: &Room, resource: ResourceType) -> Option<Structure> {
+    if resource != ResourceType::Power {
+        return None;
+    }
+
+    let power_spawn = get_room_power_spawn(room)?;
+    if power_spawn.store_free_capacity(Some(resource)) <= 0 {
+        return None;
+    }
+
+    return Some(Structure::PowerSpawn(power_spawn));
+}
+
+fn run_power_spawn(room: &Room) {
+    let power_spawn = match get_room_power_spawn(room) {
+        Some(power_spawn) => power_spawn,
+        None => return,
+    };
+
+    // energyに余裕がある時だけ.
+    let storage_energy = room
+        .storage()
+        .map(|s| s.store_of(ResourceType::Energy))
+        .unwrap_or(0);
+    if storage_energy < POWER_SPAWN_MIN_STORAGE_ENERGY {
+        return;
+    }
+
+    if power_spawn.store_of(ResourceType::Power) <= 0
+        || power_spawn.store_of(ResourceType::Energy) < POWER_SPAWN_ENERGY_RATIO
+    {
+        return;
+    }
+
+    let r = power_spawn.process_power();
+    if r != ReturnCode::Ok {
+        debug!("couldn't process power: {:?}", r);
+    }
+}
+
+pub fn run_power() {
+    run_power_ops();
+
+    for room in game::rooms::values() {
+        let controller = match room.controller() {
+            Some(controller) => controller,
+            None => continue,
+        };
+
+        if controller.my() == false {
+            continue;
+        }
+
+        run_power_spawn(&room);
+    }
+}
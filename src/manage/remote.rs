@@ -0,0 +1,372 @@
+use crate::constants::*;
+use crate::creeps::miner::*;
+use crate::defence::threat::*;
+use crate::manage::intel::*;
+use crate::route::*;
+use log::*;
+use screeps::constants::find::*;
+use screeps::{
+    game, look, prelude::*, Position, ReturnCode, Room, RoomName, StructureController,
+    StructureType,
+};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+use lazy_static::lazy_static;
+use std::sync::RwLock;
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct RemoteRoom {
+    pub home: String,
+    pub sources: Vec<String>,
+    pub hostile_damage: u32,
+    pub hostile_reserved: bool,
+    pub threat_ticks: u32,
+    pub abandoned_until: u32,
+}
+
+type RemoteRoomMap = HashMap<String, RemoteRoom>;
+
+// memoryのjsonはtickごとに1回だけ読む.
+#[derive(Default)]
+struct RemoteRoomData {
+    remote_map: RemoteRoomMap,
+    updated: Option<u32>,
+}
+
+lazy_static! {
+    static ref REMOTE_ROOM_CACHE: RwLock<RemoteRoomData> = RwLock::new(RemoteRoomData::default());
+}
+
+fn refresh_remote_rooms() {
+    let mut remote_cache = REMOTE_ROOM_CACHE.write().unwrap();
+
+    if remote_cache.updated == Some(game::time()) {
+        return;
+    }
+
+    let json_str = screeps::memory::root()
+        .string("remote_rooms")
+        .unwrap_or(None)
+        .unwrap_or(String::from("{}"));
+
+    remote_cache.remote_map = serde_json::from_str(json_str.as_str()).unwrap_or(HashMap::new());
+    remote_cache.updated = Some(game::time());
+}
+
+pub fn load_remote_rooms() -> RemoteRoomMap {
+    refresh_remote_rooms();
+
+    return REMOTE_ROOM_CACHE.read().unwrap().remote_map.clone();
+}
+
+fn get_remote_room(room_name: &str) -> Option<RemoteRoom> {
+    refresh_remote_rooms();
+
+    return REMOTE_ROOM_CACHE
+        .read()
+        .unwrap()
+        .remote_map
+        .get(room_name)
+        .cloned();
+}
+
+fn save_remote_rooms(remote_map: &RemoteRoomMap) {
+    let json_str = serde_json::to_string(remote_map).unwrap();
+    screeps::memory::root().set("remote_rooms", json_str);
+
+    let mut remote_cache = REMOTE_ROOM_CACHE.write().unwrap();
+    remote_cache.remote_map = remote_map.clone();
+    remote_cache.updated = Some(game::time());
+}
+
+pub fn is_remote_active(remote: &RemoteRoom) -> bool {
+    return remote.abandoned_until <= game::time();
+}
+
+// 敵がいる間はremoteのcreepを退避させる.
+pub fn check_remote_threat(room_name: &str) -> bool {
+    match get_remote_room(room_name) {
+        Some(remote) => {
+            return remote.hostile_damage > 0;
+        }
+        None => {}
+    }
+
+    return false;
+}
+
+// 放棄中の部屋からも引き上げる.
+pub fn check_remote_abandoned(room_name: &str) -> bool {
+    match get_remote_room(room_name) {
+        Some(remote) => {
+            return is_remote_active(&remote) == false;
+        }
+        None => {}
+    }
+
+    return false;
+}
+
+pub fn count_creeps_for_room(role: &str, room_name: &str) -> u32 {
+    let mut count = 0;
+
+    for creep in screeps::game::creeps::values() {
+        let creep_role = creep.memory().string("role").unwrap_or(None);
+        let target_room = creep.memory().string("target_room").unwrap_or(None);
+
+        if creep_role.as_ref().map(|r| r.as_str()) == Some(role)
+            && target_room.as_ref().map(|s| s.as_str()) == Some(room_name)
+        {
+            count += 1;
+        }
+    }
+
+    return count;
+}
+
+pub fn get_my_username() -> Option<String> {
+    for spawn in screeps::game::spawns::values() {
+        return spawn.owner_name();
+    }
+
+    return None;
+}
+
+// reservationの残りtick. 他人に予約されていればNone.
+pub fn get_reservation_ticks(controller: &StructureController) -> Option<u32> {
+    match controller.reservation() {
+        Some(reservation) => {
+            if Some(reservation.username) != get_my_username() {
+                return None;
+            }
+            return Some(reservation.ticks_to_end);
+        }
+        None => {}
+    }
+
+    return Some(0);
+}
+
+// 他人の部屋やsource keeperの部屋は使わない.
+fn check_remote_room(room: &Room) -> bool {
+    let controller = match room.controller() {
+        Some(controller) => controller,
+        None => return false,
+    };
+
+    if controller.owner_name().is_some() {
+        return false;
+    }
+
+    if room.find(SOURCES).len() <= 0 {
+        return false;
+    }
+
+    return true;
+}
+
+fn check_remote_intel(room_intel: &RoomIntel) -> bool {
+    let controller = match &room_intel.controller {
+        Some(controller) => controller,
+        None => return false,
+    };
+
+    if controller.owner.is_some() || check_source_keeper_room(room_intel) {
+        return false;
+    }
+
+    return room_intel.sources.len() > 0;
+}
+
+fn update_remote_candidates(remote_map: &mut RemoteRoomMap) {
+    for room in screeps::game::rooms::values() {
+        let controller = match room.controller() {
+            Some(controller) => controller,
+            None => continue,
+        };
+
+        if controller.my() == false || controller.level() < REMOTE_MIN_RCL {
+            continue;
+        }
+
+        if room.storage().is_none() {
+            continue;
+        }
+
+        let home = room.name().to_string();
+        let mut num_remote = remote_map.values().filter(|r| r.home == home).count();
+
+        for exit_room_name in game::map::describe_exits(room.name()).values() {
+            if num_remote >= REMOTE_MAX_ROOMS_PER_HOME {
+                break;
+            }
+
+            let exit_room_key = exit_room_name.to_string();
+            if remote_map.contains_key(&exit_room_key) {
+                continue;
+            }
+
+            // intelの無い部屋はreserverが行ってから調べる.
+            let mut sources = Vec::new();
+            if let Some(room_intel) = get_room_intel(exit_room_key.as_str()) {
+                if check_remote_intel(&room_intel) == false {
+                    continue;
+                }
+
+                for source in room_intel.sources.iter() {
+                    sources.push(source.id.clone());
+                }
+            }
+
+            info!("new remote room: {} -> {}", home, exit_room_key);
+            remote_map.insert(
+                exit_room_key,
+                RemoteRoom {
+                    home: home.clone(),
+                    sources: sources,
+                    hostile_damage: 0,
+                    hostile_reserved: false,
+                    threat_ticks: 0,
+                    abandoned_until: 0,
+                },
+            );
+            num_remote += 1;
+        }
+    }
+}
+
+fn update_remote_state(room: &Room, remote: &mut RemoteRoom) {
+    if check_remote_room(room) == false {
+        warn!("remote room is not available: {:?}", room.name());
+        remote.abandoned_until = game::time() + REMOTE_ABANDON_TICKS;
+        return;
+    }
+
+    if remote.sources.len() <= 0 {
+        for source in room.find(SOURCES) {
+            remote.sources.push(source.id().to_string());
+        }
+    }
+
+    let mut hostile_damage = 0;
+    for hostile in get_dangerous_hostiles(room) {
+        hostile_damage += hostile.damage + hostile.heal;
+    }
+
+    remote.hostile_damage = hostile_damage;
+
+    // 他人(invader含む)に予約されている間は採掘できない.
+    remote.hostile_reserved = match room.controller() {
+        Some(controller) => get_reservation_ticks(&controller).is_none(),
+        None => false,
+    };
+
+    if hostile_damage > 0 || remote.hostile_reserved == true {
+        remote.threat_ticks += 1;
+    } else if remote.threat_ticks > 0 {
+        remote.threat_ticks -= 1;
+    }
+
+    if hostile_damage > REMOTE_MAX_DEFEND_DAMAGE
+        || remote.threat_ticks >= REMOTE_ABANDON_THREAT_TICKS
+    {
+        warn!(
+            "abandon remote room: {:?}, damage:{}, threat_ticks:{}",
+            room.name(),
+            hostile_damage,
+            remote.threat_ticks
+        );
+        remote.abandoned_until = game::time() + REMOTE_ABANDON_TICKS;
+        remote.hostile_damage = 0;
+        remote.hostile_reserved = false;
+        remote.threat_ticks = 0;
+    }
+}
+
+fn check_road_or_site(room: &Room, pos: &Position) -> bool {
+    let has_road = room
+        .look_for_at(look::STRUCTURES, pos)
+        .iter()
+        .any(|s| s.structure_type() == StructureType::Road);
+
+    return has_road || room.look_for_at(look::CONSTRUCTION_SITES, pos).len() > 0;
+}
+
+// home storageから各sourceまでroadを引く.
+fn place_remote_roads(remote: &RemoteRoom) {
+    let home_name: RoomName = match remote.home.parse() {
+        Ok(home_name) => home_name,
+        Err(_err) => return,
+    };
+
+    let storage = match game::rooms::get(home_name).and_then(|room| room.storage()) {
+        Some(storage) => storage,
+        None => return,
+    };
+
+    let mut num_sites = 0;
+
+    for source_id in remote.sources.iter() {
+        let source = match get_source(source_id.as_str()) {
+            Some(source) => source,
+            None => continue,
+        };
+
+        let res = find_route_path_between(&storage.pos(), &source.pos(), 1);
+
+        for pos in res.load_local_path() {
+            if num_sites >= REMOTE_ROAD_MAX_SITES {
+                return;
+            }
+
+            let room = match game::rooms::get(pos.room_name()) {
+                Some(room) => room,
+                None => continue,
+            };
+
+            if check_road_or_site(&room, &pos) == true {
+                continue;
+            }
+
+            let r = room.create_construction_site(&pos, StructureType::Road);
+            if r == ReturnCode::Ok {
+                num_sites += 1;
+            } else {
+                debug!("couldn't place remote road: {:?}, {:?}", pos, r);
+            }
+        }
+    }
+}
+
+pub fn run_remote() {
+    let mut remote_map = load_remote_rooms();
+
+    update_remote_candidates(&mut remote_map);
+
+    for (room_name, remote) in remote_map.iter_mut() {
+        if is_remote_active(remote) == false {
+            debug!("remote room is abandoned: {}", room_name);
+            continue;
+        }
+
+        let room = match room_name
+            .parse::<RoomName>()
+            .ok()
+            .and_then(|name| game::rooms::get(name))
+        {
+            Some(room) => room,
+            None => continue,
+        };
+
+        update_remote_state(&room, remote);
+
+        if is_remote_active(remote) == true && game::time() % REMOTE_ROAD_PLAN_INTERVAL == 0 {
+            place_remote_roads(remote);
+        }
+
+        debug!("remote room: {}, {:?}", room_name, remote);
+    }
+
+    save_remote_rooms(&remote_map);
+}
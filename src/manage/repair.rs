@@ -0,0 +1,199 @@
+use crate::constants::*;
+use crate::plan::rampart::*;
+use crate::util::*;
+use log::*;
+use screeps::{game, prelude::*, ResourceType, Room, Structure, StructureType};
+use std::collections::HashMap;
+
+use lazy_static::lazy_static;
+use std::sync::RwLock;
+
+#[derive(PartialEq, Eq, Clone, Copy, Debug, Hash)]
+pub enum RepairCategory {
+    INFRASTRUCTURE,
+    FORTIFICATION,
+}
+
+// 部屋ごとの修理方針. tickごとに作り直す.
+#[derive(Clone, Debug)]
+pub struct RepairSchedule {
+    pub room_name: String,
+    pub rcl: u32,
+    pub storage_energy: u32,
+    pub fortification_hp: u32,
+    pub fortification_budget: u32,
+}
+
+#[derive(Clone, Copy, Debug, Default)]
+struct RepairSpent {
+    started: u32,
+    energy: u32,
+}
+
+lazy_static! {
+    static ref REPAIR_SPENT_CACHE: RwLock<HashMap<(String, RepairCategory), RepairSpent>> =
+        RwLock::new(HashMap::new());
+}
+
+pub fn get_repair_category(structure_type: StructureType) -> RepairCategory {
+    match structure_type {
+        StructureType::Wall | StructureType::Rampart => RepairCategory::FORTIFICATION,
+        _ => RepairCategory::INFRASTRUCTURE,
+    }
+}
+
+// RCLごとの壁/rampartの目標HP.
+fn get_fortification_base_hp(rcl: u32) -> u32 {
+    let hp: [u32; 9] = [
+        0, 0, 10_000, 30_000, 100_000, 300_000, 1_000_000, 3_000_000, 10_000_000,
+    ];
+    return hp[std::cmp::min(rcl, 8) as usize];
+}
+
+// storageの余裕に応じて目標HPと予算を増減する.
+fn get_storage_rate_percent(storage_energy: u32) -> u32 {
+    if storage_energy < REPAIR_STORAGE_LOW_ENERGY {
+        return 25;
+    }
+    if storage_energy < REPAIR_STORAGE_NORMAL_ENERGY {
+        return 50;
+    }
+    if storage_energy < REPAIR_STORAGE_HIGH_ENERGY {
+        return 100;
+    }
+    return 200;
+}
+
+pub fn make_repair_schedule(room: &Room) -> RepairSchedule {
+    let rcl = room.controller().map(|c| c.level()).unwrap_or(0);
+
+    // storageがなければ最低限だけ.
+    let storage_energy = room
+        .storage()
+        .map(|s| s.store_of(ResourceType::Energy))
+        .unwrap_or(0);
+    let rate = get_storage_rate_percent(storage_energy);
+
+    return RepairSchedule {
+        room_name: room.name().to_string(),
+        rcl: rcl,
+        storage_energy: storage_energy,
+        fortification_hp: get_fortification_base_hp(rcl) / 100 * rate,
+        fortification_budget: REPAIR_FORTIFICATION_BUDGET / 100 * rate,
+    };
+}
+
+pub fn get_target_hp(structure: &Structure, schedule: &RepairSchedule) -> u32 {
+    let hits_max = match structure.as_attackable() {
+        Some(attackable) => attackable.hits_max(),
+        None => return 0,
+    };
+
+    match get_repair_category(structure.structure_type()) {
+        RepairCategory::INFRASTRUCTURE => {
+            return hits_max;
+        }
+
+        RepairCategory::FORTIFICATION => {
+            let mut target = schedule.fortification_hp;
+            if check_critical_rampart(structure) == true {
+                target = target.saturating_mul(REPAIR_CRITICAL_RAMPART_RATE);
+            }
+            return std::cmp::min(target, hits_max);
+        }
+    }
+}
+
+// 道やcontainerは少し減ってから直す. 壁/rampartは目標HPまで.
+pub fn check_repair_needed(structure: &Structure, schedule: &RepairSchedule) -> bool {
+    if check_repairable(structure) == false {
+        return false;
+    }
+
+    let hits = get_hp(structure).unwrap_or(0);
+    let target = get_target_hp(structure, schedule);
+
+    match get_repair_category(structure.structure_type()) {
+        RepairCategory::INFRASTRUCTURE => {
+            return (hits as u64) * 100
+                < (target as u64) * REPAIR_INFRASTRUCTURE_RATE_PERCENT as u64;
+        }
+
+        RepairCategory::FORTIFICATION => {
+            return hits < target;
+        }
+    }
+}
+
+fn get_repair_budget(schedule: &RepairSchedule, category: RepairCategory) -> u32 {
+    match category {
+        RepairCategory::INFRASTRUCTURE => REPAIR_INFRASTRUCTURE_BUDGET,
+        RepairCategory::FORTIFICATION => schedule.fortification_budget,
+    }
+}
+
+fn get_repair_spent(room_name: &str, category: RepairCategory) -> u32 {
+    let spent_cache = REPAIR_SPENT_CACHE.read().unwrap();
+
+    match spent_cache.get(&(room_name.to_string(), category)) {
+        Some(spent) => {
+            if game::time() - spent.started < REPAIR_BUDGET_INTERVAL {
+                return spent.energy;
+            }
+        }
+        None => {}
+    }
+
+    return 0;
+}
+
+pub fn check_repair_budget(schedule: &RepairSchedule, category: RepairCategory) -> bool {
+    return get_repair_spent(schedule.room_name.as_str(), category)
+        < get_repair_budget(schedule, category);
+}
+
+// 修理に使ったenergyを記録する. 一定期間ごとにリセット.
+pub fn record_repair_spent(room_name: &str, structure_type: StructureType, energy: u32) {
+    let category = get_repair_category(structure_type);
+    let mut spent_cache = REPAIR_SPENT_CACHE.write().unwrap();
+
+    let spent = spent_cache
+        .entry((room_name.to_string(), category))
+        .or_insert(RepairSpent::default());
+
+    if game::time() - spent.started >= REPAIR_BUDGET_INTERVAL {
+        spent.started = game::time();
+        spent.energy = 0;
+    }
+    spent.energy += energy;
+
+    debug!(
+        "repair spent {}: {:?}, {}",
+        room_name, category, spent.energy
+    );
+}
+
+// 予算の残っている種類の中で、目標HPに対して一番減っているもの.
+pub fn find_repair_target(
+    structures: &Vec<Structure>,
+    schedule: &RepairSchedule,
+) -> Option<Structure> {
+    let is_infrastructure_ok = check_repair_budget(schedule, RepairCategory::INFRASTRUCTURE);
+    let is_fortification_ok = check_repair_budget(schedule, RepairCategory::FORTIFICATION);
+
+    return structures
+        .iter()
+        .filter(
+            |structure| match get_repair_category(structure.structure_type()) {
+                RepairCategory::INFRASTRUCTURE => is_infrastructure_ok,
+                RepairCategory::FORTIFICATION => is_fortification_ok,
+            },
+        )
+        .filter(|structure| check_repair_needed(structure, schedule))
+        .min_by_key(|structure| {
+            let hits = get_hp(structure).unwrap_or(0) as u64;
+            let target = std::cmp::max(get_target_hp(structure, schedule), 1) as u64;
+            hits * 1000 / target
+        })
+        .cloned();
+}
@@ -0,0 +1,198 @@
+use crate::constants::*;
+use crate::util::*;
+use log::*;
+use screeps::constants::*;
+use screeps::{prelude::*, Creep, Part, Position, Source};
+use std::collections::HashMap;
+
+use lazy_static::lazy_static;
+use std::sync::RwLock;
+
+struct SourceSlots {
+    slots: Vec<Position>,
+    updated: u32,
+}
+
+type SourceSlotData = HashMap<String, SourceSlots>;
+
+// source_idごとの(creep数, WORK数). tickごとに作り直す.
+#[derive(Default)]
+struct SourceClaims {
+    claims: HashMap<String, (u32, u32)>,
+    updated: u32,
+}
+
+lazy_static! {
+    static ref SOURCE_SLOT_CACHE: RwLock<SourceSlotData> = RwLock::new(HashMap::new());
+    static ref SOURCE_CLAIM_CACHE: RwLock<SourceClaims> = RwLock::new(SourceClaims::default());
+}
+
+// sourceの周りの歩けるマス.
+fn calc_source_slots(source: &Source) -> Vec<Position> {
+    let mut slots = Vec::<Position>::new();
+    let room_name = source.pos().room_name();
+
+    for x_pos_offset in 0..=2 {
+        for y_pos_offset in 0..=2 {
+            if x_pos_offset == 1 && y_pos_offset == 1 {
+                continue;
+            }
+
+            let x = source.pos().x() as i32 + x_pos_offset - 1;
+            let y = source.pos().y() as i32 + y_pos_offset - 1;
+
+            if x <= 0 || y <= 0 || x >= 49 || y >= 49 {
+                continue;
+            }
+
+            let position = Position::new(x as u32, y as u32, room_name);
+            if check_walkable_tile(&position, true) {
+                slots.push(position);
+            }
+        }
+    }
+
+    return slots;
+}
+
+pub fn get_source_slots(source: &Source) -> Vec<Position> {
+    let source_id = source.id().to_string();
+
+    {
+        let source_slot_cache = SOURCE_SLOT_CACHE.read().unwrap();
+
+        if let Some(value) = source_slot_cache.get(&source_id) {
+            if screeps::game::time() - value.updated < SOURCE_SLOT_REFRESH_TICKS {
+                // use cached value.
+                return value.slots.clone();
+            }
+        }
+    }
+
+    let slots = calc_source_slots(source);
+    debug!("source slots:{:?}, {:?}", source.pos(), slots.len());
+
+    {
+        let mut source_slot_cache = SOURCE_SLOT_CACHE.write().unwrap();
+        source_slot_cache.insert(
+            source_id,
+            SourceSlots {
+                slots: slots.clone(),
+                updated: screeps::game::time(),
+            },
+        );
+    }
+
+    return slots;
+}
+
+// 1つのsourceを枯らすのに必要なWORKの数(3000/300/2 = 5).
+pub fn calc_saturation_work_parts(source: &Source) -> u32 {
+    let energy_per_tick = source.energy_capacity() as f64 / ENERGY_REGEN_TIME as f64;
+
+    return (energy_per_tick / HARVEST_POWER as f64).ceil() as u32;
+}
+
+// minerはsource_idで、それ以外のharvesterはclaimed_sourceで数える.
+fn calc_source_claims() -> HashMap<String, (u32, u32)> {
+    let mut claims = HashMap::<String, (u32, u32)>::new();
+
+    for creep in screeps::game::creeps::values() {
+        let role = creep.memory().string("role").unwrap_or(None);
+        let claimed_source = creep.memory().string("claimed_source").unwrap_or(None);
+        let miner_source = creep.memory().string("source_id").unwrap_or(None);
+
+        let source_id = if role.as_ref().map(|r| r.as_str()) == Some("miner") {
+            miner_source.or(claimed_source)
+        } else {
+            claimed_source
+        };
+
+        if let Some(source_id) = source_id {
+            let claim = claims.entry(source_id).or_insert((0, 0));
+            claim.0 += 1;
+            claim.1 += creep.get_active_bodyparts(Part::Work);
+        }
+    }
+
+    return claims;
+}
+
+fn refresh_source_claims() {
+    let mut source_claim_cache = SOURCE_CLAIM_CACHE.write().unwrap();
+
+    if source_claim_cache.updated == screeps::game::time() {
+        return;
+    }
+
+    source_claim_cache.claims = calc_source_claims();
+    source_claim_cache.updated = screeps::game::time();
+}
+
+// このtickの中でclaim/releaseした分を反映する.
+fn add_source_claim(source_id: String, creep: &Creep, is_add: bool) {
+    let mut source_claim_cache = SOURCE_CLAIM_CACHE.write().unwrap();
+    let claim = source_claim_cache.claims.entry(source_id).or_insert((0, 0));
+    let num_work = creep.get_active_bodyparts(Part::Work);
+
+    if is_add == true {
+        claim.0 += 1;
+        claim.1 += num_work;
+    } else {
+        claim.0 = claim.0.saturating_sub(1);
+        claim.1 = claim.1.saturating_sub(num_work);
+    }
+}
+
+// sourceを使っているcreepの数とWORKの数.
+pub fn count_source_claims(source_id: &str) -> (u32, u32) {
+    refresh_source_claims();
+
+    let source_claim_cache = SOURCE_CLAIM_CACHE.read().unwrap();
+    return *source_claim_cache.claims.get(source_id).unwrap_or(&(0, 0));
+}
+
+// まだ空いているslotの数. WORKが足りていれば0.
+pub fn calc_source_free_slots(source: &Source) -> u32 {
+    let slots = get_source_slots(source).len() as u32;
+    let (num_creeps, num_work) = count_source_claims(source.id().to_string().as_str());
+
+    if num_work >= calc_saturation_work_parts(source) {
+        return 0;
+    }
+
+    if num_creeps >= slots {
+        return 0;
+    }
+
+    return slots - num_creeps;
+}
+
+pub fn check_source_available(creep: &Creep, source: &Source) -> bool {
+    let source_id = source.id().to_string();
+
+    // すでに確保済みなら使ってよい.
+    if creep.memory().string("claimed_source").unwrap_or(None) == Some(source_id) {
+        return true;
+    }
+
+    return calc_source_free_slots(source) > 0;
+}
+
+pub fn claim_source(creep: &Creep, source: &Source) {
+    debug!("claim source {}: {:?}", creep.name(), source.pos());
+    release_source(creep);
+
+    let source_id = source.id().to_string();
+    creep.memory().set("claimed_source", source_id.as_str());
+    add_source_claim(source_id, creep, true);
+}
+
+pub fn release_source(creep: &Creep) {
+    refresh_source_claims();
+
+    if let Some(source_id) = creep.memory().string("claimed_source").unwrap_or(None) {
+        creep.memory().del("claimed_source");
+        add_source_claim(source_id, creep, false);
+    }
+}
@@ -0,0 +1,776 @@
+use crate::constants::*;
+use log::*;
+use screeps::constants::find::*;
+use screeps::{game, prelude::*, Position, ReturnCode, Room, StructureType, Terrain};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet, VecDeque};
+
+const ROOM_SIZE: i32 = 50;
+
+#[derive(PartialEq, Eq, Clone, Copy, Debug, Hash, Serialize, Deserialize)]
+pub enum PlanKind {
+    SPAWN,
+    EXTENSION,
+    TOWER,
+    STORAGE,
+    LINK,
+    TERMINAL,
+    EXTRACTOR,
+    LAB,
+    FACTORY,
+    POWERSPAWN,
+    NUKER,
+    OBSERVER,
+    ROAD,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct PlannedStructure {
+    pub kind: PlanKind,
+    pub x: u32,
+    pub y: u32,
+}
+
+// structuresは建てる優先順に並んでいる.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct BaseLayout {
+    pub anchor_x: u32,
+    pub anchor_y: u32,
+    pub structures: Vec<PlannedStructure>,
+    pub created: u32,
+}
+
+type BaseLayoutMap = HashMap<String, BaseLayout>;
+
+// core stampはanchorを中心にした5x5. 真ん中はmanagerの立ち位置なので空けておく.
+const CORE_STAMP: [(i32, i32, PlanKind); 8] = [
+    (-1, -1, PlanKind::SPAWN),
+    (0, -1, PlanKind::TERMINAL),
+    (1, -1, PlanKind::FACTORY),
+    (-1, 0, PlanKind::STORAGE),
+    (1, 0, PlanKind::LINK),
+    (-1, 1, PlanKind::POWERSPAWN),
+    (0, 1, PlanKind::SPAWN),
+    (1, 1, PlanKind::SPAWN),
+];
+
+// lab stampは4x4で対角線が道. 先頭の2つがinput lab.
+const LAB_STAMP: [(i32, i32); 10] = [
+    (2, 1),
+    (1, 2),
+    (1, 0),
+    (2, 0),
+    (0, 1),
+    (3, 1),
+    (0, 2),
+    (3, 2),
+    (1, 3),
+    (2, 3),
+];
+
+pub fn to_structure_type(kind: PlanKind) -> StructureType {
+    match kind {
+        PlanKind::SPAWN => StructureType::Spawn,
+        PlanKind::EXTENSION => StructureType::Extension,
+        PlanKind::TOWER => StructureType::Tower,
+        PlanKind::STORAGE => StructureType::Storage,
+        PlanKind::LINK => StructureType::Link,
+        PlanKind::TERMINAL => StructureType::Terminal,
+        PlanKind::EXTRACTOR => StructureType::Extractor,
+        PlanKind::LAB => StructureType::Lab,
+        PlanKind::FACTORY => StructureType::Factory,
+        PlanKind::POWERSPAWN => StructureType::PowerSpawn,
+        PlanKind::NUKER => StructureType::Nuker,
+        PlanKind::OBSERVER => StructureType::Observer,
+        PlanKind::ROAD => StructureType::Road,
+    }
+}
+
+// RCLごとに建てられる数(CONTROLLER_STRUCTURES).
+pub fn get_structure_limit(kind: PlanKind, rcl: u32) -> u32 {
+    let limits: [u32; 9] = match kind {
+        PlanKind::SPAWN => [0, 1, 1, 1, 1, 1, 1, 2, 3],
+        PlanKind::EXTENSION => [0, 0, 5, 10, 20, 30, 40, 50, 60],
+        PlanKind::TOWER => [0, 0, 0, 1, 1, 2, 2, 3, 6],
+        PlanKind::STORAGE => [0, 0, 0, 0, 1, 1, 1, 1, 1],
+        PlanKind::LINK => [0, 0, 0, 0, 0, 2, 3, 4, 6],
+        PlanKind::TERMINAL => [0, 0, 0, 0, 0, 0, 1, 1, 1],
+        PlanKind::EXTRACTOR => [0, 0, 0, 0, 0, 0, 1, 1, 1],
+        PlanKind::LAB => [0, 0, 0, 0, 0, 0, 3, 6, 10],
+        PlanKind::FACTORY => [0, 0, 0, 0, 0, 0, 0, 1, 1],
+        PlanKind::POWERSPAWN => [0, 0, 0, 0, 0, 0, 0, 0, 1],
+        PlanKind::NUKER => [0, 0, 0, 0, 0, 0, 0, 0, 1],
+        PlanKind::OBSERVER => [0, 0, 0, 0, 0, 0, 0, 0, 1],
+        PlanKind::ROAD => [2500; 9],
+    };
+
+    return limits[std::cmp::min(rcl, 8) as usize];
+}
+
+// 建てる順番. 小さいほど先.
+fn get_plan_priority(kind: PlanKind) -> u32 {
+    match kind {
+        PlanKind::SPAWN => 0,
+        PlanKind::TOWER => 1,
+        PlanKind::EXTENSION => 2,
+        PlanKind::STORAGE => 3,
+        PlanKind::LINK => 4,
+        PlanKind::TERMINAL => 5,
+        PlanKind::EXTRACTOR => 6,
+        PlanKind::LAB => 7,
+        PlanKind::FACTORY => 8,
+        PlanKind::POWERSPAWN => 9,
+        PlanKind::NUKER => 10,
+        PlanKind::OBSERVER => 11,
+        PlanKind::ROAD => 12,
+    }
+}
+
+pub fn load_base_layouts() -> BaseLayoutMap {
+    let json_str = screeps::memory::root()
+        .string("base_layouts")
+        .unwrap_or(None)
+        .unwrap_or(String::from("{}"));
+
+    return serde_json::from_str(json_str.as_str()).unwrap_or(HashMap::new());
+}
+
+fn save_base_layouts(layout_map: &BaseLayoutMap) {
+    let json_str = serde_json::to_string(layout_map).unwrap();
+    screeps::memory::root().set("base_layouts", json_str);
+}
+
+pub fn get_base_layout(room_name: &str) -> Option<BaseLayout> {
+    return load_base_layouts().remove(room_name);
+}
+
+fn to_index(x: i32, y: i32) -> usize {
+    return (y * ROOM_SIZE + x) as usize;
+}
+
+fn check_in_room(x: i32, y: i32) -> bool {
+    return x >= 0 && y >= 0 && x < ROOM_SIZE && y < ROOM_SIZE;
+}
+
+// 出口の近くには建てられない.
+fn check_buildable(walls: &[bool], x: i32, y: i32) -> bool {
+    let min = LAYOUT_EDGE_MARGIN as i32;
+    let max = ROOM_SIZE - 1 - LAYOUT_EDGE_MARGIN as i32;
+
+    if x < min || y < min || x > max || y > max {
+        return false;
+    }
+
+    return walls[to_index(x, y)] == false;
+}
+
+fn get_range(a: (i32, i32), b: (i32, i32)) -> i32 {
+    return std::cmp::max((a.0 - b.0).abs(), (a.1 - b.1).abs());
+}
+
+// 壁(と部屋の端)からの距離. 8方向なのでchebyshev距離になる.
+pub fn calc_distance_transform(walls: &[bool]) -> Vec<u32> {
+    let mut dist = vec![0; (ROOM_SIZE * ROOM_SIZE) as usize];
+
+    for y in 0..ROOM_SIZE {
+        for x in 0..ROOM_SIZE {
+            if walls[to_index(x, y)] == true {
+                continue;
+            }
+
+            let mut d = LAYOUT_MAX_DISTANCE;
+            for (dx, dy) in [(-1, -1), (0, -1), (1, -1), (-1, 0)].iter() {
+                let nx = x + dx;
+                let ny = y + dy;
+                let nd = if check_in_room(nx, ny) {
+                    dist[to_index(nx, ny)]
+                } else {
+                    0
+                };
+                d = std::cmp::min(d, nd + 1);
+            }
+            dist[to_index(x, y)] = d;
+        }
+    }
+
+    for y in (0..ROOM_SIZE).rev() {
+        for x in (0..ROOM_SIZE).rev() {
+            if walls[to_index(x, y)] == true {
+                continue;
+            }
+
+            let mut d = dist[to_index(x, y)];
+            for (dx, dy) in [(1, 1), (0, 1), (-1, 1), (1, 0)].iter() {
+                let nx = x + dx;
+                let ny = y + dy;
+                let nd = if check_in_room(nx, ny) {
+                    dist[to_index(nx, ny)]
+                } else {
+                    0
+                };
+                d = std::cmp::min(d, nd + 1);
+            }
+            dist[to_index(x, y)] = d;
+        }
+    }
+
+    return dist;
+}
+
+fn check_core_fits(walls: &[bool], anchor: (i32, i32)) -> bool {
+    return (-2..=2)
+        .all(|dx| (-2..=2).all(|dy| check_buildable(walls, anchor.0 + dx, anchor.1 + dy)));
+}
+
+// 既存のspawnがcore stampの先頭のspawnに重なるように置けるならそれを使う.
+// 置けなければspawnに一番近い、周りが十分開けている場所にする.
+fn find_core_anchor(walls: &[bool], dist: &Vec<u32>, spawn: (i32, i32)) -> Option<(i32, i32)> {
+    let (sx, sy, _kind) = CORE_STAMP[0];
+    let anchor = (spawn.0 - sx, spawn.1 - sy);
+
+    if check_core_fits(walls, anchor) == true {
+        return Some(anchor);
+    }
+
+    let mut best: Option<(i32, i32)> = None;
+    for y in 0..ROOM_SIZE {
+        for x in 0..ROOM_SIZE {
+            if dist[to_index(x, y)] < LAYOUT_CORE_DISTANCE
+                || check_core_fits(walls, (x, y)) == false
+            {
+                continue;
+            }
+
+            let is_better = match best {
+                Some(b) => get_range((x, y), spawn) < get_range(b, spawn),
+                None => true,
+            };
+            if is_better == true {
+                best = Some((x, y));
+            }
+        }
+    }
+
+    return best;
+}
+
+fn find_lab_anchor(
+    walls: &[bool],
+    reserved: &HashSet<(i32, i32)>,
+    anchor: (i32, i32),
+) -> Option<(i32, i32)> {
+    let mut best: Option<(i32, i32)> = None;
+
+    for y in 0..ROOM_SIZE {
+        for x in 0..ROOM_SIZE {
+            let fits = (0..4).all(|dx| {
+                (0..4).all(|dy| {
+                    check_buildable(walls, x + dx, y + dy)
+                        && reserved.contains(&(x + dx, y + dy)) == false
+                })
+            });
+            if fits == false {
+                continue;
+            }
+
+            let center = (x + 1, y + 1);
+            let is_better = match best {
+                Some(b) => get_range(center, anchor) < get_range((b.0 + 1, b.1 + 1), anchor),
+                None => true,
+            };
+            if is_better == true {
+                best = Some((x, y));
+            }
+        }
+    }
+
+    return best;
+}
+
+// targetから指定の距離以内で、anchorに一番近い空き地.
+fn find_link_pos(
+    walls: &[bool],
+    reserved: &HashSet<(i32, i32)>,
+    target: (i32, i32),
+    range: i32,
+    anchor: (i32, i32),
+) -> Option<(i32, i32)> {
+    let mut best: Option<(i32, i32)> = None;
+
+    for dx in -range..=range {
+        for dy in -range..=range {
+            let pos = (target.0 + dx, target.1 + dy);
+            if check_buildable(walls, pos.0, pos.1) == false || reserved.contains(&pos) == true {
+                continue;
+            }
+
+            // sourceやcontrollerのすぐ横は作業場所なので空けておく.
+            if get_range(pos, target) <= 1 {
+                continue;
+            }
+
+            let is_better = match best {
+                Some(b) => get_range(pos, anchor) < get_range(b, anchor),
+                None => true,
+            };
+            if is_better == true {
+                best = Some(pos);
+            }
+        }
+    }
+
+    return best;
+}
+
+fn add(planned: &mut Vec<PlannedStructure>, kind: PlanKind, pos: (i32, i32)) {
+    planned.push(PlannedStructure {
+        kind: kind,
+        x: pos.0 as u32,
+        y: pos.1 as u32,
+    });
+}
+
+// 地形と既存spawnの位置からレイアウト全体を計算する. game APIに触らないのでそのまま試せる.
+pub fn calc_base_layout(
+    walls: &[bool],
+    spawn: (i32, i32),
+    sources: &Vec<(i32, i32)>,
+    controller: Option<(i32, i32)>,
+    mineral: Option<(i32, i32)>,
+) -> Option<BaseLayout> {
+    let dist = calc_distance_transform(walls);
+    let anchor = find_core_anchor(walls, &dist, spawn)?;
+
+    let mut planned: Vec<PlannedStructure> = Vec::new();
+    let mut reserved: HashSet<(i32, i32)> = HashSet::new();
+    let mut roads: HashSet<(i32, i32)> = HashSet::new();
+
+    // core.
+    reserved.insert(anchor);
+    for (dx, dy, kind) in CORE_STAMP.iter() {
+        let pos = (anchor.0 + dx, anchor.1 + dy);
+        add(&mut planned, *kind, pos);
+        reserved.insert(pos);
+    }
+    for dx in -2..=2 {
+        for dy in -2..=2 {
+            if dx == -2 || dx == 2 || dy == -2 || dy == 2 {
+                roads.insert((anchor.0 + dx, anchor.1 + dy));
+                reserved.insert((anchor.0 + dx, anchor.1 + dy));
+            }
+        }
+    }
+
+    // sourceやcontrollerの周りには建てない.
+    let mut keep_out: Vec<(i32, i32)> = sources.clone();
+    keep_out.extend(controller.iter());
+    keep_out.extend(mineral.iter());
+
+    // labs.
+    match find_lab_anchor(walls, &reserved, anchor) {
+        Some(lab_anchor) => {
+            for (dx, dy) in LAB_STAMP.iter() {
+                let pos = (lab_anchor.0 + dx, lab_anchor.1 + dy);
+                add(&mut planned, PlanKind::LAB, pos);
+                reserved.insert(pos);
+            }
+            for i in 0..4 {
+                let pos = (lab_anchor.0 + i, lab_anchor.1 + i);
+                roads.insert(pos);
+                reserved.insert(pos);
+            }
+            reserved.insert((lab_anchor.0 + 3, lab_anchor.1));
+            reserved.insert((lab_anchor.0, lab_anchor.1 + 3));
+        }
+        None => {
+            warn!("no space for labs");
+        }
+    }
+
+    // controller link -> source linkの順. storage linkはcore stampに入っている.
+    if let Some(controller) = controller {
+        if let Some(pos) = find_link_pos(walls, &reserved, controller, 2, anchor) {
+            add(&mut planned, PlanKind::LINK, pos);
+            reserved.insert(pos);
+        }
+    }
+    for source in sources.iter() {
+        if let Some(pos) = find_link_pos(walls, &reserved, *source, 2, anchor) {
+            add(&mut planned, PlanKind::LINK, pos);
+            reserved.insert(pos);
+        }
+    }
+
+    if let Some(mineral) = mineral {
+        add(&mut planned, PlanKind::EXTRACTOR, mineral);
+    }
+
+    // 残りはanchorから広げながら市松模様で埋める. 偶奇の揃うマスは道.
+    let mut queue: Vec<PlanKind> = Vec::new();
+    queue.extend(std::iter::repeat(PlanKind::TOWER).take(6));
+    queue.extend(std::iter::repeat(PlanKind::EXTENSION).take(60));
+    queue.push(PlanKind::NUKER);
+    queue.push(PlanKind::OBSERVER);
+    queue.reverse();
+
+    let parity = (anchor.0 + anchor.1) % 2;
+    let mut visited: HashSet<(i32, i32)> = HashSet::new();
+    let mut bfs: VecDeque<(i32, i32)> = VecDeque::new();
+    bfs.push_back(anchor);
+    visited.insert(anchor);
+
+    while let Some(pos) = bfs.pop_front() {
+        if queue.len() <= 0 {
+            break;
+        }
+
+        let is_free =
+            reserved.contains(&pos) == false && keep_out.iter().all(|p| get_range(*p, pos) > 2);
+
+        if is_free == true && (pos.0 + pos.1) % 2 != parity {
+            // 道に面していないと運べない.
+            let has_road = [(0, -1), (0, 1), (-1, 0), (1, 0)].iter().any(|(dx, dy)| {
+                let n = (pos.0 + dx, pos.1 + dy);
+                roads.contains(&n)
+                    || (check_buildable(walls, n.0, n.1)
+                        && reserved.contains(&n) == false
+                        && (n.0 + n.1) % 2 == parity)
+            });
+
+            if has_road == true {
+                if let Some(kind) = queue.pop() {
+                    add(&mut planned, kind, pos);
+                    reserved.insert(pos);
+
+                    for (dx, dy) in [(0, -1), (0, 1), (-1, 0), (1, 0)].iter() {
+                        let n = (pos.0 + dx, pos.1 + dy);
+                        if check_buildable(walls, n.0, n.1) && reserved.contains(&n) == false {
+                            roads.insert(n);
+                            reserved.insert(n);
+                        }
+                    }
+                }
+            }
+        }
+
+        for dx in -1..=1 {
+            for dy in -1..=1 {
+                let n = (pos.0 + dx, pos.1 + dy);
+                if check_buildable(walls, n.0, n.1) && visited.contains(&n) == false {
+                    visited.insert(n);
+                    bfs.push_back(n);
+                }
+            }
+        }
+    }
+
+    if queue.len() > 0 {
+        warn!("layout is incomplete, {} structures left", queue.len());
+    }
+
+    let mut road_list: Vec<(i32, i32)> = roads.into_iter().collect();
+    road_list.sort_by_key(|pos| get_range(*pos, anchor));
+    for pos in road_list {
+        if check_buildable(walls, pos.0, pos.1) == true {
+            add(&mut planned, PlanKind::ROAD, pos);
+        }
+    }
+
+    // 種類ごとの優先度で並べる. 同じ種類の中では計算した順(anchorに近い順).
+    planned.sort_by_key(|s| get_plan_priority(s.kind));
+
+    return Some(BaseLayout {
+        anchor_x: anchor.0 as u32,
+        anchor_y: anchor.1 as u32,
+        structures: planned,
+        created: 0,
+    });
+}
+
+fn make_wall_grid(room: &Room) -> Vec<bool> {
+    let terrain = room.get_terrain();
+    let mut walls = vec![false; (ROOM_SIZE * ROOM_SIZE) as usize];
+
+    for y in 0..ROOM_SIZE {
+        for x in 0..ROOM_SIZE {
+            walls[to_index(x, y)] = terrain.get(x as u32, y as u32) == Terrain::Wall;
+        }
+    }
+
+    return walls;
+}
+
+fn plan_room_layout(room: &Room) -> Option<BaseLayout> {
+    let spawn = room.find(MY_SPAWNS).into_iter().next()?;
+    let to_xy = |pos: Position| (pos.x() as i32, pos.y() as i32);
+
+    let sources: Vec<(i32, i32)> = room.find(SOURCES).iter().map(|s| to_xy(s.pos())).collect();
+    let controller = room.controller().map(|c| to_xy(c.pos()));
+    let mineral = room.find(MINERALS).first().map(|m| to_xy(m.pos()));
+
+    let mut layout = calc_base_layout(
+        &make_wall_grid(room),
+        to_xy(spawn.pos()),
+        &sources,
+        controller,
+        mineral,
+    )?;
+    layout.created = game::time();
+
+    return Some(layout);
+}
+
+// RCLで許される分だけ、優先順にconstruction siteを置く.
+fn place_layout_sites(room: &Room, layout: &BaseLayout, rcl: u32) {
+    let mut num_sites = room.find(MY_CONSTRUCTION_SITES).len() as u32;
+    if num_sites >= LAYOUT_MAX_ROOM_SITES {
+        return;
+    }
+
+    let mut counts: HashMap<StructureType, u32> = HashMap::new();
+    for structure in room.find(STRUCTURES) {
+        *counts.entry(structure.structure_type()).or_insert(0) += 1;
+    }
+    for site in room.find(MY_CONSTRUCTION_SITES) {
+        *counts.entry(site.structure_type()).or_insert(0) += 1;
+    }
+
+    for planned in layout.structures.iter() {
+        if num_sites >= LAYOUT_MAX_ROOM_SITES {
+            return;
+        }
+
+        if planned.kind == PlanKind::ROAD && rcl < LAYOUT_ROAD_MIN_RCL {
+            continue;
+        }
+
+        let structure_type = to_structure_type(planned.kind);
+        let count = counts.get(&structure_type).cloned().unwrap_or(0);
+        if count >= get_structure_limit(planned.kind, rcl) {
+            continue;
+        }
+
+        let pos = Position::new(planned.x, planned.y, room.name());
+
+        let is_built = room
+            .look_for_at(screeps::look::STRUCTURES, &pos)
+            .iter()
+            .any(|s| s.structure_type() == structure_type)
+            || room
+                .look_for_at(screeps::look::CONSTRUCTION_SITES, &pos)
+                .len()
+                > 0;
+        if is_built == true {
+            continue;
+        }
+
+        let r = room.create_construction_site(&pos, structure_type);
+        if r == ReturnCode::Ok {
+            info!("place layout site: {:?}, {:?}", structure_type, pos);
+            counts.insert(structure_type, count + 1);
+            num_sites += 1;
+        } else {
+            debug!(
+                "couldn't place layout site: {:?}, {:?}, {:?}",
+                structure_type, pos, r
+            );
+        }
+    }
+}
+
+pub fn run_layout() {
+    let mut layout_map = load_base_layouts();
+    let mut is_planned = false;
+
+    for room in game::rooms::values() {
+        let controller = match room.controller() {
+            Some(controller) => controller,
+            None => continue,
+        };
+
+        if controller.my() == false {
+            continue;
+        }
+
+        let room_name = room.name().to_string();
+
+        // 計算は重いので1tickに1部屋まで.
+        if layout_map.contains_key(&room_name) == false {
+            if is_planned == true {
+                continue;
+            }
+
+            match plan_room_layout(&room) {
+                Some(layout) => {
+                    info!(
+                        "base layout planned {}: anchor:({}, {}), {} structures",
+                        room_name,
+                        layout.anchor_x,
+                        layout.anchor_y,
+                        layout.structures.len()
+                    );
+                    layout_map.insert(room_name.clone(), layout);
+                    is_planned = true;
+                }
+                None => {
+                    debug!("couldn't plan base layout {}", room_name);
+                    continue;
+                }
+            }
+        }
+
+        if game::time() % LAYOUT_PLACE_INTERVAL != 0 {
+            continue;
+        }
+
+        if let Some(layout) = layout_map.get(&room_name) {
+            place_layout_sites(&room, layout, controller.level());
+        }
+    }
+
+    if is_planned == true {
+        save_base_layouts(&layout_map);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // 外周は壁で、上と右に出口がある部屋. 中に壁の塊と壁の線.
+    fn make_test_walls() -> Vec<bool> {
+        let mut walls = vec![false; (ROOM_SIZE * ROOM_SIZE) as usize];
+
+        for i in 0..ROOM_SIZE {
+            walls[to_index(i, 0)] = true;
+            walls[to_index(i, ROOM_SIZE - 1)] = true;
+            walls[to_index(0, i)] = true;
+            walls[to_index(ROOM_SIZE - 1, i)] = true;
+        }
+
+        for x in 20..30 {
+            walls[to_index(x, 0)] = false;
+        }
+        for y in 10..20 {
+            walls[to_index(ROOM_SIZE - 1, y)] = false;
+        }
+
+        for y in 30..40 {
+            for x in 30..40 {
+                walls[to_index(x, y)] = true;
+            }
+        }
+        for x in 5..22 {
+            walls[to_index(x, 15)] = true;
+        }
+
+        return walls;
+    }
+
+    fn get_exits(walls: &[bool]) -> Vec<(i32, i32)> {
+        let mut exits = Vec::new();
+
+        for y in 0..ROOM_SIZE {
+            for x in 0..ROOM_SIZE {
+                let is_edge = x == 0 || y == 0 || x == ROOM_SIZE - 1 || y == ROOM_SIZE - 1;
+                if is_edge && walls[to_index(x, y)] == false {
+                    exits.push((x, y));
+                }
+            }
+        }
+
+        return exits;
+    }
+
+    fn assert_layout_avoids_walls_and_exits(walls: &[bool], layout: &BaseLayout) {
+        let exits = get_exits(walls);
+        let mut used: HashSet<(i32, i32)> = HashSet::new();
+
+        for s in layout.structures.iter() {
+            let pos = (s.x as i32, s.y as i32);
+
+            assert!(check_in_room(pos.0, pos.1), "{:?} is out of room", s);
+            assert!(
+                walls[to_index(pos.0, pos.1)] == false,
+                "{:?} is on a wall",
+                s
+            );
+            assert!(
+                exits.iter().all(|exit| get_range(*exit, pos) > 1),
+                "{:?} is next to an exit",
+                s
+            );
+            assert!(used.insert(pos), "{:?} overlaps another structure", s);
+        }
+    }
+
+    #[test]
+    fn distance_transform_open_room() {
+        let walls = vec![false; (ROOM_SIZE * ROOM_SIZE) as usize];
+        let dist = calc_distance_transform(&walls);
+
+        assert_eq!(dist[to_index(0, 0)], 1);
+        assert_eq!(dist[to_index(1, 1)], 2);
+        assert_eq!(dist[to_index(0, 25)], 1);
+        assert_eq!(dist[to_index(24, 24)], 25);
+        assert_eq!(dist[to_index(49, 49)], 1);
+    }
+
+    #[test]
+    fn distance_transform_walls() {
+        let walls = make_test_walls();
+        let dist = calc_distance_transform(&walls);
+
+        assert_eq!(dist[to_index(0, 0)], 0);
+        assert_eq!(dist[to_index(35, 35)], 0);
+        assert_eq!(dist[to_index(29, 35)], 1);
+        assert_eq!(dist[to_index(27, 35)], 3);
+        assert_eq!(dist[to_index(10, 14)], 1);
+        assert_eq!(dist[to_index(10, 17)], 2);
+    }
+
+    #[test]
+    fn layout_keeps_existing_spawn() {
+        let walls = make_test_walls();
+        let layout = calc_base_layout(
+            &walls,
+            (20, 25),
+            &vec![(8, 8), (42, 8)],
+            Some((10, 40)),
+            None,
+        )
+        .unwrap();
+
+        assert_eq!((layout.anchor_x, layout.anchor_y), (21, 26));
+        assert!(layout
+            .structures
+            .iter()
+            .any(|s| s.kind == PlanKind::SPAWN && s.x == 20 && s.y == 25));
+        assert_layout_avoids_walls_and_exits(&walls, &layout);
+    }
+
+    #[test]
+    fn layout_moves_core_away_from_walls() {
+        let walls = make_test_walls();
+        let layout = calc_base_layout(
+            &walls,
+            (33, 28),
+            &vec![(8, 8), (42, 8)],
+            Some((10, 40)),
+            Some((45, 45)),
+        )
+        .unwrap();
+
+        assert_ne!((layout.anchor_x, layout.anchor_y), (34, 29));
+        assert_layout_avoids_walls_and_exits(&walls, &layout);
+    }
+
+    #[test]
+    fn layout_near_exit() {
+        let walls = make_test_walls();
+        let layout =
+            calc_base_layout(&walls, (25, 4), &vec![(40, 20)], Some((45, 12)), None).unwrap();
+
+        assert_layout_avoids_walls_and_exits(&walls, &layout);
+    }
+}
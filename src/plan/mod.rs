@@ -0,0 +1,3 @@
+pub mod layout;
+pub mod rampart;
+pub mod road;
@@ -0,0 +1,597 @@
+use crate::constants::*;
+use crate::plan::layout::*;
+use log::*;
+use screeps::constants::find::*;
+use screeps::{game, prelude::*, Position, ReturnCode, Room, Structure, StructureType, Terrain};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet, VecDeque};
+
+use lazy_static::lazy_static;
+use std::sync::RwLock;
+
+const ROOM_SIZE: i32 = 50;
+const INFINITE_CAPACITY: i32 = 10000;
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct RampartTile {
+    pub x: u32,
+    pub y: u32,
+    pub is_critical: bool,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct RampartPlan {
+    pub tiles: Vec<RampartTile>,
+    pub created: u32,
+}
+
+type RampartPlanMap = HashMap<String, RampartPlan>;
+
+lazy_static! {
+    // (部屋, x, y)で引けるようにしておく. Noneはまだ読み込んでいない.
+    static ref CRITICAL_RAMPART_CACHE: RwLock<Option<HashSet<(String, u32, u32)>>> =
+        RwLock::new(None);
+}
+
+struct FlowEdge {
+    to: usize,
+    capacity: i32,
+}
+
+// Dinic法.
+struct FlowGraph {
+    edges: Vec<FlowEdge>,
+    graph: Vec<Vec<usize>>,
+    level: Vec<i32>,
+    iter: Vec<usize>,
+}
+
+impl FlowGraph {
+    fn new(num_nodes: usize) -> FlowGraph {
+        return FlowGraph {
+            edges: Vec::new(),
+            graph: vec![Vec::new(); num_nodes],
+            level: vec![-1; num_nodes],
+            iter: vec![0; num_nodes],
+        };
+    }
+
+    fn add_edge(&mut self, from: usize, to: usize, capacity: i32) {
+        self.graph[from].push(self.edges.len());
+        self.edges.push(FlowEdge {
+            to: to,
+            capacity: capacity,
+        });
+        self.graph[to].push(self.edges.len());
+        self.edges.push(FlowEdge {
+            to: from,
+            capacity: 0,
+        });
+    }
+
+    fn calc_level(&mut self, source: usize) {
+        for level in self.level.iter_mut() {
+            *level = -1;
+        }
+        self.level[source] = 0;
+
+        let mut queue = VecDeque::new();
+        queue.push_back(source);
+
+        while let Some(node) = queue.pop_front() {
+            for edge_index in self.graph[node].iter() {
+                let edge = &self.edges[*edge_index];
+                if edge.capacity > 0 && self.level[edge.to] < 0 {
+                    self.level[edge.to] = self.level[node] + 1;
+                    queue.push_back(edge.to);
+                }
+            }
+        }
+    }
+
+    fn find_augment_path(&mut self, node: usize, sink: usize, flow: i32) -> i32 {
+        if node == sink {
+            return flow;
+        }
+
+        while self.iter[node] < self.graph[node].len() {
+            let edge_index = self.graph[node][self.iter[node]];
+            let to = self.edges[edge_index].to;
+            let capacity = self.edges[edge_index].capacity;
+
+            if capacity > 0 && self.level[node] < self.level[to] {
+                let d = self.find_augment_path(to, sink, std::cmp::min(flow, capacity));
+                if d > 0 {
+                    self.edges[edge_index].capacity -= d;
+                    self.edges[edge_index ^ 1].capacity += d;
+                    return d;
+                }
+            }
+
+            self.iter[node] += 1;
+        }
+
+        return 0;
+    }
+
+    fn calc_max_flow(&mut self, source: usize, sink: usize) -> i32 {
+        let mut flow = 0;
+
+        loop {
+            self.calc_level(source);
+            if self.level[sink] < 0 {
+                return flow;
+            }
+
+            for iter in self.iter.iter_mut() {
+                *iter = 0;
+            }
+
+            loop {
+                let f = self.find_augment_path(source, sink, INFINITE_CAPACITY);
+                if f <= 0 {
+                    break;
+                }
+                flow += f;
+            }
+        }
+    }
+}
+
+fn to_index(x: i32, y: i32) -> usize {
+    return (y * ROOM_SIZE + x) as usize;
+}
+
+fn get_range(a: (i32, i32), b: (i32, i32)) -> i32 {
+    return std::cmp::max((a.0 - b.0).abs(), (a.1 - b.1).abs());
+}
+
+// 出口と、出口から1マス以内には建てられない.
+fn check_exit_zone(walls: &[bool], x: i32, y: i32) -> bool {
+    for dx in -1..=1 {
+        for dy in -1..=1 {
+            let nx = x + dx;
+            let ny = y + dy;
+            if nx < 0 || ny < 0 || nx >= ROOM_SIZE || ny >= ROOM_SIZE {
+                continue;
+            }
+
+            let is_edge = nx == 0 || ny == 0 || nx == ROOM_SIZE - 1 || ny == ROOM_SIZE - 1;
+            if is_edge == true && walls[to_index(nx, ny)] == false {
+                return true;
+            }
+        }
+    }
+
+    return false;
+}
+
+// 守りたいマスと出口を分ける最小のマス集合を求める. マスを入口/出口の2頂点に分けて容量1の辺でつなぐ.
+// game APIに触らないのでそのまま試せる.
+pub fn calc_min_cut(walls: &[bool], protected: &HashSet<(i32, i32)>) -> Vec<(i32, i32)> {
+    let num_tiles = (ROOM_SIZE * ROOM_SIZE) as usize;
+    let source = num_tiles * 2;
+    let sink = source + 1;
+    let mut graph = FlowGraph::new(num_tiles * 2 + 2);
+
+    for y in 0..ROOM_SIZE {
+        for x in 0..ROOM_SIZE {
+            if walls[to_index(x, y)] == true {
+                continue;
+            }
+
+            let tile_in = to_index(x, y);
+            let tile_out = tile_in + num_tiles;
+            let is_exit_zone = check_exit_zone(walls, x, y);
+
+            if protected.contains(&(x, y)) == true {
+                graph.add_edge(source, tile_in, INFINITE_CAPACITY);
+                graph.add_edge(tile_in, tile_out, INFINITE_CAPACITY);
+            } else if is_exit_zone == true {
+                graph.add_edge(tile_in, tile_out, INFINITE_CAPACITY);
+                graph.add_edge(tile_out, sink, INFINITE_CAPACITY);
+            } else {
+                graph.add_edge(tile_in, tile_out, 1);
+            }
+
+            for dx in -1..=1 {
+                for dy in -1..=1 {
+                    let nx = x + dx;
+                    let ny = y + dy;
+                    if (dx == 0 && dy == 0)
+                        || nx < 0
+                        || ny < 0
+                        || nx >= ROOM_SIZE
+                        || ny >= ROOM_SIZE
+                    {
+                        continue;
+                    }
+                    if walls[to_index(nx, ny)] == true {
+                        continue;
+                    }
+
+                    graph.add_edge(tile_out, to_index(nx, ny), INFINITE_CAPACITY);
+                }
+            }
+        }
+    }
+
+    let flow = graph.calc_max_flow(source, sink);
+    debug!("rampart min cut flow:{}", flow);
+
+    // 残余グラフでsourceから届く入口頂点のうち、出口頂点に届かないマスがcut.
+    graph.calc_level(source);
+
+    let mut cut = Vec::new();
+    for y in 0..ROOM_SIZE {
+        for x in 0..ROOM_SIZE {
+            let tile_in = to_index(x, y);
+            if graph.level[tile_in] >= 0 && graph.level[tile_in + num_tiles] < 0 {
+                cut.push((x, y));
+            }
+        }
+    }
+
+    return cut;
+}
+
+// layoutの建物(道以外)とcontrollerの周りを守る範囲にする.
+fn make_protected_tiles(
+    walls: &[bool],
+    layout: &BaseLayout,
+    controller: Option<(i32, i32)>,
+) -> HashSet<(i32, i32)> {
+    let anchor = (layout.anchor_x as i32, layout.anchor_y as i32);
+    let mut centers: Vec<(i32, i32)> = layout
+        .structures
+        .iter()
+        .filter(|s| s.kind != PlanKind::ROAD && s.kind != PlanKind::EXTRACTOR)
+        // source linkは外に出てもよい.
+        .filter(|s| s.kind != PlanKind::LINK || get_range((s.x as i32, s.y as i32), anchor) <= 1)
+        .map(|s| (s.x as i32, s.y as i32))
+        .collect();
+    centers.extend(controller.iter());
+
+    let range = RAMPART_PROTECT_RANGE as i32;
+    let mut protected = HashSet::new();
+
+    for (cx, cy) in centers {
+        for dx in -range..=range {
+            for dy in -range..=range {
+                let x = cx + dx;
+                let y = cy + dy;
+                if x < 0 || y < 0 || x >= ROOM_SIZE || y >= ROOM_SIZE {
+                    continue;
+                }
+                if walls[to_index(x, y)] == true || check_exit_zone(walls, x, y) == true {
+                    continue;
+                }
+                protected.insert((x, y));
+            }
+        }
+    }
+
+    return protected;
+}
+
+// 守りの要になる建物の近くのrampartは優先して直す.
+fn check_critical_tile(layout: &BaseLayout, tile: (i32, i32)) -> bool {
+    return layout.structures.iter().any(|s| {
+        (s.kind == PlanKind::SPAWN
+            || s.kind == PlanKind::STORAGE
+            || s.kind == PlanKind::TERMINAL
+            || s.kind == PlanKind::TOWER)
+            && get_range((s.x as i32, s.y as i32), tile) <= RAMPART_CRITICAL_RANGE as i32
+    });
+}
+
+pub fn load_rampart_plans() -> RampartPlanMap {
+    let json_str = screeps::memory::root()
+        .string("rampart_plans")
+        .unwrap_or(None)
+        .unwrap_or(String::from("{}"));
+
+    return serde_json::from_str(json_str.as_str()).unwrap_or(HashMap::new());
+}
+
+fn save_rampart_plans(plan_map: &RampartPlanMap) {
+    let json_str = serde_json::to_string(plan_map).unwrap();
+    screeps::memory::root().set("rampart_plans", json_str);
+}
+
+fn plan_room_ramparts(room: &Room, layout: &BaseLayout) -> RampartPlan {
+    let terrain = room.get_terrain();
+    let mut walls = vec![false; (ROOM_SIZE * ROOM_SIZE) as usize];
+    for y in 0..ROOM_SIZE {
+        for x in 0..ROOM_SIZE {
+            walls[to_index(x, y)] = terrain.get(x as u32, y as u32) == Terrain::Wall;
+        }
+    }
+
+    let controller = room
+        .controller()
+        .map(|c| (c.pos().x() as i32, c.pos().y() as i32));
+    let protected = make_protected_tiles(&walls, layout, controller);
+
+    let mut tiles: Vec<RampartTile> = calc_min_cut(&walls, &protected)
+        .into_iter()
+        .map(|tile| RampartTile {
+            x: tile.0 as u32,
+            y: tile.1 as u32,
+            is_critical: check_critical_tile(layout, tile),
+        })
+        .collect();
+
+    // 重要な建物の上にも置く.
+    for s in layout.structures.iter() {
+        if s.kind == PlanKind::SPAWN || s.kind == PlanKind::STORAGE || s.kind == PlanKind::TERMINAL
+        {
+            tiles.push(RampartTile {
+                x: s.x,
+                y: s.y,
+                is_critical: true,
+            });
+        }
+    }
+
+    tiles.sort_by_key(|tile| tile.is_critical == false);
+
+    return RampartPlan {
+        tiles: tiles,
+        created: game::time(),
+    };
+}
+
+pub fn check_critical_rampart(structure: &Structure) -> bool {
+    if structure.structure_type() != StructureType::Rampart {
+        return false;
+    }
+
+    let pos = structure.pos();
+    let critical_ramparts = CRITICAL_RAMPART_CACHE.read().unwrap();
+
+    match critical_ramparts.as_ref() {
+        Some(critical_ramparts) => {
+            return critical_ramparts.contains(&(pos.room_name().to_string(), pos.x(), pos.y()));
+        }
+        None => {}
+    }
+
+    return false;
+}
+
+fn update_critical_cache(plan_map: &RampartPlanMap) {
+    let mut critical_ramparts = HashSet::new();
+
+    for (room_name, plan) in plan_map.iter() {
+        for tile in plan.tiles.iter() {
+            if tile.is_critical == true {
+                critical_ramparts.insert((room_name.clone(), tile.x, tile.y));
+            }
+        }
+    }
+
+    *CRITICAL_RAMPART_CACHE.write().unwrap() = Some(critical_ramparts);
+}
+
+fn place_rampart_sites(room: &Room, plan: &RampartPlan) {
+    let mut num_sites = room.find(MY_CONSTRUCTION_SITES).len() as u32;
+
+    for tile in plan.tiles.iter() {
+        if num_sites >= RAMPART_MAX_ROOM_SITES {
+            return;
+        }
+
+        let pos = Position::new(tile.x, tile.y, room.name());
+
+        let is_built = room
+            .look_for_at(screeps::look::STRUCTURES, &pos)
+            .iter()
+            .any(|s| s.structure_type() == StructureType::Rampart)
+            || room
+                .look_for_at(screeps::look::CONSTRUCTION_SITES, &pos)
+                .iter()
+                .any(|s| s.structure_type() == StructureType::Rampart);
+        if is_built == true {
+            continue;
+        }
+
+        let r = room.create_construction_site(&pos, StructureType::Rampart);
+        if r == ReturnCode::Ok {
+            info!(
+                "place rampart site: {:?}, critical:{}",
+                pos, tile.is_critical
+            );
+            num_sites += 1;
+        } else {
+            debug!("couldn't place rampart site: {:?}, {:?}", pos, r);
+        }
+    }
+}
+
+pub fn run_rampart_planner() {
+    let is_loaded = CRITICAL_RAMPART_CACHE.read().unwrap().is_some();
+
+    if is_loaded == true && game::time() % RAMPART_PLAN_INTERVAL != 0 {
+        return;
+    }
+
+    let mut plan_map = load_rampart_plans();
+    let mut is_planned = false;
+
+    let layout_map = load_base_layouts();
+
+    for room in game::rooms::values() {
+        let controller = match room.controller() {
+            Some(controller) => controller,
+            None => continue,
+        };
+
+        if controller.my() == false || controller.level() < RAMPART_MIN_RCL {
+            continue;
+        }
+
+        let room_name = room.name().to_string();
+
+        let layout = match layout_map.get(&room_name) {
+            Some(layout) => layout,
+            None => continue,
+        };
+
+        // layoutが作り直されたらrampartも作り直す.
+        let is_outdated = match plan_map.get(&room_name) {
+            Some(plan) => plan.created < layout.created,
+            None => true,
+        };
+
+        // min cutは重いので1回に1部屋まで.
+        if is_outdated == true {
+            if is_planned == true {
+                continue;
+            }
+
+            let plan = plan_room_ramparts(&room, layout);
+            info!(
+                "rampart plan {}: {} tiles, {} critical",
+                room_name,
+                plan.tiles.len(),
+                plan.tiles.iter().filter(|t| t.is_critical).count()
+            );
+            plan_map.insert(room_name.clone(), plan);
+            is_planned = true;
+        }
+
+        if let Some(plan) = plan_map.get(&room_name) {
+            place_rampart_sites(&room, plan);
+        }
+    }
+
+    if is_planned == true {
+        save_rampart_plans(&plan_map);
+    }
+    update_critical_cache(&plan_map);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn make_open_walls() -> Vec<bool> {
+        return vec![false; (ROOM_SIZE * ROOM_SIZE) as usize];
+    }
+
+    // 外周は壁で、上に出口. y=20の壁の線にx=10..13の隙間がある.
+    fn make_corridor_walls() -> Vec<bool> {
+        let mut walls = make_open_walls();
+
+        for i in 0..ROOM_SIZE {
+            walls[to_index(i, 0)] = true;
+            walls[to_index(i, ROOM_SIZE - 1)] = true;
+            walls[to_index(0, i)] = true;
+            walls[to_index(ROOM_SIZE - 1, i)] = true;
+        }
+        for x in 20..30 {
+            walls[to_index(x, 0)] = false;
+        }
+        for x in 0..ROOM_SIZE {
+            if x < 10 || x >= 13 {
+                walls[to_index(x, 20)] = true;
+            }
+        }
+
+        return walls;
+    }
+
+    fn make_square(center: (i32, i32), range: i32) -> HashSet<(i32, i32)> {
+        let mut tiles = HashSet::new();
+        for dx in -range..=range {
+            for dy in -range..=range {
+                tiles.insert((center.0 + dx, center.1 + dy));
+            }
+        }
+        return tiles;
+    }
+
+    // cutを壁として、守るマスから出口の近くまで歩けないこと.
+    fn assert_cut_separates(
+        walls: &[bool],
+        protected: &HashSet<(i32, i32)>,
+        cut: &Vec<(i32, i32)>,
+    ) {
+        for tile in cut.iter() {
+            assert!(
+                walls[to_index(tile.0, tile.1)] == false,
+                "{:?} is a wall",
+                tile
+            );
+            assert!(protected.contains(tile) == false, "{:?} is protected", tile);
+            assert!(
+                check_exit_zone(walls, tile.0, tile.1) == false,
+                "{:?} is next to an exit",
+                tile
+            );
+        }
+
+        let blocked: HashSet<(i32, i32)> = cut.iter().cloned().collect();
+        let mut visited: HashSet<(i32, i32)> = protected.clone();
+        let mut queue: VecDeque<(i32, i32)> = protected.iter().cloned().collect();
+
+        while let Some(pos) = queue.pop_front() {
+            assert!(
+                check_exit_zone(walls, pos.0, pos.1) == false,
+                "{:?} reaches an exit",
+                pos
+            );
+
+            for dx in -1..=1 {
+                for dy in -1..=1 {
+                    let n = (pos.0 + dx, pos.1 + dy);
+                    if n.0 < 0 || n.1 < 0 || n.0 >= ROOM_SIZE || n.1 >= ROOM_SIZE {
+                        continue;
+                    }
+                    if walls[to_index(n.0, n.1)] == true
+                        || blocked.contains(&n)
+                        || visited.contains(&n)
+                    {
+                        continue;
+                    }
+                    visited.insert(n);
+                    queue.push_back(n);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn min_cut_open_room() {
+        let walls = make_open_walls();
+        let protected = make_square((25, 25), 2);
+        let cut = calc_min_cut(&walls, &protected);
+
+        assert_cut_separates(&walls, &protected, &cut);
+        // 5x5を囲む7x7の輪.
+        assert_eq!(cut.len(), 24);
+    }
+
+    #[test]
+    fn min_cut_corridor() {
+        let walls = make_corridor_walls();
+        let protected = make_square((25, 35), 3);
+        let cut = calc_min_cut(&walls, &protected);
+
+        assert_cut_separates(&walls, &protected, &cut);
+        // 隙間だけふさげばよい.
+        assert_eq!(cut.len(), 3);
+    }
+
+    #[test]
+    fn min_cut_without_exits() {
+        let mut walls = make_corridor_walls();
+        for x in 20..30 {
+            walls[to_index(x, 0)] = true;
+        }
+        let protected = make_square((25, 35), 3);
+
+        assert_eq!(calc_min_cut(&walls, &protected).len(), 0);
+    }
+}
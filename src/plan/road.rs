@@ -0,0 +1,263 @@
+use crate::constants::*;
+use crate::plan::layout::*;
+use crate::util::*;
+use log::*;
+use screeps::constants::find::*;
+use screeps::{
+    game, prelude::*, Creep, Position, ReturnCode, Room, RoomName, Structure, StructureType,
+};
+use std::collections::{HashMap, HashSet};
+
+use lazy_static::lazy_static;
+use std::sync::RwLock;
+
+#[derive(Default)]
+struct TrafficData {
+    // 部屋ごとに(x, y) -> 踏まれた回数.
+    heatmap: HashMap<RoomName, HashMap<(u32, u32), u32>>,
+    last_pos: HashMap<String, Position>,
+    started: u32,
+}
+
+lazy_static! {
+    static ref TRAFFIC_CACHE: RwLock<TrafficData> = RwLock::new(TrafficData::default());
+    static ref UNUSED_ROAD_CACHE: RwLock<HashSet<String>> = RwLock::new(HashSet::new());
+}
+
+// 前のtickから位置が変わっていれば、今いるマスを踏んだとみなす.
+pub fn record_traffic(creep: &Creep) {
+    let pos = creep.pos();
+    let mut traffic = TRAFFIC_CACHE.write().unwrap();
+
+    if traffic.started == 0 {
+        traffic.started = game::time();
+    }
+
+    let is_moved = match traffic.last_pos.get(&creep.name()) {
+        Some(last_pos) => *last_pos != pos,
+        None => false,
+    };
+    traffic.last_pos.insert(creep.name(), pos);
+
+    if is_moved == false {
+        return;
+    }
+
+    *traffic
+        .heatmap
+        .entry(pos.room_name())
+        .or_insert(HashMap::new())
+        .entry((pos.x(), pos.y()))
+        .or_insert(0) += 1;
+}
+
+pub fn get_traffic(pos: &Position) -> u32 {
+    let traffic = TRAFFIC_CACHE.read().unwrap();
+
+    return traffic
+        .heatmap
+        .get(&pos.room_name())
+        .and_then(|room_heat| room_heat.get(&(pos.x(), pos.y())))
+        .cloned()
+        .unwrap_or(0);
+}
+
+// 使われなくなった道はrepair対象から外して自然に消えるのを待つ.
+pub fn check_road_unused(structure: &Structure) -> bool {
+    if structure.structure_type() != StructureType::Road {
+        return false;
+    }
+
+    let unused_roads = UNUSED_ROAD_CACHE.read().unwrap();
+    return unused_roads.contains(&structure.id().to_string());
+}
+
+// 古い記録の重みを下げていく. 死んだcreepの位置も捨てる.
+fn decay_traffic() {
+    let mut traffic = TRAFFIC_CACHE.write().unwrap();
+
+    for room_heat in traffic.heatmap.values_mut() {
+        for heat in room_heat.values_mut() {
+            *heat = *heat * ROAD_HEAT_DECAY_PERCENT / 100;
+        }
+        room_heat.retain(|_xy, heat| *heat > 0);
+    }
+
+    let alive_creeps: HashSet<String> = game::creeps::keys().into_iter().collect();
+    traffic
+        .last_pos
+        .retain(|name, _pos| alive_creeps.contains(name));
+}
+
+// 既に道かsiteがあるマス、通れない建物のあるマスには置かない.
+fn check_road_placeable(room: &Room, pos: &Position) -> bool {
+    let has_road_or_site = room
+        .look_for_at(screeps::look::STRUCTURES, pos)
+        .iter()
+        .any(|s| s.structure_type() == StructureType::Road)
+        || room
+            .look_for_at(screeps::look::CONSTRUCTION_SITES, pos)
+            .len()
+            > 0;
+
+    if has_road_or_site == true {
+        return false;
+    }
+
+    return check_walkable_tile(pos, true);
+}
+
+// storage(なければspawn)からsource/controller/mineralへの道.
+fn get_planned_route_tiles(room: &Room) -> HashSet<(u32, u32)> {
+    let mut tiles = HashSet::new();
+
+    let hub_pos = match room.storage() {
+        Some(storage) => storage.pos(),
+        None => match room.find(MY_SPAWNS).first() {
+            Some(spawn) => spawn.pos(),
+            None => return tiles,
+        },
+    };
+
+    let mut targets: Vec<(Position, u32)> = Vec::new();
+    for source in room.find(SOURCES) {
+        targets.push((source.pos(), 1));
+    }
+    if let Some(controller) = room.controller() {
+        targets.push((controller.pos(), 3));
+
+        if controller.level() >= ROAD_MINERAL_MIN_RCL {
+            for mineral in room.find(MINERALS) {
+                targets.push((mineral.pos(), 1));
+            }
+        }
+    }
+
+    for (target_pos, range) in targets {
+        let res = find_path_between(&hub_pos, &target_pos, range);
+        for pos in res.load_local_path() {
+            if pos.room_name() == room.name() {
+                tiles.insert((pos.x(), pos.y()));
+            }
+        }
+    }
+
+    return tiles;
+}
+
+fn plan_room_roads(room: &Room, rcl: u32) {
+    let planned_tiles = get_planned_route_tiles(room);
+
+    // layoutの道も必要な道として扱う.
+    // 道以外を建てる予定のマスに道があるとその建物を置けなくなるので、道は置かない.
+    let mut needed_tiles = planned_tiles.clone();
+    let mut reserved_tiles = HashSet::new();
+    if let Some(layout) = get_base_layout(&room.name().to_string()) {
+        for planned in layout.structures.iter() {
+            if planned.kind == PlanKind::ROAD {
+                needed_tiles.insert((planned.x, planned.y));
+            } else {
+                reserved_tiles.insert((planned.x, planned.y));
+            }
+        }
+    }
+
+    let room_heat: HashMap<(u32, u32), u32> = {
+        let traffic = TRAFFIC_CACHE.read().unwrap();
+        traffic
+            .heatmap
+            .get(&room.name())
+            .cloned()
+            .unwrap_or(HashMap::new())
+    };
+
+    // 踏まれた回数の多いマスから置く.
+    let mut candidates: Vec<((u32, u32), u32)> = room_heat
+        .iter()
+        .filter(|(_xy, heat)| **heat >= ROAD_HEAT_THRESHOLD)
+        .map(|(xy, heat)| (*xy, *heat))
+        .collect();
+    candidates.sort_by(|a, b| b.1.cmp(&a.1));
+
+    let mut tiles: Vec<(u32, u32)> = Vec::new();
+    if rcl >= ROAD_PLANNED_ROUTE_MIN_RCL {
+        tiles.extend(planned_tiles.iter());
+    }
+    tiles.extend(candidates.iter().map(|(xy, _heat)| *xy));
+
+    let mut num_sites = room.find(MY_CONSTRUCTION_SITES).len() as u32;
+
+    for (x, y) in tiles {
+        if num_sites >= ROAD_MAX_ROOM_SITES {
+            break;
+        }
+
+        if x <= 0 || y <= 0 || x >= 49 || y >= 49 {
+            continue;
+        }
+
+        if reserved_tiles.contains(&(x, y)) {
+            continue;
+        }
+
+        let pos = Position::new(x, y, room.name());
+        if check_road_placeable(room, &pos) == false {
+            continue;
+        }
+
+        let r = room.create_construction_site(&pos, StructureType::Road);
+        if r == ReturnCode::Ok {
+            info!("place road site: {:?}, heat:{}", pos, get_traffic(&pos));
+            num_sites += 1;
+        } else {
+            debug!("couldn't place road site: {:?}, {:?}", pos, r);
+        }
+    }
+
+    // 必要な道以外で、ほとんど踏まれていない道.
+    let is_enough_sampled =
+        game::time() - TRAFFIC_CACHE.read().unwrap().started >= ROAD_MIN_SAMPLE_TICKS;
+    let mut unused_roads = UNUSED_ROAD_CACHE.write().unwrap();
+
+    for structure in room.find(STRUCTURES) {
+        if structure.structure_type() != StructureType::Road {
+            continue;
+        }
+
+        let id = structure.id().to_string();
+        let xy = (structure.pos().x(), structure.pos().y());
+        let heat = room_heat.get(&xy).cloned().unwrap_or(0);
+
+        if is_enough_sampled == true
+            && needed_tiles.contains(&xy) == false
+            && heat < ROAD_UNUSED_THRESHOLD
+        {
+            unused_roads.insert(id);
+        } else {
+            unused_roads.remove(&id);
+        }
+    }
+}
+
+pub fn run_road_planner() {
+    if game::time() % ROAD_DECAY_INTERVAL == 0 {
+        decay_traffic();
+    }
+
+    if game::time() % ROAD_PLAN_INTERVAL != 0 {
+        return;
+    }
+
+    for room in game::rooms::values() {
+        let controller = match room.controller() {
+            Some(controller) => controller,
+            None => continue,
+        };
+
+        if controller.my() == false {
+            continue;
+        }
+
+        plan_room_roads(&room, controller.level());
+    }
+}
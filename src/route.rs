@@ -0,0 +1,145 @@
+use crate::constants::*;
+use crate::manage::intel::*;
+use crate::manage::remote::*;
+use crate::util::*;
+use log::*;
+use screeps::{game, prelude::*, Creep, Position, RoomName};
+use std::collections::{HashMap, HashSet};
+
+use lazy_static::lazy_static;
+use std::sync::RwLock;
+
+struct RouteData {
+    rooms: Vec<RoomName>,
+    updated: u32,
+}
+
+lazy_static! {
+    static ref ROUTE_CACHE: RwLock<HashMap<(RoomName, RoomName), RouteData>> =
+        RwLock::new(HashMap::new());
+}
+
+// W10N5のように座標が10の倍数の部屋.
+pub fn check_highway_room(room_name: &str) -> bool {
+    let coords: Vec<u32> = room_name
+        .split(|c: char| c.is_ascii_alphabetic())
+        .filter(|s| s.len() > 0)
+        .map(|s| s.parse().unwrap_or(1))
+        .collect();
+
+    return coords.iter().any(|coord| coord % 10 == 0);
+}
+
+// find_routeに渡す部屋ごとのコスト.
+fn calc_route_cost(room_name: RoomName, my_name: &Option<String>) -> f64 {
+    let room_key = room_name.to_string();
+
+    if let Some(room_intel) = get_room_intel(room_key.as_str()) {
+        if check_hostile_owned(&room_intel, my_name) {
+            return std::f64::INFINITY;
+        }
+
+        if check_source_keeper_room(&room_intel) {
+            return ROUTE_COST_SOURCE_KEEPER;
+        }
+
+        if let Some(controller) = &room_intel.controller {
+            if controller.owner.is_some() {
+                // 自分の部屋.
+                return ROUTE_COST_MY_ROOM;
+            }
+
+            if controller.reservation.is_some() && controller.reservation != *my_name {
+                return ROUTE_COST_HOSTILE_RESERVED;
+            }
+        }
+    }
+
+    if check_highway_room(room_key.as_str()) {
+        return ROUTE_COST_HIGHWAY;
+    }
+
+    return ROUTE_COST_DEFAULT;
+}
+
+fn calc_route_rooms(from: RoomName, to: RoomName) -> Option<Vec<RoomName>> {
+    let my_name = get_my_username();
+
+    let route = game::map::find_route_with_callback(from, to, |room_name, _from_room| {
+        calc_route_cost(room_name, &my_name)
+    });
+
+    match route {
+        Ok(steps) => {
+            let mut rooms = vec![from];
+            rooms.extend(steps.iter().map(|step| step.room));
+            return Some(rooms);
+        }
+        Err(r) => {
+            warn!("no route: {:?} -> {:?}, {:?}", from, to, r);
+            return None;
+        }
+    }
+}
+
+// 通る部屋の一覧(出発・到着の部屋を含む).
+pub fn find_route_rooms(from: RoomName, to: RoomName) -> Option<Vec<RoomName>> {
+    if from == to {
+        return Some(vec![from]);
+    }
+
+    {
+        let route_cache = ROUTE_CACHE.read().unwrap();
+
+        if let Some(value) = route_cache.get(&(from, to)) {
+            if game::time() - value.updated < ROUTE_CACHE_TICKS {
+                // use cached value.
+                return Some(value.rooms.clone());
+            }
+        }
+    }
+
+    let rooms = calc_route_rooms(from, to)?;
+    debug!("route: {:?} -> {:?}, {:?}", from, to, rooms);
+
+    {
+        let mut route_cache = ROUTE_CACHE.write().unwrap();
+        route_cache.insert(
+            (from, to),
+            RouteData {
+                rooms: rooms.clone(),
+                updated: game::time(),
+            },
+        );
+    }
+
+    return Some(rooms);
+}
+
+// 部屋をまたぐ移動はrouteの部屋だけでPathFinderを回す.
+pub fn find_route_path_between(
+    origin: &Position,
+    target_pos: &Position,
+    range: u32,
+) -> screeps::pathfinder::SearchResults {
+    if origin.room_name() == target_pos.room_name() {
+        return find_path_between(origin, target_pos, range);
+    }
+
+    match find_route_rooms(origin.room_name(), target_pos.room_name()) {
+        Some(rooms) => {
+            let allowed_rooms: HashSet<RoomName> = rooms.into_iter().collect();
+            return find_path_in_rooms(origin, target_pos, range, &allowed_rooms);
+        }
+        None => {
+            return find_path_between(origin, target_pos, range);
+        }
+    }
+}
+
+// 部屋の中央付近まで. 部屋に入ればよいのでrangeは広めにとる.
+pub fn find_path_to_room(creep: &Creep, room_name: RoomName) -> screeps::pathfinder::SearchResults {
+    let target_pos = Position::new(25, 25, room_name);
+
+    return find_route_path_between(&creep.pos(), &target_pos, 20);
+}
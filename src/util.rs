@@ -1,4 +1,8 @@
 use crate::constants::*;
+use crate::defence::threat::*;
+use crate::manage::source::*;
+use crate::plan::road::*;
+use crate::route::find_route_path_between;
 use log::*;
 use screeps::constants::find::*;
 use screeps::constants::*;
@@ -7,7 +11,7 @@ use screeps::local::RoomName;
 use screeps::objects::{HasPosition, Resource};
 use screeps::SharedCreepProperties;
 use screeps::{
-    game, pathfinder::*, ConstructionSite, HasStore, LookResult, RoomObjectProperties,
+    game, pathfinder::*, ConstructionSite, HasStore, LookResult, ReturnCode, RoomObjectProperties,
     RoomPosition, Source, Structure, StructureProperties,
 };
 
@@ -24,13 +28,18 @@ const ROOM_SIZE_Y: u8 = 50;
 type Data = HashMap<RoomName, LocalCostMatrix>;
 
 type ConstructionProgressAverage = HashMap<RoomName, u128>;
-type StructureHpAverage = HashMap<RoomName, u128>;
 
 type ConstructionProgressMin = HashMap<RoomName, u128>;
-type StructureHpMin = HashMap<RoomName, u128>;
 
 type RoomHashSet = HashSet<RoomName>;
 
+// 部屋ごとの汎用creepの数. tickごとに作り直す.
+#[derive(Default)]
+struct GenericCreepCounts {
+    counts: HashMap<RoomName, u32>,
+    updated: u32,
+}
+
 struct GlobalInitFlag {
     init_flag: bool,
 }
@@ -39,10 +48,10 @@ lazy_static! {
     static ref MAP_CACHE: RwLock<Data> = RwLock::new(HashMap::new());
     static ref CONSTRUCTION_PROGRESS_AVERAGE_CACHE: RwLock<ConstructionProgressAverage> =
         RwLock::new(HashMap::new());
-    static ref STRUCTURE_HP_AVERAGE_CACHE: RwLock<StructureHpAverage> = RwLock::new(HashMap::new());
     static ref CONSTRUCTION_PROGRESS_MIN_CACHE: RwLock<ConstructionProgressMin> =
         RwLock::new(HashMap::new());
-    static ref STRUCTURE_HP_MIN_CACHE: RwLock<StructureHpMin> = RwLock::new(HashMap::new());
+    static ref GENERIC_CREEP_CACHE: RwLock<GenericCreepCounts> =
+        RwLock::new(GenericCreepCounts::default());
 }
 
 pub fn clear_init_flag() {
@@ -52,14 +61,8 @@ pub fn clear_init_flag() {
     let mut construction_progress_average = CONSTRUCTION_PROGRESS_AVERAGE_CACHE.write().unwrap();
     construction_progress_average.clear();
 
-    let mut structure_hp_average = STRUCTURE_HP_AVERAGE_CACHE.write().unwrap();
-    structure_hp_average.clear();
-
     let mut construction_progress_min = CONSTRUCTION_PROGRESS_MIN_CACHE.write().unwrap();
     construction_progress_min.clear();
-
-    let mut structure_hp_min = STRUCTURE_HP_MIN_CACHE.write().unwrap();
-    structure_hp_min.clear();
 }
 
 #[derive(PartialEq, Debug)]
@@ -72,39 +75,14 @@ pub enum ResourceKind {
 
 pub fn calc_average(room_name: &RoomName) {
     let mut construction_progress_average = CONSTRUCTION_PROGRESS_AVERAGE_CACHE.write().unwrap();
-    let mut structure_hp_average = STRUCTURE_HP_AVERAGE_CACHE.write().unwrap();
-
     let mut construction_progress_min = CONSTRUCTION_PROGRESS_MIN_CACHE.write().unwrap();
-    let mut structure_hp_min = STRUCTURE_HP_MIN_CACHE.write().unwrap();
 
     let room = screeps::game::rooms::get(*room_name);
 
     match room {
         Some(room_obj) => {
-            let structures = room_obj.find(find::STRUCTURES);
             let construction_sites = room_obj.find(MY_CONSTRUCTION_SITES);
 
-            let mut total_hp: u128 = 0;
-            let mut hp_min: u128 = 0;
-
-            let mut struct_count: u128 = 0;
-
-            for chk_struct in structures {
-                let cur_hp = get_hp(&chk_struct);
-
-                match cur_hp {
-                    Some(hp) => {
-                        struct_count += 1 as u128;
-                        total_hp += hp as u128;
-
-                        if (hp_min > hp as u128) || (hp_min == 0) {
-                            hp_min = hp as u128;
-                        }
-                    }
-                    None => {}
-                }
-            }
-
             let mut sum_of_progress: u128 = 0;
             let mut progress_min: u128 = 0;
             let mut construction_count: u128 = 0;
@@ -120,21 +98,6 @@ pub fn calc_average(room_name: &RoomName) {
                 }
             }
 
-            if struct_count > 0 {
-                structure_hp_average.insert(*room_name, total_hp / struct_count);
-
-                structure_hp_min.insert(*room_name, hp_min);
-                info!(
-                    "{:?}: structure_hp_average:{:?}/min:{:?}",
-                    room_name,
-                    total_hp / struct_count,
-                    hp_min
-                );
-            } else {
-                structure_hp_average.insert(*room_name, 0);
-                structure_hp_min.insert(*room_name, 0);
-            }
-
             if construction_count > 0 {
                 construction_progress_average
                     .insert(*room_name, sum_of_progress / construction_count);
@@ -155,58 +118,6 @@ pub fn calc_average(room_name: &RoomName) {
     }
 }
 
-pub fn get_hp_average(room_name: &RoomName) -> (u128, u128) {
-    {
-        let structure_hp_average = STRUCTURE_HP_AVERAGE_CACHE.read().unwrap();
-        let cache_value = structure_hp_average.get(&room_name);
-
-        let structure_hp_min = STRUCTURE_HP_MIN_CACHE.read().unwrap();
-        let cache_value_min = structure_hp_min.get(&room_name);
-
-        match cache_value {
-            Some(value) => {
-                // use cached value.
-
-                match cache_value_min {
-                    Some(value_min) => {
-                        return (*value, *value_min);
-                    }
-
-                    None => {}
-                }
-            }
-            None => {}
-        }
-    }
-
-    calc_average(room_name);
-
-    {
-        let structure_hp_average = STRUCTURE_HP_AVERAGE_CACHE.read().unwrap();
-        let cache_value = structure_hp_average.get(&room_name);
-
-        let structure_hp_min = STRUCTURE_HP_MIN_CACHE.read().unwrap();
-        let cache_value_min = structure_hp_min.get(&room_name);
-
-        match cache_value {
-            Some(value) => {
-                // use cached value.
-
-                match cache_value_min {
-                    Some(value_min) => {
-                        return (*value, *value_min);
-                    }
-
-                    None => {}
-                }
-            }
-            None => {}
-        }
-    }
-
-    return (0, 0);
-}
-
 pub fn get_construction_progress_average(room_name: &RoomName) -> (u128, u128) {
     {
         let construction_progress_average = CONSTRUCTION_PROGRESS_AVERAGE_CACHE.read().unwrap();
@@ -495,7 +406,67 @@ fn calc_room_cost(room_name: RoomName) -> MultiRoomCostResult<'static> {
     return room_cost_result;
 }
 
+// role無しで湧かせて、部屋の状況で役割を変える汎用creep.
+pub fn check_generic_role(creep: &screeps::objects::Creep) -> bool {
+    let role = creep.memory().string("role").unwrap_or(None);
+
+    match role.as_ref().map(|r| r.as_str()) {
+        None
+        | Some("none")
+        | Some("harvester")
+        | Some("harvester_spawn")
+        | Some("harvester_mineral")
+        | Some("carrier_mineral")
+        | Some("builder")
+        | Some("upgrader")
+        | Some("repairer") => {
+            return true;
+        }
+        _ => {
+            return false;
+        }
+    }
+}
+
+pub fn count_generic_creeps(room_name: RoomName) -> u32 {
+    {
+        let generic_creep_cache = GENERIC_CREEP_CACHE.read().unwrap();
+
+        if generic_creep_cache.updated == screeps::game::time() {
+            if let Some(count) = generic_creep_cache.counts.get(&room_name) {
+                return *count;
+            }
+        }
+    }
+
+    let count = match screeps::game::rooms::get(room_name) {
+        Some(room) => room
+            .find(MY_CREEPS)
+            .iter()
+            .filter(|creep| check_generic_role(creep))
+            .count() as u32,
+        None => 0,
+    };
+
+    {
+        let mut generic_creep_cache = GENERIC_CREEP_CACHE.write().unwrap();
+
+        if generic_creep_cache.updated != screeps::game::time() {
+            generic_creep_cache.counts.clear();
+            generic_creep_cache.updated = screeps::game::time();
+        }
+        generic_creep_cache.counts.insert(room_name, count);
+    }
+
+    return count;
+}
+
 pub fn check_walkable(position: &RoomPosition) -> bool {
+    return check_walkable_tile(position, false);
+}
+
+// creepがいるマスを通行可能として扱うかどうかを指定できる版.
+pub fn check_walkable_tile(position: &RoomPosition, is_ignore_creeps: bool) -> bool {
     let chk_room = screeps::game::rooms::get(position.room_name());
 
     if let Some(room) = chk_room {
@@ -504,7 +475,9 @@ pub fn check_walkable(position: &RoomPosition) -> bool {
         for object in objects {
             match object {
                 LookResult::Creep(_creep) => {
-                    return false;
+                    if is_ignore_creeps == false {
+                        return false;
+                    }
                 }
 
                 LookResult::Terrain(terrain) => {
@@ -608,6 +581,10 @@ pub fn check_transferable(
 }
 
 pub fn check_repairable(structure: &screeps::objects::Structure) -> bool {
+    if check_road_unused(structure) == true {
+        return false;
+    }
+
     match structure.as_owned() {
         Some(my_structure) => {
             if my_structure.my() == false {
@@ -1166,44 +1143,6 @@ pub fn find_nearest_transfarable_terminal(
     return search_many(creep, find_item_list, option);
 }
 
-pub fn find_nearest_repairable_item_hp(
-    creep: &screeps::objects::Creep,
-    threshold: u32,
-) -> screeps::pathfinder::SearchResults {
-    let item_list = &mut creep
-        .room()
-        .expect("room is not visible to you")
-        .find(find::STRUCTURES);
-
-    {
-        let room_list = game::rooms::values();
-
-        for room_item in room_list.iter() {
-            if room_item.name() != *(&creep.room().expect("room is not visible to you").name()) {
-                let local_list = room_item.find(find::STRUCTURES);
-                item_list.extend(local_list);
-            }
-        }
-    }
-
-    let mut find_item_list = Vec::<(Structure, u32)>::new();
-
-    for chk_item in item_list {
-        if check_repairable(chk_item) {
-            if get_hp(chk_item).unwrap_or(0) <= threshold {
-                find_item_list.push((chk_item.clone(), 3));
-            }
-        }
-    }
-
-    let option = SearchOptions::new()
-        .room_callback(calc_room_cost)
-        .plain_cost(2)
-        .swamp_cost(10);
-
-    return search_many(creep, find_item_list, option);
-}
-
 pub fn find_nearest_repairable_item_except_wall_dying(
     creep: &screeps::objects::Creep,
     threshold: u128,
@@ -1450,6 +1389,11 @@ pub fn find_nearest_active_source(
             }
 
             for chk_item in item_list.iter() {
+                // 周りのマスやWORKが埋まっているsourceは避ける.
+                if check_source_available(creep, chk_item) == false {
+                    continue;
+                }
+
                 let mut object: Position = creep.pos();
                 object.set_x(chk_item.pos().x());
                 object.set_y(chk_item.pos().y());
@@ -1698,8 +1642,6 @@ pub fn find_nearest_stored_source(
                 || chk_item.structure_type() == StructureType::Link
                 || ((chk_item.structure_type() == StructureType::Terminal)
                     && (*resource_kind == ResourceKind::ENERGY))
-                || ((chk_item.structure_type() == StructureType::Lab)
-                    && (*resource_kind == ResourceKind::MINELALS))
             {
                 if check_my_structure(chk_item)
                     || (chk_item.structure_type() == StructureType::Container)
@@ -1946,6 +1888,11 @@ pub fn find_nearest_enemy(
     let mut find_item_list = Vec::<(screeps::objects::Creep, u32)>::new();
 
     for chk_item in item_list.iter() {
+        // 味方は追いかけない.
+        if is_ally(chk_item.owner_name().as_str()) {
+            continue;
+        }
+
         find_item_list.push((chk_item.clone(), range));
     }
 
@@ -1957,6 +1904,26 @@ pub fn find_nearest_enemy(
     return search_many(creep, find_item_list, option);
 }
 
+pub fn find_flee_path_from_enemies(
+    creep: &screeps::objects::Creep,
+    enemies: &Vec<screeps::objects::Creep>,
+    range: u32,
+) -> screeps::pathfinder::SearchResults {
+    let mut find_item_list = Vec::<(screeps::objects::Creep, u32)>::new();
+
+    for chk_item in enemies.iter() {
+        find_item_list.push((chk_item.clone(), range));
+    }
+
+    let option = SearchOptions::new()
+        .room_callback(calc_room_cost)
+        .plain_cost(2)
+        .swamp_cost(10)
+        .flee(true);
+
+    return search_many(creep, find_item_list, option);
+}
+
 pub fn find_nearest_room_controler(
     creep: &screeps::objects::Creep,
 ) -> screeps::pathfinder::SearchResults {
@@ -1993,11 +1960,16 @@ pub fn find_nearest_room_controler(
     return search_many(creep, find_item_list, option);
 }
 
+// 別の部屋へはrouteの部屋だけを通る.
 pub fn find_path(
     creep: &screeps::objects::Creep,
     target_pos: &RoomPosition,
     range: u32,
 ) -> screeps::pathfinder::SearchResults {
+    if creep.pos().room_name() != target_pos.room_name() {
+        return find_route_path_between(&creep.pos(), target_pos, range);
+    }
+
     let option = SearchOptions::new()
         .room_callback(calc_room_cost)
         .plain_cost(2)
@@ -2005,3 +1977,49 @@ pub fn find_path(
 
     return search(creep, target_pos, range, option);
 }
+
+// target_posのrange以内まで1歩進む.
+pub fn move_near(creep: &screeps::objects::Creep, target_pos: &RoomPosition, range: u32) {
+    let res = find_path(&creep, target_pos, range);
+
+    if res.load_local_path().len() > 0 {
+        let res = creep.move_by_path_search_result(&res);
+        if res != ReturnCode::Ok {
+            info!("couldn't move: {:?}", res);
+        }
+    }
+}
+
+pub fn find_path_between(
+    origin: &RoomPosition,
+    target_pos: &RoomPosition,
+    range: u32,
+) -> screeps::pathfinder::SearchResults {
+    let option = SearchOptions::new()
+        .room_callback(calc_room_cost)
+        .plain_cost(2)
+        .swamp_cost(10);
+
+    return search(origin, target_pos, range, option);
+}
+
+// 通ってよい部屋を限定して探す. それ以外の部屋は通行不可.
+pub fn find_path_in_rooms(
+    origin: &RoomPosition,
+    target_pos: &RoomPosition,
+    range: u32,
+    allowed_rooms: &HashSet<RoomName>,
+) -> screeps::pathfinder::SearchResults {
+    let option = SearchOptions::new()
+        .room_callback(|room_name: RoomName| {
+            if allowed_rooms.contains(&room_name) {
+                return calc_room_cost(room_name);
+            }
+            return MultiRoomCostResult::Impassable;
+        })
+        .max_rooms(allowed_rooms.len() as u8)
+        .plain_cost(2)
+        .swamp_cost(10);
+
+    return search(origin, target_pos, range, option);
+}